@@ -1,19 +1,27 @@
 use crate::{
-    gui::{ResetSimEvent, SimConfig, SimStatistics},
+    gui::{
+        color_ramp, run_if_not_paused, AntColorMode, ResetSimEvent, SimClock, SimConfig, SimSettings, SimStatistics,
+    },
+    inspector::SelectedAnts,
+    food::Regrow,
+    map::{FoodSpec, MapSize, Nest, ObstacleMap},
     pheromone::Pheromones,
-    utils::{calc_rotation_angle, get_rand_unit_vec2},
+    recording::AntState,
+    utils::{calc_rotation_angle, get_rand_unit_vec2, get_rand_unit_vec2_with},
     *,
 };
 use bevy::{
-    math::{vec2, vec3},
+    math::vec2,
     prelude::*,
     time::common_conditions::on_timer,
 };
 use rand::{thread_rng, Rng};
-use std::{f32::consts::PI, time::Duration};
+use serde::{Deserialize, Serialize};
+use std::{collections::HashSet, f32::consts::PI, time::Duration};
 
 pub struct AntPlugin;
 
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Serialize, Deserialize)]
 pub enum AntTask {
     FindFood,
     FindHome,
@@ -24,11 +32,11 @@ pub struct Ant;
 #[derive(Component)]
 pub struct CurrentTask(pub AntTask);
 #[derive(Component)]
-struct Velocity(Vec2);
+pub struct Velocity(pub Vec2);
 #[derive(Component)]
 struct Acceleration(Vec2);
 #[derive(Component)]
-struct PhStrength(f32);
+pub struct PhStrength(pub f32);
 
 #[derive(Resource)]
 struct AntScanRadius(f32);
@@ -47,9 +55,95 @@ pub struct AntAnimations {
 #[derive(Component)]
 pub struct DecisionTimer(pub f32);
 
+/// Inputs and outcome of the ant's last steering decision, for the debug views
+#[derive(Component, Default)]
+pub struct SteerState {
+    pub sensors: SensorReadings,
+    pub target: Option<Vec2>,
+    /// Target came from the food/home auto pull rather than the sensors
+    pub is_auto_pull: bool,
+}
+
+/// Sim time of the ant's last food pickup
+#[derive(Component)]
+pub struct PickupTime(pub Option<f32>);
+
+/// Quality of the food being carried home, only meaningful on the way home
+#[derive(Component)]
+pub struct CarriedFood(pub f32);
+
+/// Colony the ant belongs to, there is a single nest for now
+#[derive(Component)]
+pub struct Colony(pub u8);
+
+/// Actual movement of the ant, velocity is always normalised so it can't tell
+/// an ant pinned against a wall from a walking one
+#[derive(Component)]
+pub struct Motion {
+    frame_pos: Vec2,
+    check_pos: Vec2,
+    /// Distance per frame, smoothed
+    pub speed: f32,
+    /// Moved less than STUCK_DISTANCE between the last two checks
+    pub is_stuck: bool,
+}
+
+impl Motion {
+    fn new(pos: Vec2) -> Self {
+        Self {
+            frame_pos: pos,
+            check_pos: pos,
+            speed: ANT_SPEED,
+            is_stuck: false,
+        }
+    }
+
+    /// Restarts the tracking at `pos` after the ant was moved there, e.g. by a rewind
+    pub(crate) fn reset(&mut self, pos: Vec2) {
+        self.frame_pos = pos;
+        self.check_pos = pos;
+        self.is_stuck = false;
+    }
+}
+
+/// Something that happened to an ant, for stepping and debug views
+#[derive(Event, Clone, Copy)]
+pub struct AntEvent {
+    pub entity: Entity,
+    pub pos: Vec2,
+    pub kind: AntEventKind,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum AntEventKind {
+    Pickup,
+    Delivery,
+    WallHit,
+}
+
 #[derive(Component)]
 pub struct Food {
     pub storage: i32,
+    pub pickup_radius: f32,
+    pub quality: f32,
+    pub regrow: Option<Regrow>,
+    /// Fraction of a unit regrown since the last whole one
+    pub regrown: f32,
+    pub name: Option<String>,
+}
+
+impl Food {
+    pub fn spec(&self, pos: Vec2) -> FoodSpec {
+        FoodSpec {
+            x: pos.x,
+            y: pos.y,
+            storage: self.storage,
+            pickup_radius: self.pickup_radius,
+            quality: self.quality,
+            regrow: self.regrow,
+            name: self.name.clone(),
+        }
+    }
 }
 
 impl Plugin for AntPlugin {
@@ -57,6 +151,7 @@ impl Plugin for AntPlugin {
         app.add_systems(Startup, setup)
             .insert_resource(AntScanRadius(INITIAL_ANT_PH_SCAN_RADIUS))
             .insert_resource(AntFollowCameraPos(Vec2::ZERO))
+            .add_event::<AntEvent>()
             .add_systems(
                 Update,
                 (
@@ -68,8 +163,12 @@ impl Plugin for AntPlugin {
                     decay_ph_strength.run_if(on_timer(Duration::from_secs_f32(ANT_PH_STRENGTH_DECAY_INTERVAL))),
                     update_position.after(check_wall_collision),
                     animate_ant,
+                    update_motion.after(update_position),
+                    update_stuck_check.run_if(on_timer(Duration::from_secs_f32(STUCK_CHECK_INTERVAL))),
                 ).run_if(run_if_not_paused)
             )
+            .add_systems(Update, update_food_sprites)
+            .add_systems(Update, update_ant_colors)
             .add_systems(Update, update_camera_follow_pos)
             .add_systems(
                 Update,
@@ -85,14 +184,11 @@ impl Plugin for AntPlugin {
     }
 }
 
-fn run_if_not_paused(settings: Res<crate::gui::SimSettings>) -> bool {
-    !settings.is_paused
-}
-
 fn setup(
     mut commands: Commands,
     asset_server: Res<AssetServer>,
     mut texture_atlases: ResMut<Assets<TextureAtlas>>,
+    nest: Res<Nest>,
 ) {
     let texture_handle = asset_server.load(SPRITE_ANT_SHEET);
     let texture_atlas = TextureAtlas::from_grid(
@@ -122,37 +218,120 @@ fn setup(
     });
 
     for _ in 0..NUM_ANTS {
-        spawn_ant(&mut commands, &walk_handle);
+        spawn_ant(&mut commands, &walk_handle, nest.0);
     }
 }
 
-fn spawn_ant(commands: &mut Commands, texture: &Handle<TextureAtlas>) {
-    commands.spawn((
+pub(crate) fn spawn_ant(commands: &mut Commands, texture: &Handle<TextureAtlas>, nest_pos: Vec2) {
+    commands.spawn(ant_bundle(
+        texture.clone(),
+        nest_pos,
+        get_rand_unit_vec2(),
+        AntTask::FindFood,
+        ANT_INITIAL_PH_STRENGTH,
+    ));
+}
+
+/// Spawns an ant mid-run at `pos`, e.g. when restoring a snapshot
+pub(crate) fn spawn_ant_with_state(
+    commands: &mut Commands,
+    animations: &AntAnimations,
+    state: &AntState,
+    pos: Vec2,
+) -> Entity {
+    let velocity = Vec2::new(state.vx, state.vy);
+    commands
+        .spawn(ant_bundle(task_atlas(state.task, animations), pos, velocity, state.task, state.ph_strength))
+        .insert((PickupTime(state.pickup_time), CarriedFood(state.carried)))
+        .id()
+}
+
+fn ant_bundle(
+    texture: Handle<TextureAtlas>,
+    pos: Vec2,
+    velocity: Vec2,
+    task: AntTask,
+    ph_strength: f32,
+) -> impl Bundle {
+    (
         SpriteSheetBundle {
-            texture_atlas: texture.clone(),
+            texture_atlas: texture,
             sprite: TextureAtlasSprite::new(0),
-            transform: Transform::from_xyz(HOME_LOCATION.0, HOME_LOCATION.1, ANT_Z_INDEX)
+            transform: Transform::from_xyz(pos.x, pos.y, ANT_Z_INDEX)
+                .with_rotation(Quat::from_rotation_z(velocity.y.atan2(velocity.x)))
                 .with_scale(Vec3::splat(ANT_SPRITE_SCALE)),
             ..Default::default()
         },
         Ant,
-        CurrentTask(AntTask::FindFood),
-        Velocity(get_rand_unit_vec2()),
+        CurrentTask(task),
+        Velocity(velocity),
         Acceleration(Vec2::ZERO),
-        PhStrength(ANT_INITIAL_PH_STRENGTH),
+        PhStrength(ph_strength),
         AnimationTimer(Timer::from_seconds(ANT_ANIMATION_SPEED, TimerMode::Repeating)),
         DecisionTimer(thread_rng().gen_range(0.0..0.1)),
+        SteerState::default(),
+        PickupTime(None),
+        CarriedFood(FOOD_QUALITY),
+        Colony(0),
+        Motion::new(pos),
+    )
+}
+
+/// Sprite sheet for an ant doing `task`, with or without food in its jaws
+pub(crate) fn task_atlas(task: AntTask, animations: &AntAnimations) -> Handle<TextureAtlas> {
+    match task {
+        AntTask::FindFood => animations.walk.clone(),
+        AntTask::FindHome => animations.walk_food.clone(),
+    }
+}
+
+pub fn spawn_food(commands: &mut Commands, asset_server: &AssetServer, spec: FoodSpec) {
+    commands.spawn((
+        SpriteBundle {
+            texture: asset_server.load(SPRITE_FOOD),
+            transform: Transform::from_xyz(spec.x, spec.y, 2.0)
+                .with_scale(Vec3::splat(food_sprite_scale(spec.storage))),
+            sprite: Sprite {
+                color: Color::rgb(1.5, 1.5, 1.5),
+                ..default()
+            },
+            ..default()
+        },
+        Food {
+            storage: spec.storage,
+            pickup_radius: spec.pickup_radius,
+            quality: spec.quality,
+            regrow: spec.regrow,
+            regrown: 0.0,
+            name: spec.name,
+        },
     ));
 }
 
+/// Sprite scale for a source with `storage` food left, the area follows the storage
+fn food_sprite_scale(storage: i32) -> f32 {
+    let fraction = storage.max(0) as f32 / FOOD_STORAGE as f32;
+    (FOOD_SPRITE_SCALE * fraction.sqrt()).clamp(FOOD_SPRITE_MIN_SCALE, FOOD_SPRITE_MAX_SCALE)
+}
+
+fn update_food_sprites(mut food_query: Query<(&Food, &mut Transform), Changed<Food>>) {
+    for (food, mut transform) in food_query.iter_mut() {
+        transform.scale = Vec3::splat(food_sprite_scale(food.storage));
+    }
+}
+
 fn reset_ants(
     mut commands: Commands,
     mut events: EventReader<ResetSimEvent>,
     ant_query: Query<Entity, With<Ant>>,
     ant_animations: Res<AntAnimations>,
     config: Res<SimConfig>,
+    nest: Res<Nest>,
+    mut stats: ResMut<SimStatistics>,
 ) {
     for _ in events.iter() {
+        stats.food_delivered = 0.0;
+
         // Despawn all ants
         for entity in ant_query.iter() {
             commands.entity(entity).despawn();
@@ -160,7 +339,7 @@ fn reset_ants(
 
         // Spawn new ants
         for _ in 0..config.ants_count {
-             spawn_ant(&mut commands, &ant_animations.walk);
+             spawn_ant(&mut commands, &ant_animations.walk, nest.0);
         }
     }
 }
@@ -170,8 +349,11 @@ fn update_ant_count(
     ant_query: Query<Entity, With<Ant>>,
     config: Res<SimConfig>,
     ant_animations: Res<AntAnimations>,
+    nest: Res<Nest>,
+    settings: Res<crate::gui::SimSettings>,
 ) {
-    if !config.is_changed() {
+    // Replay sets the ant count from the recording
+    if !config.is_changed() || settings.is_replaying {
         return;
     }
 
@@ -181,7 +363,7 @@ fn update_ant_count(
     if current_count < target_count {
         let diff = target_count - current_count;
         for _ in 0..diff {
-            spawn_ant(&mut commands, &ant_animations.walk);
+            spawn_ant(&mut commands, &ant_animations.walk, nest.0);
         }
     } else if current_count > target_count {
         let diff = current_count - target_count;
@@ -218,9 +400,15 @@ fn update_scan_radius(mut scan_radius: ResMut<AntScanRadius>) {
 
 fn update_camera_follow_pos(
     ant_query: Query<&Transform, With<Ant>>,
+    selected: Res<SelectedAnts>,
     mut follow_pos: ResMut<AntFollowCameraPos>,
 ) {
-    if let Some(transform) = ant_query.iter().next() {
+    // Follow the selected ant, or any ant when nothing is selected
+    let followed = match selected.primary() {
+        Some(entity) => ant_query.get(entity).ok(),
+        None => ant_query.iter().next(),
+    };
+    if let Some(transform) = followed {
         follow_pos.0 = transform.translation.truncate();
     }
 }
@@ -246,128 +434,200 @@ fn get_steering_force(target: Vec2, current: Vec2, velocity: Vec2) -> Vec2 {
     steering * 0.2
 }
 
+#[allow(clippy::too_many_arguments, clippy::type_complexity)]
 fn periodic_direction_update(
-    mut ant_query: Query<(&mut Acceleration, &Transform, &CurrentTask, &Velocity, &mut DecisionTimer), With<Ant>>,
+    mut ant_query: Query<
+        (&mut Acceleration, &Transform, &CurrentTask, &Velocity, &mut DecisionTimer, &mut SteerState),
+        With<Ant>,
+    >,
     mut pheromones: ResMut<Pheromones>,
     mut stats: ResMut<SimStatistics>,
     _scan_radius: Res<AntScanRadius>,
     config: Res<SimConfig>,
     time: Res<Time>,
-    food_query: Query<&Transform, With<Food>>,
+    food_query: Query<(&Transform, &Food)>,
     obstacle_map: Res<crate::map::ObstacleMap>,
     map_size: Res<crate::map::MapSize>,
+    nest: Res<Nest>,
 ) {
     (stats.food_cache_size, stats.home_cache_size) = pheromones.clear_cache();
+    let home_pos = nest.0;
+    let mut rng = thread_rng();
 
-    for (mut acceleration, transform, current_task, velocity, mut timer) in ant_query.iter_mut() {
+    for (mut acceleration, transform, current_task, velocity, mut timer, mut steer) in ant_query.iter_mut() {
         timer.0 -= time.delta_seconds();
         if timer.0 > 0.0 {
             continue;
         }
         // Reset timer with some randomness to prevent syncing
-        timer.0 = config.ant_update_interval + thread_rng().gen_range(-0.01..0.01);
-
-        let current_pos = transform.translation;
-        let mut target = None;
-
-        // If ant is close to food/home, pull it towards itself
-        match current_task.0 {
-            AntTask::FindFood => {
-                let pull_radius_sq = config.ant_target_auto_pull_radius * config.ant_target_auto_pull_radius;
-                let mut best_dist = pull_radius_sq;
-                // Find closest food
-                for food_transform in food_query.iter() {
-                    let food_pos = food_transform.translation.truncate();
-                    let dist_sq = transform.translation.distance_squared(food_transform.translation);
-                    if dist_sq <= best_dist {
-                         // Check Line of Sight
-                         if obstacle_map.has_line_of_sight(current_pos.truncate(), food_pos, map_size.width, map_size.height) {
-                             best_dist = dist_sq;
-                             target = Some(food_pos);
-                         }
-                    }
+        timer.0 = config.ant_update_interval + rng.gen_range(-0.01..0.01);
+
+        let current_pos = transform.translation.truncate();
+        let pull_target = find_pull_target(
+            &current_task.0,
+            current_pos,
+            food_query
+                .iter()
+                .filter(|(_, food)| food.storage > 0)
+                .map(|(t, _)| t.translation.truncate()),
+            home_pos,
+            &config,
+            &obstacle_map,
+            &map_size,
+        );
+        let sensors = sensor_readings(&current_task.0, current_pos, velocity.0, &pheromones, &config);
+        let target = pull_target.or_else(|| sensors.steer_target(current_pos, config.ant_sensor_dist));
+
+        *steer = SteerState {
+            sensors,
+            target,
+            is_auto_pull: pull_target.is_some(),
+        };
+        let terrain = obstacle_map.terrain_at(current_pos.x, current_pos.y, map_size.width, map_size.height);
+        acceleration.0 +=
+            steer_acceleration(target, current_pos, velocity.0, &config, terrain.turn_factor(), &mut rng);
+    }
+}
+
+/// If the ant is close to food/home and can see it, pull it straight towards it
+pub(crate) fn find_pull_target(
+    task: &AntTask,
+    pos: Vec2,
+    food_positions: impl Iterator<Item = Vec2>,
+    home_pos: Vec2,
+    config: &SimConfig,
+    obstacle_map: &ObstacleMap,
+    map_size: &MapSize,
+) -> Option<Vec2> {
+    let pull_radius_sq = config.ant_target_auto_pull_radius * config.ant_target_auto_pull_radius;
+
+    match task {
+        AntTask::FindFood => {
+            let mut target = None;
+            let mut best_dist = pull_radius_sq;
+            // Find closest food
+            for food_pos in food_positions {
+                let dist_sq = pos.distance_squared(food_pos);
+                // Check Line of Sight
+                if dist_sq <= best_dist
+                    && obstacle_map.has_line_of_sight(pos, food_pos, map_size.width, map_size.height)
+                {
+                    best_dist = dist_sq;
+                    target = Some(food_pos);
                 }
             }
-            AntTask::FindHome => {
-                let home_pos = vec2(HOME_LOCATION.0, HOME_LOCATION.1);
-                let dist_to_home = transform.translation.distance_squared(vec3(
-                    HOME_LOCATION.0,
-                    HOME_LOCATION.1,
-                    0.0,
-                ));
-                if dist_to_home <= config.ant_target_auto_pull_radius * config.ant_target_auto_pull_radius {
-                    // Check LOS
-                     if obstacle_map.has_line_of_sight(current_pos.truncate(), home_pos, map_size.width, map_size.height) {
-                         target = Some(home_pos);
-                     }
-                }
+            target
+        }
+        AntTask::FindHome => {
+            if pos.distance_squared(home_pos) <= pull_radius_sq
+                && obstacle_map.has_line_of_sight(pos, home_pos, map_size.width, map_size.height)
+            {
+                Some(home_pos)
+            } else {
+                None
             }
-        };
-
-        if target.is_none() {
+        }
+    }
+}
 
+/// Pheromone strength sampled at the left, front and right sensors
+#[derive(Clone, Copy, Default, Debug)]
+pub struct SensorReadings {
+    pub positions: [Vec2; 3],
+    pub values: [f32; 3],
+}
 
-            // Sensor Based Steering
-            // 1. Calculate Sensor Positions
-            let (pos_l, pos_r, pos_f) = calculate_sensor_positions(
-                current_pos.truncate(), 
-                velocity.0,
-                config.ant_sensor_dist,
-                config.ant_sensor_angle,
-            );
-            
-            // 2. Sample Strength
-             // Optimization: We know which grid we need.
-            let grid = match current_task.0 {
-                AntTask::FindFood => &pheromones.to_food,
-                AntTask::FindHome => &pheromones.to_home,
-            };
-            
-            // Sample sensors
-            let v_l = grid.sample_sensor_sum(pos_l, ANT_SENSOR_RADIUS);
-            let v_r = grid.sample_sensor_sum(pos_r, ANT_SENSOR_RADIUS);
-            let v_f = grid.sample_sensor_sum(pos_f, ANT_SENSOR_RADIUS);
-            // 3. Normal Steering
-            // Use squared values for sharper gradients
-            let v_l = v_l.powf(2.0);
-            let v_r = v_r.powf(2.0);
-            let v_f = v_f.powf(2.0);
-
-            if v_l + v_r + v_f > 0.0 {
-                 let dir_l = (pos_l - current_pos.truncate()).normalize();
-                 let dir_r = (pos_r - current_pos.truncate()).normalize();
-                 let dir_f = (pos_f - current_pos.truncate()).normalize();
-
-                 // Simple Weighted Sum = Forward Bias (due to geometry)
-                 let steer_dir = (dir_l * v_l + dir_r * v_r + dir_f * v_f).normalize_or_zero();
-                 
-                 if steer_dir != Vec2::ZERO {
-                      target = Some(current_pos.truncate() + steer_dir * config.ant_sensor_dist);
-                 }
-            }
+impl SensorReadings {
+    /// Point ahead of the ant in the direction of the strongest signal
+    pub fn steer_target(&self, pos: Vec2, sensor_dist: f32) -> Option<Vec2> {
+        // Use squared values for sharper gradients
+        let weights = self.values.map(|v| v.powf(2.0));
+        if weights.iter().sum::<f32>() <= 0.0 {
+            return None;
         }
- 
-        if target.is_none() {
-            // No signal? Random Search.
-            acceleration.0 += get_rand_unit_vec2() * config.ant_turn_randomness;
-            continue;
+
+        // Simple Weighted Sum = Forward Bias (due to geometry)
+        let steer_dir = self
+            .positions
+            .iter()
+            .zip(weights)
+            .map(|(p, w)| (*p - pos).normalize() * w)
+            .sum::<Vec2>()
+            .normalize_or_zero();
+        if steer_dir == Vec2::ZERO {
+            return None;
         }
 
-        let steering_force = get_steering_force(
-            target.unwrap(),
-            transform.translation.truncate(),
-            velocity.0,
-        );
+        Some(pos + steer_dir * sensor_dist)
+    }
+}
 
-        let mut rng = rand::thread_rng();
-        acceleration.0 += steering_force * rng.gen_range(0.8..=1.2) * config.ant_steering_force_factor;
-        // Reduced lateral wiggle on established trails for stability
-        acceleration.0 += get_rand_unit_vec2() * (config.ant_turn_randomness * 0.1);
-        acceleration.0 += get_rand_unit_vec2() * (config.ant_turn_randomness * 0.33); 
+/// Samples the pheromone grid for the ant's current task at its three sensors
+pub(crate) fn sensor_readings(
+    task: &AntTask,
+    pos: Vec2,
+    velocity: Vec2,
+    pheromones: &Pheromones,
+    config: &SimConfig,
+) -> SensorReadings {
+    let (pos_l, pos_r, pos_f) = calculate_sensor_positions(
+        pos,
+        velocity,
+        config.ant_sensor_dist,
+        config.ant_sensor_angle,
+    );
+
+    // Optimization: We know which grid we need.
+    let grid = match task {
+        AntTask::FindFood => &pheromones.to_food,
+        AntTask::FindHome => &pheromones.to_home,
+    };
+
+    let positions = [pos_l, pos_f, pos_r];
+    SensorReadings {
+        positions,
+        values: positions.map(|p| grid.sample_sensor_sum(p, ANT_SENSOR_RADIUS)),
     }
 }
 
-fn calculate_sensor_positions(
+/// Sensor based steering, returns a point ahead of the ant in the direction of the
+/// strongest pheromone signal for its current task
+pub(crate) fn sensor_steer_target(
+    task: &AntTask,
+    pos: Vec2,
+    velocity: Vec2,
+    pheromones: &Pheromones,
+    config: &SimConfig,
+) -> Option<Vec2> {
+    sensor_readings(task, pos, velocity, pheromones, config).steer_target(pos, config.ant_sensor_dist)
+}
+
+/// Acceleration for one steering decision, random search when there is no target.
+/// `turn_factor` scales the random part, from the terrain under the ant
+pub(crate) fn steer_acceleration<R: Rng>(
+    target: Option<Vec2>,
+    pos: Vec2,
+    velocity: Vec2,
+    config: &SimConfig,
+    turn_factor: f32,
+    rng: &mut R,
+) -> Vec2 {
+    let randomness = config.ant_turn_randomness * turn_factor;
+    let Some(target) = target else {
+        // No signal? Random Search.
+        return get_rand_unit_vec2_with(rng) * randomness;
+    };
+
+    let steering_force = get_steering_force(target, pos, velocity);
+
+    let mut acceleration = steering_force * rng.gen_range(0.8..=1.2) * config.ant_steering_force_factor;
+    // Reduced lateral wiggle on established trails for stability
+    acceleration += get_rand_unit_vec2_with(rng) * (randomness * 0.1);
+    acceleration += get_rand_unit_vec2_with(rng) * (randomness * 0.33);
+    acceleration
+}
+
+pub(crate) fn calculate_sensor_positions(
     current_pos: Vec2, 
     velocity: Vec2,
     sensor_dist: f32,
@@ -405,15 +665,21 @@ fn calculate_sensor_positions(
 
 fn debug_sensors(
     mut gizmos: Gizmos,
-    ant_query: Query<(&Transform, &Velocity), With<Ant>>,
+    ant_query: Query<(Entity, &Transform, &Velocity, &SteerState), With<Ant>>,
     settings: Res<crate::gui::SimSettings>,
+    selected: Res<SelectedAnts>,
     config: Res<SimConfig>,
+    obstacle_map: Res<ObstacleMap>,
+    map_size: Res<MapSize>,
 ) {
     if !settings.is_show_ants_path && !settings.is_show_sensor_radius {
         return;
     }
 
-    for (transform, velocity) in ant_query.iter() {
+    for (entity, transform, velocity, steer) in ant_query.iter() {
+        if settings.is_debug_selected_only && !selected.contains(entity) {
+            continue;
+        }
         let pos = transform.translation.truncate();
         
         if settings.is_show_ants_path {
@@ -427,6 +693,32 @@ fn debug_sensors(
             gizmos.line_2d(pos, l, Color::RED);   // Left
             gizmos.line_2d(pos, r, Color::BLUE);  // Right
             gizmos.line_2d(pos, f, Color::GREEN); // Front;
+
+            // Sampled values at the last decision, relative to the strongest sensor
+            let max_value = steer.sensors.values.iter().fold(0.0f32, |a, b| a.max(*b));
+            if max_value > 0.0 {
+                let colors = [Color::RED, Color::GREEN, Color::BLUE];
+                for ((p, v), color) in steer.sensors.positions.iter().zip(steer.sensors.values).zip(colors) {
+                    gizmos.circle_2d(*p, 1.0 + 5.0 * v / max_value, color);
+                }
+            }
+
+            // Chosen direction, magenta when the auto pull took over from the sensors
+            if let Some(target) = steer.target {
+                let color = if steer.is_auto_pull { Color::FUCHSIA } else { Color::WHITE };
+                let dir = (target - pos).normalize_or_zero();
+                gizmos.line_2d(pos, pos + dir * config.ant_sensor_dist * 1.5, color);
+                if steer.is_auto_pull {
+                    gizmos.line_2d(pos, target, color.with_a(0.3));
+                }
+            }
+
+            if let Some(probes) = avoidance_probes(pos, velocity.0, &obstacle_map, &map_size) {
+                for (p, hit) in probes.positions.iter().zip(probes.hits) {
+                    let color = if hit { Color::ORANGE_RED } else { Color::rgba(0.7, 0.7, 0.7, 0.4) };
+                    gizmos.circle_2d(*p, AVOIDANCE_PROBE_RADIUS, color);
+                }
+            }
         }
 
         if settings.is_show_sensor_radius {
@@ -435,30 +727,36 @@ fn debug_sensors(
     }
 }
 
+#[allow(clippy::too_many_arguments, clippy::type_complexity)]
 fn check_home_food_collisions(
     mut commands: Commands,
     mut ant_query: Query<
         (
+            Entity,
             &Transform,
-            &mut TextureAtlasSprite,
             &mut Velocity,
             &mut CurrentTask,
             &mut PhStrength,
             &mut Handle<TextureAtlas>,
+            &mut PickupTime,
+            &mut CarriedFood,
         ),
         With<Ant>,
     >,
     mut food_query: Query<(Entity, &Transform, &mut Food), Without<Ant>>,
     ant_animations: Res<AntAnimations>,
+    nest: Res<Nest>,
+    clock: Res<SimClock>,
+    mut stats: ResMut<SimStatistics>,
+    mut ant_events: EventWriter<AntEvent>,
 ) {
-    for (transform, mut sprite, mut velocity, mut ant_task, mut ph_strength, mut atlas_handle) in
+    for (entity, transform, mut velocity, mut ant_task, mut ph_strength, mut atlas_handle, mut pickup_time, mut carried) in
         ant_query.iter_mut()
     {
+        let pos = transform.translation.truncate();
+
         // Home collision
-        let dist_to_home =
-            transform
-                .translation
-                .distance_squared(vec3(HOME_LOCATION.0, HOME_LOCATION.1, 0.0));
+        let dist_to_home = pos.distance_squared(nest.0);
         if dist_to_home < HOME_RADIUS * HOME_RADIUS {
             // If we were bringing food home, drop it and turn around
             match ant_task.0 {
@@ -472,8 +770,9 @@ fn check_home_food_collisions(
                     // 1. Drop Food (Switch Task)
                     ant_task.0 = AntTask::FindFood;
                     ph_strength.0 = ANT_INITIAL_PH_STRENGTH;
-                    *atlas_handle = ant_animations.walk.clone();
-                    sprite.color = Color::rgb(1.0, 1.0, 2.5);
+                    *atlas_handle = task_atlas(ant_task.0, &ant_animations);
+                    stats.food_delivered += carried.0;
+                    ant_events.send(AntEvent { entity, pos, kind: AntEventKind::Delivery });
 
                     // 2. Turn Around to go back to where we came from
                     // Reflect velocity perfectly to head back out the "entrance" we came in
@@ -492,17 +791,21 @@ fn check_home_food_collisions(
             for (food_entity, food_transform, mut food) in food_query.iter_mut() {
                 let dist_to_food = transform.translation.distance_squared(food_transform.translation);
                 
-                if dist_to_food < FOOD_PICKUP_RADIUS * FOOD_PICKUP_RADIUS {
+                // Eaten regrowing sources wait for their storage to come back
+                if food.storage > 0 && dist_to_food < food.pickup_radius * food.pickup_radius {
                      // Collided with food
                      velocity.0 *= -1.0;
                      ant_task.0 = AntTask::FindHome;
-                     ph_strength.0 = ANT_INITIAL_PH_STRENGTH;
+                     // Richer food lays a stronger and longer trail back
+                     ph_strength.0 = ANT_INITIAL_PH_STRENGTH * food.quality;
+                     carried.0 = food.quality;
                      
-                     *atlas_handle = ant_animations.walk_food.clone();
-                     sprite.color = Color::rgb(1.0, 2.0, 1.0);
+                     *atlas_handle = task_atlas(ant_task.0, &ant_animations);
+                     pickup_time.0 = Some(clock.elapsed_secs);
+                     ant_events.send(AntEvent { entity, pos, kind: AntEventKind::Pickup });
                      
                      food.storage -= 1;
-                     if food.storage <= 0 {
+                     if food.storage <= 0 && food.regrow.is_none() {
                          commands.entity(food_entity).despawn();
                      }
                      
@@ -514,6 +817,71 @@ fn check_home_food_collisions(
     }
 }
 
+fn update_motion(mut ant_query: Query<(&Transform, &mut Motion), With<Ant>>) {
+    for (transform, mut motion) in ant_query.iter_mut() {
+        let pos = transform.translation.truncate();
+        let moved = pos.distance(motion.frame_pos);
+        motion.speed += (moved - motion.speed) * 0.1;
+        motion.frame_pos = pos;
+    }
+}
+
+fn update_stuck_check(mut ant_query: Query<(&Transform, &mut Motion), With<Ant>>) {
+    for (transform, mut motion) in ant_query.iter_mut() {
+        let pos = transform.translation.truncate();
+        motion.is_stuck = pos.distance_squared(motion.check_pos) < STUCK_DISTANCE * STUCK_DISTANCE;
+        motion.check_pos = pos;
+    }
+}
+
+/// Colour for an ant's task, also used by the legend
+pub fn task_color(task: AntTask) -> Color {
+    match task {
+        AntTask::FindFood => Color::rgb(1.0, 1.0, 2.5),
+        AntTask::FindHome => Color::rgb(1.0, 2.0, 1.0),
+    }
+}
+
+pub fn colony_color(colony: u8) -> Color {
+    const PALETTE: [Color; 4] = [
+        Color::rgb(2.0, 1.2, 0.4),
+        Color::rgb(0.6, 1.2, 2.0),
+        Color::rgb(2.0, 0.6, 1.6),
+        Color::rgb(1.0, 2.0, 0.6),
+    ];
+    PALETTE[colony as usize % PALETTE.len()]
+}
+
+pub const STUCK_COLOR: Color = Color::rgb(2.5, 0.3, 0.3);
+pub const MOVING_COLOR: Color = Color::rgb(0.7, 0.7, 0.7);
+pub const NEVER_PICKED_UP_COLOR: Color = Color::rgb(0.5, 0.5, 0.5);
+
+#[allow(clippy::type_complexity)]
+fn update_ant_colors(
+    mut ant_query: Query<
+        (&mut TextureAtlasSprite, &CurrentTask, &PhStrength, &PickupTime, &Colony, &Motion),
+        With<Ant>,
+    >,
+    settings: Res<SimSettings>,
+    clock: Res<SimClock>,
+) {
+    for (mut sprite, task, ph_strength, pickup_time, colony, motion) in ant_query.iter_mut() {
+        sprite.color = match settings.color_mode {
+            AntColorMode::Task => task_color(task.0),
+            AntColorMode::PhStrength => color_ramp(ph_strength.0 / ANT_INITIAL_PH_STRENGTH),
+            AntColorMode::TimeSincePickup => match pickup_time.0 {
+                // Recent pickups are hot
+                Some(t) => color_ramp(1.0 - (clock.elapsed_secs - t) / PICKUP_COLOR_SECS),
+                None => NEVER_PICKED_UP_COLOR,
+            },
+            AntColorMode::Colony => colony_color(colony.0),
+            AntColorMode::Speed => color_ramp(motion.speed / ANT_SPEED),
+            AntColorMode::Stuck if motion.is_stuck => STUCK_COLOR,
+            AntColorMode::Stuck => MOVING_COLOR,
+        };
+    }
+}
+
 fn animate_ant(
     time: Res<Time>,
     mut query: Query<(&mut AnimationTimer, &mut TextureAtlasSprite), With<Ant>>,
@@ -527,164 +895,249 @@ fn animate_ant(
 }
 
 fn check_wall_collision(
-    mut ant_query: Query<(&mut Transform, &mut Velocity, &mut Acceleration), With<Ant>>,
+    mut ant_query: Query<(Entity, &mut Transform, &mut Velocity, &mut Acceleration), With<Ant>>,
     obstacle_map: Res<crate::map::ObstacleMap>,
     map_size: Res<crate::map::MapSize>,
+    mut ant_events: EventWriter<AntEvent>,
+    mut in_contact: Local<HashSet<Entity>>,
 ) {
+    let mut rng = thread_rng();
+
+    // Ants touching a wall last frame, an ant scraping along a wall only hits it once
+    let was_in_contact = std::mem::take(&mut *in_contact);
+    for (entity, mut transform, mut velocity, mut acceleration) in ant_query.iter_mut() {
+        let mut pos = transform.translation.truncate();
+        if resolve_wall_collision(&mut pos, &mut velocity.0, &mut acceleration.0, &obstacle_map, &map_size, &mut rng) {
+            if !was_in_contact.contains(&entity) {
+                ant_events.send(AntEvent { entity, pos, kind: AntEventKind::WallHit });
+            }
+            in_contact.insert(entity);
+        }
+        transform.translation.x = pos.x;
+        transform.translation.y = pos.y;
+    }
+}
+
+/// Keeps the ant inside the map and off obstacles, returns true if it bumped into something
+pub(crate) fn resolve_wall_collision<R: Rng>(
+    pos: &mut Vec2,
+    velocity: &mut Vec2,
+    acceleration: &mut Vec2,
+    obstacle_map: &ObstacleMap,
+    map_size: &MapSize,
+    rng: &mut R,
+) -> bool {
     let w = map_size.width;
     let h = map_size.height;
 
-    for (mut transform, mut velocity, mut acceleration) in ant_query.iter_mut() {
-        // wall rebound
-        let border = 20.0;
-        let top_left = (-w / 2.0, h / 2.0);
-        let bottom_right = (w / 2.0, -h / 2.0);
-        
-        let pos = transform.translation;
-        
-        let mut hit_wall = false;
-        
-        // Wall Clamping
-        if pos.x < top_left.0 + border {
-            transform.translation.x = top_left.0 + border;
-            hit_wall = true;
-        } else if pos.x >= bottom_right.0 - border {
-            transform.translation.x = bottom_right.0 - border;
-            hit_wall = true;
-        }
-        
-        if pos.y > top_left.1 - border {
-            transform.translation.y = top_left.1 - border;
-            hit_wall = true;
-        } else if pos.y < bottom_right.1 + border {
-            transform.translation.y = bottom_right.1 + border;
-            hit_wall = true;
-        }
+    // wall rebound
+    let border = 20.0;
+    let top_left = (-w / 2.0, h / 2.0);
+    let bottom_right = (w / 2.0, -h / 2.0);
 
-        let mut hit_obstacle = false;
-        if !hit_wall {
-             // Check obstacle map with radius
-             // Radius reduced to 10.0 for tighter visual collision
-             if obstacle_map.is_obstacle_in_radius(pos.x, pos.y, 10.0, w, h) {
-                 hit_obstacle = true;
-                 
-                 // Push ant back slightly to unstuck (opposite to current velocity)
-                 let push_dir = -velocity.0.normalize_or_zero();
-                 transform.translation.x += push_dir.x * 2.0;
-                 transform.translation.y += push_dir.y * 2.0;
-             }
-        }
+    let old_pos = *pos;
 
-        if hit_wall || hit_obstacle {
-            // "Stop and observe" behavior
-            // Heavily dampen velocity and reverse it slightly to detach from wall
-            velocity.0 = -velocity.0 * 0.2;
-            
-            // Clear acceleration
-            acceleration.0 = Vec2::ZERO;
-
-            // Add a small random rotation to velocity to simulate "looking for new direction"
-            let mut rng = thread_rng();
-            let jitter_angle: f32 = rng.gen_range(-1.0..1.0); 
-            let cos_a = jitter_angle.cos();
-            let sin_a = jitter_angle.sin();
-            velocity.0 = vec2(
-                velocity.0.x * cos_a - velocity.0.y * sin_a,
-                velocity.0.x * sin_a + velocity.0.y * cos_a
-            );
-        }
+    let mut hit_wall = false;
+
+    // Wall Clamping
+    if old_pos.x < top_left.0 + border {
+        pos.x = top_left.0 + border;
+        hit_wall = true;
+    } else if old_pos.x >= bottom_right.0 - border {
+        pos.x = bottom_right.0 - border;
+        hit_wall = true;
+    }
+
+    if old_pos.y > top_left.1 - border {
+        pos.y = top_left.1 - border;
+        hit_wall = true;
+    } else if old_pos.y < bottom_right.1 + border {
+        pos.y = bottom_right.1 + border;
+        hit_wall = true;
+    }
+
+    let mut hit_obstacle = false;
+    // Check obstacle map with radius
+    // Radius reduced to 10.0 for tighter visual collision
+    if !hit_wall && obstacle_map.is_obstacle_in_radius(old_pos.x, old_pos.y, 10.0, w, h) {
+        hit_obstacle = true;
+
+        // Push ant back slightly to unstuck (opposite to current velocity)
+        let push_dir = -velocity.normalize_or_zero();
+        *pos += push_dir * 2.0;
     }
+
+    if hit_wall || hit_obstacle {
+        // "Stop and observe" behavior
+        // Heavily dampen velocity and reverse it slightly to detach from wall
+        *velocity = -*velocity * 0.2;
+
+        // Clear acceleration
+        *acceleration = Vec2::ZERO;
+
+        // Add a small random rotation to velocity to simulate "looking for new direction"
+        let jitter_angle: f32 = rng.gen_range(-1.0..1.0);
+        let cos_a = jitter_angle.cos();
+        let sin_a = jitter_angle.sin();
+        *velocity = vec2(
+            velocity.x * cos_a - velocity.y * sin_a,
+            velocity.x * sin_a + velocity.y * cos_a
+        );
+    }
+
+    hit_wall || hit_obstacle
 }
 
 fn update_position(
     mut ant_query: Query<(&mut Transform, &mut Velocity, &mut Acceleration), With<Ant>>,
+    obstacle_map: Res<ObstacleMap>,
+    map_size: Res<MapSize>,
 ) {
     for (mut transform, mut velocity, mut acceleration) in ant_query.iter_mut() {
         let old_pos = transform.translation;
+        let speed = ant_speed(&obstacle_map, &map_size, old_pos.truncate());
+        let new_pos = integrate_position(old_pos.truncate(), &mut velocity.0, &mut acceleration.0, speed);
+        transform.translation.x = new_pos.x;
+        transform.translation.y = new_pos.y;
 
-        if !acceleration.0.is_nan() {
-            velocity.0 = (velocity.0 + acceleration.0).normalize();
-            let new_translation =
-                transform.translation + vec3(velocity.0.x, velocity.0.y, 0.0) * ANT_SPEED;
-            if !new_translation.is_nan() {
-                transform.translation = new_translation;
-            }
-        }
-
-        acceleration.0 = Vec2::ZERO;
         transform.rotation =
             Quat::from_rotation_z(calc_rotation_angle(old_pos, transform.translation) + PI);
     }
 }
 
+/// Step length of an ant at the given position, slower on sand, water and rough ground
+pub(crate) fn ant_speed(obstacle_map: &ObstacleMap, map_size: &MapSize, pos: Vec2) -> f32 {
+    let terrain = obstacle_map.terrain_at(pos.x, pos.y, map_size.width, map_size.height);
+    // Ants pushed into a wall still need to move to get out of it
+    if terrain.is_obstacle() {
+        return ANT_SPEED;
+    }
+    ANT_SPEED * terrain.speed_factor()
+}
+
+/// Applies the accumulated acceleration and moves the ant one step forward
+pub(crate) fn integrate_position(pos: Vec2, velocity: &mut Vec2, acceleration: &mut Vec2, speed: f32) -> Vec2 {
+    let mut new_pos = pos;
+
+    if !acceleration.is_nan() {
+        *velocity = (*velocity + *acceleration).normalize();
+        let new_translation = pos + *velocity * speed;
+        if !new_translation.is_nan() {
+            new_pos = new_translation;
+        }
+    }
+
+    *acceleration = Vec2::ZERO;
+    new_pos
+}
+
 fn avoid_obstacles(
     mut ant_query: Query<(&Transform, &Velocity, &mut Acceleration), With<Ant>>,
     obstacle_map: Res<crate::map::ObstacleMap>,
     map_size: Res<crate::map::MapSize>,
 ) {
-    let w = map_size.width;
-    let h = map_size.height;
+    let mut rng = thread_rng();
+
+    for (transform, velocity, mut acceleration) in ant_query.iter_mut() {
+        acceleration.0 += obstacle_avoidance(
+            transform.translation.truncate(),
+            velocity.0,
+            &obstacle_map,
+            &map_size,
+            &mut rng,
+        );
+    }
+}
+
+/// Wall probes ahead of the ant, left/center/right
+#[derive(Clone, Copy, Default, Debug)]
+pub struct AvoidanceProbes {
+    pub positions: [Vec2; 3],
+    pub hits: [bool; 3],
+}
+
+/// Samples the obstacle map ahead of the ant, None if it is standing still
+pub(crate) fn avoidance_probes(
+    pos: Vec2,
+    velocity: Vec2,
+    obstacle_map: &ObstacleMap,
+    map_size: &MapSize,
+) -> Option<AvoidanceProbes> {
     // Look ahead distance reduced to 20.0 for closer reaction
     let look_ahead = 20.0;
     // Sensor probe offset angle (radians)
     let probe_angle: f32 = 0.5; // ~30 degrees
 
-    for (transform, velocity, mut acceleration) in ant_query.iter_mut() {
-        if velocity.0.length_squared() < 0.1 { continue; }
-        
-        let forward = velocity.0.normalize();
-        let pos = transform.translation.truncate();
-        
-        // Center probe
-        let center_probe = pos + forward * look_ahead;
-        
-        // Left probe
-        let cos_a = probe_angle.cos();
-        let sin_a = probe_angle.sin();
-        let left_dir = vec2(
-            forward.x * cos_a - forward.y * sin_a,
-            forward.x * sin_a + forward.y * cos_a
-        );
-        let left_probe = pos + left_dir * look_ahead;
-        
-        // Right probe
-        let right_dir = vec2(
-            forward.x * cos_a + forward.y * sin_a,
-            -forward.x * sin_a + forward.y * cos_a
-        );
-        let right_probe = pos + right_dir * look_ahead;
-        
-        let center_hit = obstacle_map.is_obstacle_in_radius(center_probe.x, center_probe.y, 5.0, w, h);
-        let left_hit = obstacle_map.is_obstacle_in_radius(left_probe.x, left_probe.y, 5.0, w, h);
-        let right_hit = obstacle_map.is_obstacle_in_radius(right_probe.x, right_probe.y, 5.0, w, h);
-        
-        if center_hit || left_hit || right_hit {
-            let mut turn_force = Vec2::ZERO;
-            
-            // Steering logic
-            
-            if left_hit && !right_hit {
-                // Obstacle on left, turn right
-                turn_force += vec2(forward.y, -forward.x) * 400.0;
-            } else if right_hit && !left_hit {
-                // Obstacle on right, turn left
-                turn_force += vec2(-forward.y, forward.x) * 400.0;
-            } else {
-                // Both blocked or center blocked, pick random valid side or turn around
-                let mut rng = thread_rng();
-                if rng.gen_bool(0.5) {
-                    turn_force += vec2(forward.y, -forward.x) * 600.0;
-                } else {
-                    turn_force += vec2(-forward.y, forward.x) * 600.0;
-                }
-            }
-            
-            if center_hit {
-                 // Brake hard
-                 acceleration.0 -= velocity.0 * 2.0;
-            }
+    if velocity.length_squared() < 0.1 { return None; }
+
+    let forward = velocity.normalize();
+
+    // Center probe
+    let center_probe = pos + forward * look_ahead;
+
+    // Left probe
+    let cos_a = probe_angle.cos();
+    let sin_a = probe_angle.sin();
+    let left_dir = vec2(
+        forward.x * cos_a - forward.y * sin_a,
+        forward.x * sin_a + forward.y * cos_a
+    );
+    let left_probe = pos + left_dir * look_ahead;
+
+    // Right probe
+    let right_dir = vec2(
+        forward.x * cos_a + forward.y * sin_a,
+        -forward.x * sin_a + forward.y * cos_a
+    );
+    let right_probe = pos + right_dir * look_ahead;
+
+    let positions = [left_probe, center_probe, right_probe];
+    let hits = positions.map(|p| {
+        obstacle_map.is_obstacle_in_radius(p.x, p.y, AVOIDANCE_PROBE_RADIUS, map_size.width, map_size.height)
+    });
+    Some(AvoidanceProbes { positions, hits })
+}
+
+/// Probes ahead of the ant and returns the turning acceleration needed to avoid walls
+pub(crate) fn obstacle_avoidance<R: Rng>(
+    pos: Vec2,
+    velocity: Vec2,
+    obstacle_map: &ObstacleMap,
+    map_size: &MapSize,
+    rng: &mut R,
+) -> Vec2 {
+    let Some(probes) = avoidance_probes(pos, velocity, obstacle_map, map_size) else {
+        return Vec2::ZERO;
+    };
+    let [left_hit, center_hit, right_hit] = probes.hits;
+    let forward = velocity.normalize();
+
+    if !(center_hit || left_hit || right_hit) {
+        return Vec2::ZERO;
+    }
+
+    let mut acceleration = Vec2::ZERO;
 
-            acceleration.0 += turn_force;
+    // Steering logic
+    if left_hit && !right_hit {
+        // Obstacle on left, turn right
+        acceleration += vec2(forward.y, -forward.x) * 400.0;
+    } else if right_hit && !left_hit {
+        // Obstacle on right, turn left
+        acceleration += vec2(-forward.y, forward.x) * 400.0;
+    } else {
+        // Both blocked or center blocked, pick random valid side or turn around
+        if rng.gen_bool(0.5) {
+            acceleration += vec2(forward.y, -forward.x) * 600.0;
+        } else {
+            acceleration += vec2(-forward.y, forward.x) * 600.0;
         }
     }
+
+    if center_hit {
+        // Brake hard
+        acceleration -= velocity * 2.0;
+    }
+
+    acceleration
 }
@@ -0,0 +1,329 @@
+//! Command line entry points that run without opening a window.
+//!
+//! `ants optimize [options]` evolves a `SimConfig` with headless runs and writes the
+//! best one in the same format as the "Save Config" button.
+//!
+//! `ants double-bridge [options]` runs the double bridge experiment headless and
+//! writes the branch counts as csv.
+//!
+//! `ants frames [options]` runs a map headless and writes numbered png frames.
+//!
+//! `ants generate [options]` writes procedurally generated maps, optionally running
+//! each one headless to compare a config across a family of arenas.
+//!
+//! `ants script [options]` runs a scenario script headless and prints its events as
+//! they fire.
+
+use crate::{
+    frames::{Frame, FrameScene, FrameWriter},
+    gui::{SavedConfig, SimConfig},
+    headless::HeadlessSim,
+    map::{MapLayout, MapSize},
+    mapgen::{generate as generate_map, MapGenKind, MapGenSettings},
+    optimizer::{Optimizer, OptimizerSettings, GENES},
+    scenario::{Branch, BranchTracker, DoubleBridge},
+    script::{Script, ScriptRun},
+    *,
+};
+
+/// Returns true if the args named a batch command, in which case the app should not start
+pub fn run(args: &[String]) -> bool {
+    let Some(command) = args.get(1) else {
+        return false;
+    };
+
+    let result = match command.as_str() {
+        "optimize" => optimize(&args[2..]),
+        "double-bridge" => double_bridge(&args[2..]),
+        "frames" => frames(&args[2..]),
+        "generate" => generate(&args[2..]),
+        "script" => script(&args[2..]),
+        _ => return false,
+    };
+
+    if let Err(e) = result {
+        eprintln!("Error: {}", e);
+        std::process::exit(1);
+    }
+    true
+}
+
+/// Minimal `--key value` parser, no external crate needed for a handful of flags
+struct Flags<'a> {
+    args: &'a [String],
+}
+
+impl<'a> Flags<'a> {
+    fn get(&self, key: &str) -> Option<&'a str> {
+        self.args
+            .iter()
+            .position(|a| a == key)
+            .and_then(|i| self.args.get(i + 1))
+            .map(|s| s.as_str())
+    }
+
+    fn parse<T: std::str::FromStr>(&self, key: &str, default: T) -> Result<T, String> {
+        match self.get(key) {
+            Some(v) => v.parse().map_err(|_| format!("invalid value for {}: {}", key, v)),
+            None => Ok(default),
+        }
+    }
+}
+
+/// Config from `--config` (or user_config.json if present), defaults otherwise
+fn load_base_config(flags: &Flags) -> Result<(SimConfig, MapSize), String> {
+    let config_path = flags.get("--config").unwrap_or(USER_CONFIG_FILE);
+    if std::path::Path::new(config_path).exists() {
+        let saved = SavedConfig::load(config_path)?;
+        Ok((saved.sim_config, saved.map_size))
+    } else {
+        Ok((SimConfig::default(), MapSize::default()))
+    }
+}
+
+/// Comma separated parameter names of `key`, checked against the optimiser's genes
+fn gene_names(flags: &Flags, key: &str) -> Result<Vec<String>, String> {
+    let names: Vec<String> = flags
+        .get(key)
+        .map(|s| s.split(',').map(|n| n.trim().to_string()).collect())
+        .unwrap_or_default();
+    if let Some(name) = names.iter().find(|n| !GENES.iter().any(|g| g.name == n.as_str())) {
+        return Err(format!("unknown parameter in {}: {}", key, name));
+    }
+    Ok(names)
+}
+
+fn optimize(args: &[String]) -> Result<(), String> {
+    let flags = Flags { args };
+    let defaults = OptimizerSettings::default();
+
+    // Base config, also provides the map size when no map file is given
+    let (base, map_size) = load_base_config(&flags)?;
+
+    let layout = match flags.get("--map") {
+        Some(path) => MapLayout::load(path)?,
+        None => MapLayout::new(map_size),
+    };
+    if layout.food.is_empty() {
+        return Err("map has no food, fitness would always be 0".to_string());
+    }
+
+    let mut frozen = defaults.frozen.clone();
+    frozen.extend(gene_names(&flags, "--freeze")?);
+    let unfrozen = gene_names(&flags, "--unfreeze")?;
+    frozen.retain(|name| !unfrozen.contains(name));
+
+    let settings = OptimizerSettings {
+        population: flags.parse("--population", defaults.population)?,
+        generations: flags.parse("--generations", defaults.generations)?,
+        ticks: flags.parse("--ticks", defaults.ticks)?,
+        runs: flags.parse("--runs", defaults.runs)?,
+        mutation_rate: flags.parse("--mutation-rate", defaults.mutation_rate)?,
+        mutation_scale: flags.parse("--mutation-scale", defaults.mutation_scale)?,
+        elitism: flags.parse("--elitism", defaults.elitism)?,
+        tournament_size: flags.parse("--tournament", defaults.tournament_size)?,
+        seed: flags.parse("--seed", defaults.seed)?,
+        frozen,
+    };
+    // Checked here, out of range values would panic inside the worker threads
+    if settings.population == 0 || settings.runs == 0 || settings.tournament_size == 0 {
+        return Err("--population, --runs and --tournament must be positive".to_string());
+    }
+    if !(0.0..=1.0).contains(&settings.mutation_rate) {
+        return Err("--mutation-rate must be between 0 and 1".to_string());
+    }
+    if !settings.mutation_scale.is_finite() || settings.mutation_scale < 0.0 {
+        return Err("--mutation-scale must not be negative".to_string());
+    }
+    if settings.elitism > settings.population {
+        return Err("--elitism can't be larger than --population".to_string());
+    }
+    let out_path = flags.get("--out").unwrap_or(USER_CONFIG_FILE);
+
+    println!(
+        "Optimizing: population {}, generations {}, {} ticks x {} runs",
+        settings.population, settings.generations, settings.ticks, settings.runs
+    );
+    let (best, best_fitness) = Optimizer::new(&layout, base, settings).run(|generation, fitness, _| {
+        println!("Generation {}: best food delivered {:.1}", generation + 1, fitness);
+    });
+
+    for gene in GENES.iter() {
+        println!("  {}: {}", gene.name, gene.value(&best));
+    }
+
+    let saved = SavedConfig {
+        sim_config: best,
+        map_size: layout.map_size,
+    };
+    saved.save(out_path)?;
+    println!("Saved best config (food delivered {:.1}) to {}", best_fitness, out_path);
+    Ok(())
+}
+
+fn double_bridge(args: &[String]) -> Result<(), String> {
+    let flags = Flags { args };
+    let (config, _) = load_base_config(&flags)?;
+    let ratio: f32 = flags.parse("--ratio", 2.0)?;
+    let ticks: u64 = flags.parse("--ticks", 18000)?;
+    let seed: u64 = flags.parse("--seed", 0)?;
+    let bin_secs: f32 = flags.parse("--bin-secs", BRIDGE_BIN_SECS)?;
+    let out_path = flags.get("--out").unwrap_or(BRIDGE_CSV_FILE);
+
+    let bridge = DoubleBridge::new(ratio);
+    let mut sim = HeadlessSim::new(config, &bridge.layout(), seed);
+    let mut tracker = BranchTracker::new(bridge, bin_secs);
+
+    for _ in 0..ticks {
+        sim.step();
+        let elapsed_secs = sim.elapsed_secs();
+        for (i, ant) in sim.ants.iter().enumerate() {
+            tracker.observe(i as u64, ant.pos, ant.task, elapsed_secs);
+        }
+    }
+
+    let totals = tracker.totals();
+    if let Some(bridge) = &tracker.bridge {
+        println!(
+            "Branch lengths: short {:.0}, long {:.0}",
+            bridge.branch_length(Branch::Short),
+            bridge.branch_length(Branch::Long)
+        );
+    }
+    println!("Short: {} traversals, long: {} traversals", totals.short(), totals.long());
+    if let Some(f) = totals.short_fraction() {
+        println!("Short branch share: {:.1}%", f * 100.0);
+    }
+
+    std::fs::write(out_path, tracker.to_csv()).map_err(|e| format!("{}: {}", out_path, e))?;
+    println!("Saved branch counts to {}", out_path);
+    Ok(())
+}
+
+fn frames(args: &[String]) -> Result<(), String> {
+    let flags = Flags { args };
+    let (config, map_size) = load_base_config(&flags)?;
+    let layout = match flags.get("--map") {
+        Some(path) => MapLayout::load(path)?,
+        None => MapLayout::new(map_size),
+    };
+    let ticks: u64 = flags.parse("--ticks", 3600)?;
+    let every_secs: f32 = flags.parse("--every", FRAME_INTERVAL_SECS)?;
+    let cell_px: usize = flags.parse("--cell-px", FRAME_CELL_PX)?;
+    let seed: u64 = flags.parse("--seed", 0)?;
+    let out_dir = flags.get("--out").unwrap_or(FRAMES_DIR);
+    if every_secs <= 0.0 || cell_px == 0 {
+        return Err("--every and --cell-px must be positive".to_string());
+    }
+
+    let mut sim = HeadlessSim::new(config, &layout, seed);
+    let mut writer = FrameWriter::new(out_dir)?;
+    let mut next_secs = 0.0;
+    for _ in 0..ticks {
+        sim.step();
+        if sim.elapsed_secs() >= next_secs {
+            next_secs += every_secs;
+            writer.write(Frame::render(&FrameScene::from_headless(&sim), cell_px))?;
+        }
+    }
+
+    println!(
+        "Saved {} frames to {}/ ({:.1} food delivered)",
+        writer.frame_count(),
+        out_dir,
+        sim.food_delivered
+    );
+    Ok(())
+}
+
+fn generate(args: &[String]) -> Result<(), String> {
+    let flags = Flags { args };
+    let defaults = MapGenSettings::default();
+    let (config, map_size) = load_base_config(&flags)?;
+    // Size, nest and food come from the base map
+    let base = match flags.get("--map") {
+        Some(path) => MapLayout::load(path)?,
+        None => MapLayout::new(map_size),
+    };
+    let kind: MapGenKind = flags.parse("--kind", defaults.kind)?;
+    let seed: u64 = flags.parse("--seed", defaults.seed)?;
+    let density: f32 = flags.parse("--density", defaults.density)?;
+    let scale: f32 = flags.parse("--scale", defaults.scale)?;
+    let count: u64 = flags.parse("--count", 1)?;
+    let ticks: u64 = flags.parse("--ticks", 0)?;
+    let out_path = flags.get("--out").unwrap_or(GENERATED_MAP_FILE);
+    if scale <= 0.0 || count == 0 {
+        return Err("--scale and --count must be positive".to_string());
+    }
+
+    let mut total_delivered = 0.0;
+    for seed in seed..seed + count {
+        let settings = MapGenSettings { kind, seed, density, scale };
+        let layout = generate_map(&base, &settings);
+        // One file per seed when generating a family of maps
+        let path = match (count, out_path.strip_suffix(".json")) {
+            (1, _) => out_path.to_string(),
+            (_, Some(stem)) => format!("{}_{}.json", stem, seed),
+            (_, None) => format!("{}_{}", out_path, seed),
+        };
+        layout.save(&path)?;
+
+        let cells = layout.to_obstacle_map().grid.len();
+        let wall_share = layout.walls.len() as f32 / cells as f32 * 100.0;
+        if ticks == 0 {
+            println!("Seed {}: {:.1}% walls, saved to {}", seed, wall_share, path);
+            continue;
+        }
+        let mut sim = HeadlessSim::new(config.clone(), &layout, seed);
+        sim.run(ticks);
+        total_delivered += sim.food_delivered;
+        println!(
+            "Seed {}: {:.1}% walls, {:.1} food delivered, saved to {}",
+            seed, wall_share, sim.food_delivered, path
+        );
+    }
+    if ticks > 0 {
+        println!(
+            "Mean food delivered over {} {} maps: {:.1}",
+            count,
+            kind.name(),
+            total_delivered / count as f32
+        );
+    }
+    Ok(())
+}
+
+fn script(args: &[String]) -> Result<(), String> {
+    let flags = Flags { args };
+    let (config, map_size) = load_base_config(&flags)?;
+    let script = Script::load(flags.get("--script").unwrap_or(SCRIPT_FILE))?;
+    // --map wins over the map named in the script
+    let layout = match (flags.get("--map"), script.load_map()?) {
+        (Some(path), _) => MapLayout::load(path)?,
+        (None, Some(layout)) => layout,
+        (None, None) => MapLayout::new(map_size),
+    };
+    let ticks: u64 = flags.parse("--ticks", 18000)?;
+    let seed: u64 = flags.parse("--seed", 0)?;
+
+    let mut sim = HeadlessSim::new(config, &layout, seed);
+    let mut run = ScriptRun::new(script);
+    for _ in 0..ticks {
+        for event in run.step_headless(&mut sim) {
+            let actions: Vec<String> = event.actions.iter().map(|a| a.label()).collect();
+            println!(
+                "Tick {} ({:.1} s, {:.1} food delivered): {}: {}",
+                sim.tick,
+                sim.elapsed_secs(),
+                sim.food_delivered,
+                event.trigger.label(),
+                actions.join(", ")
+            );
+        }
+        sim.step();
+    }
+
+    let pending = run.fired.iter().filter(|f| !**f).count();
+    println!("{:.1} food delivered in {} ticks, {} events never fired", sim.food_delivered, ticks, pending);
+    Ok(())
+}
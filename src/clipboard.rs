@@ -0,0 +1,431 @@
+//! Region selection and the editor clipboard.
+//! With the "Select Region" tool a rectangle of cells is dragged out. Its walls,
+//! terrain, decay multipliers and food can be copied or cut to the clipboard, which
+//! can be rotated and mirrored before it is pasted, e.g. to build symmetric arenas.
+//! Cut and paste are editor actions, so they are recorded and can be undone.
+
+use crate::{
+    ant::Food,
+    gui::{cursor_world_pos, handle_editor_input, EditorAction, EditorActionEvent, EditorState, EditorTool, SimSettings},
+    history::record_edits,
+    map::{FoodSpec, MapSize, ObstacleMap, Terrain},
+    *,
+};
+use bevy::prelude::*;
+use bevy_egui::EguiContexts;
+use bevy_pancam::PanCam;
+use serde::{Deserialize, Serialize};
+
+pub struct ClipboardPlugin;
+
+const SELECTION_COLOR: Color = Color::rgb(0.5, 1.5, 2.0);
+const PASTE_COLOR: Color = Color::rgb(1.5, 1.5, 0.5);
+
+/// Rectangle of grid cells, `col`/`row` is the bottom-left cell
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Debug)]
+pub struct CellRegion {
+    pub col: isize,
+    pub row: isize,
+    pub width: usize,
+    pub height: usize,
+}
+
+impl CellRegion {
+    /// Cells between two world positions, both corners included, clipped to the map
+    pub fn from_corners(a: Vec2, b: Vec2, obstacle_map: &ObstacleMap, map_size: &MapSize) -> Self {
+        let clamp_col = |v: isize| v.clamp(0, obstacle_map.width as isize - 1);
+        let clamp_row = |v: isize| v.clamp(0, obstacle_map.height as isize - 1);
+        let (ca, ra) = world_to_cell(a, map_size);
+        let (cb, rb) = world_to_cell(b, map_size);
+        let (c0, c1) = (clamp_col(ca.min(cb)), clamp_col(ca.max(cb)));
+        let (r0, r1) = (clamp_row(ra.min(rb)), clamp_row(ra.max(rb)));
+        Self {
+            col: c0,
+            row: r0,
+            width: (c1 - c0 + 1) as usize,
+            height: (r1 - r0 + 1) as usize,
+        }
+    }
+
+    /// Bottom-left corner in world coordinates
+    pub fn world_min(&self, map_size: &MapSize) -> Vec2 {
+        cell_to_world(self.col, self.row, map_size)
+    }
+
+    pub fn world_max(&self, map_size: &MapSize) -> Vec2 {
+        cell_to_world(self.col + self.width as isize, self.row + self.height as isize, map_size)
+    }
+
+    pub fn contains(&self, pos: Vec2, map_size: &MapSize) -> bool {
+        let (min, max) = (self.world_min(map_size), self.world_max(map_size));
+        pos.x >= min.x && pos.y >= min.y && pos.x < max.x && pos.y < max.y
+    }
+
+    /// Grid indices of the cells inside the map, with their position in the region
+    pub fn cells(&self, obstacle_map: &ObstacleMap) -> Vec<(usize, usize, usize)> {
+        let mut cells = vec![];
+        for y in 0..self.height {
+            for x in 0..self.width {
+                let (gx, gy) = (self.col + x as isize, self.row + y as isize);
+                if gx >= 0 && gy >= 0 && gx < obstacle_map.width as isize && gy < obstacle_map.height as isize {
+                    cells.push((x, y, gy as usize * obstacle_map.width + gx as usize));
+                }
+            }
+        }
+        cells
+    }
+}
+
+/// Cell under a world position, may be outside the map
+pub fn world_to_cell(pos: Vec2, map_size: &MapSize) -> (isize, isize) {
+    let unit = PH_UNIT_GRID_SIZE as f32;
+    (
+        ((pos.x + map_size.width / 2.0) / unit).floor() as isize,
+        ((pos.y + map_size.height / 2.0) / unit).floor() as isize,
+    )
+}
+
+/// Bottom-left corner of a cell
+pub fn cell_to_world(col: isize, row: isize, map_size: &MapSize) -> Vec2 {
+    let unit = PH_UNIT_GRID_SIZE as f32;
+    Vec2::new(
+        col as f32 * unit - map_size.width / 2.0,
+        row as f32 * unit - map_size.height / 2.0,
+    )
+}
+
+/// Copied cells and food
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct Clipboard {
+    pub width: usize,
+    pub height: usize,
+    /// Row 0 is the bottom, like `ObstacleMap::grid`
+    pub cells: Vec<Terrain>,
+    pub decay: Vec<f32>,
+    /// Positions relative to the bottom-left corner of the region
+    pub food: Vec<FoodSpec>,
+}
+
+impl Clipboard {
+    pub fn capture(
+        region: &CellRegion,
+        obstacle_map: &ObstacleMap,
+        map_size: &MapSize,
+        food: impl Iterator<Item = FoodSpec>,
+    ) -> Self {
+        let mut clipboard = Self {
+            width: region.width,
+            height: region.height,
+            cells: vec![Terrain::Open; region.width * region.height],
+            decay: vec![1.0; region.width * region.height],
+            food: vec![],
+        };
+        for (x, y, idx) in region.cells(obstacle_map) {
+            clipboard.cells[y * region.width + x] = obstacle_map.grid[idx];
+            clipboard.decay[y * region.width + x] = obstacle_map.decay[idx];
+        }
+        let origin = region.world_min(map_size);
+        clipboard.food = food
+            .filter(|f| region.contains(Vec2::new(f.x, f.y), map_size))
+            .map(|f| FoodSpec {
+                x: f.x - origin.x,
+                y: f.y - origin.y,
+                ..f
+            })
+            .collect();
+        clipboard
+    }
+
+    /// Rebuilds the clipboard with cell `(x, y)` moved to `to(x, y)` and food at
+    /// `(fx, fy)` moved to `to_food(fx, fy)`
+    fn remap(
+        &self,
+        width: usize,
+        height: usize,
+        to: impl Fn(usize, usize) -> (usize, usize),
+        to_food: impl Fn(f32, f32) -> (f32, f32),
+    ) -> Self {
+        let mut cells = vec![Terrain::Open; width * height];
+        let mut decay = vec![1.0; width * height];
+        for y in 0..self.height {
+            for x in 0..self.width {
+                let (nx, ny) = to(x, y);
+                cells[ny * width + nx] = self.cells[y * self.width + x];
+                decay[ny * width + nx] = self.decay[y * self.width + x];
+            }
+        }
+        let food = self
+            .food
+            .iter()
+            .map(|f| {
+                let (x, y) = to_food(f.x, f.y);
+                FoodSpec { x, y, ..f.clone() }
+            })
+            .collect();
+        Self { width, height, cells, decay, food }
+    }
+
+    /// Quarter turn counter-clockwise
+    pub fn rotated(&self) -> Self {
+        let (w, h) = (self.width, self.height);
+        let size_y = h as f32 * PH_UNIT_GRID_SIZE as f32;
+        self.remap(h, w, |x, y| (h - 1 - y, x), |fx, fy| (size_y - fy, fx))
+    }
+
+    /// Flipped left to right
+    pub fn mirrored_x(&self) -> Self {
+        let w = self.width;
+        let size_x = w as f32 * PH_UNIT_GRID_SIZE as f32;
+        self.remap(w, self.height, |x, y| (w - 1 - x, y), |fx, fy| (size_x - fx, fy))
+    }
+
+    /// Flipped top to bottom
+    pub fn mirrored_y(&self) -> Self {
+        let h = self.height;
+        let size_y = h as f32 * PH_UNIT_GRID_SIZE as f32;
+        self.remap(self.width, h, |x, y| (x, h - 1 - y), |fx, fy| (fx, size_y - fy))
+    }
+
+    /// Region covered when pasted with the bottom-left cell at `(col, row)`
+    pub fn region_at(&self, col: isize, row: isize) -> CellRegion {
+        CellRegion {
+            col,
+            row,
+            width: self.width,
+            height: self.height,
+        }
+    }
+
+    /// Bottom-left cell that centres the clipboard on a world position
+    pub fn origin_centered_at(&self, pos: Vec2, map_size: &MapSize) -> (isize, isize) {
+        let (col, row) = world_to_cell(pos, map_size);
+        (col - self.width as isize / 2, row - self.height as isize / 2)
+    }
+
+    /// Writes the cells into the map, cells falling outside it are dropped
+    pub fn paste_cells(&self, col: isize, row: isize, obstacle_map: &mut ObstacleMap) {
+        for (x, y, idx) in self.region_at(col, row).cells(obstacle_map) {
+            obstacle_map.grid[idx] = self.cells[y * self.width + x];
+            obstacle_map.decay[idx] = self.decay[y * self.width + x];
+        }
+    }
+
+    /// Food in world coordinates when pasted at `(col, row)`
+    pub fn food_at(&self, col: isize, row: isize, map_size: &MapSize) -> Vec<FoodSpec> {
+        let origin = cell_to_world(col, row, map_size);
+        self.food
+            .iter()
+            .map(|f| FoodSpec {
+                x: f.x + origin.x,
+                y: f.y + origin.y,
+                ..f.clone()
+            })
+            .collect()
+    }
+}
+
+#[derive(Event, Clone, Copy)]
+pub enum ClipboardCommand {
+    Copy,
+    Cut,
+    /// Toggles paste mode, where each click pastes the clipboard
+    Paste,
+    Rotate,
+    MirrorX,
+    MirrorY,
+}
+
+impl Plugin for ClipboardPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<ClipboardCommand>()
+            .add_systems(
+                Update,
+                handle_clipboard_commands.after(handle_editor_input).before(record_edits),
+            )
+            .add_systems(Update, draw_selection);
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn handle_clipboard_commands(
+    mut contexts: EguiContexts,
+    keys: Res<Input<KeyCode>>,
+    settings: Res<SimSettings>,
+    mut editor_state: ResMut<EditorState>,
+    mut commands: EventReader<ClipboardCommand>,
+    mut actions: EventWriter<EditorActionEvent>,
+    obstacle_map: Res<ObstacleMap>,
+    map_size: Res<MapSize>,
+    food_query: Query<(&Transform, &Food)>,
+) {
+    let mut requested: Vec<ClipboardCommand> = commands.iter().copied().collect();
+    let ctrl = keys.any_pressed([KeyCode::ControlLeft, KeyCode::ControlRight]);
+    if ctrl && editor_state.selected_tool == EditorTool::Select && !contexts.ctx_mut().wants_keyboard_input() {
+        for (key, command) in [
+            (KeyCode::C, ClipboardCommand::Copy),
+            (KeyCode::X, ClipboardCommand::Cut),
+            (KeyCode::V, ClipboardCommand::Paste),
+        ] {
+            if keys.just_pressed(key) {
+                requested.push(command);
+            }
+        }
+    }
+    if settings.is_replaying {
+        return;
+    }
+
+    for command in requested {
+        match command {
+            ClipboardCommand::Copy | ClipboardCommand::Cut => {
+                let Some(region) = editor_state.selection else {
+                    continue;
+                };
+                let food = food_query.iter().map(|(tr, food)| food.spec(tr.translation.truncate()));
+                editor_state.clipboard = Some(Clipboard::capture(&region, &obstacle_map, &map_size, food));
+                if matches!(command, ClipboardCommand::Cut) {
+                    actions.send(EditorActionEvent::new(EditorAction::ClearRegion { region }));
+                }
+            }
+            ClipboardCommand::Paste => {
+                editor_state.is_pasting = !editor_state.is_pasting && editor_state.clipboard.is_some();
+            }
+            ClipboardCommand::Rotate | ClipboardCommand::MirrorX | ClipboardCommand::MirrorY => {
+                if let Some(clipboard) = &editor_state.clipboard {
+                    let transformed = match command {
+                        ClipboardCommand::Rotate => clipboard.rotated(),
+                        ClipboardCommand::MirrorX => clipboard.mirrored_x(),
+                        _ => clipboard.mirrored_y(),
+                    };
+                    editor_state.clipboard = Some(transformed);
+                }
+            }
+        }
+    }
+}
+
+fn draw_selection(
+    mut gizmos: Gizmos,
+    editor_state: Res<EditorState>,
+    map_size: Res<MapSize>,
+    windows: Query<&Window>,
+    camera_q: Query<(&Camera, &GlobalTransform), With<PanCam>>,
+) {
+    if editor_state.selected_tool != EditorTool::Select {
+        return;
+    }
+    if let Some(region) = &editor_state.selection {
+        draw_region(&mut gizmos, region, &map_size, SELECTION_COLOR);
+    }
+    let Some(clipboard) = editor_state.clipboard.as_ref().filter(|_| editor_state.is_pasting) else {
+        return;
+    };
+    let Some(cursor) = cursor_world_pos(&windows, &camera_q) else {
+        return;
+    };
+    let (col, row) = clipboard.origin_centered_at(cursor, &map_size);
+    draw_region(&mut gizmos, &clipboard.region_at(col, row), &map_size, PASTE_COLOR);
+    for food in clipboard.food_at(col, row, &map_size) {
+        gizmos.circle_2d(Vec2::new(food.x, food.y), 10.0, PASTE_COLOR);
+    }
+}
+
+fn draw_region(gizmos: &mut Gizmos, region: &CellRegion, map_size: &MapSize, color: Color) {
+    let (min, max) = (region.world_min(map_size), region.world_max(map_size));
+    gizmos.rect_2d((min + max) / 2.0, 0.0, max - min, color);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const UNIT: f32 = PH_UNIT_GRID_SIZE as f32;
+
+    /// 3x2 clipboard with a different terrain and decay in every cell and food
+    /// in the middle of cell (2, 1)
+    fn sample() -> Clipboard {
+        Clipboard {
+            width: 3,
+            height: 2,
+            cells: vec![
+                Terrain::Wall,
+                Terrain::Open,
+                Terrain::Sand,
+                Terrain::Water,
+                Terrain::Rough,
+                Terrain::Open,
+            ],
+            decay: (0..6).map(|i| i as f32).collect(),
+            food: vec![FoodSpec::new(2.5 * UNIT, 1.5 * UNIT, 10)],
+        }
+    }
+
+    fn cell(clipboard: &Clipboard, x: usize, y: usize) -> (Terrain, f32) {
+        let idx = y * clipboard.width + x;
+        (clipboard.cells[idx], clipboard.decay[idx])
+    }
+
+    /// Cell under each food source
+    fn food_cells(clipboard: &Clipboard) -> Vec<(usize, usize)> {
+        clipboard
+            .food
+            .iter()
+            .map(|f| ((f.x / UNIT) as usize, (f.y / UNIT) as usize))
+            .collect()
+    }
+
+    fn assert_same(a: &Clipboard, b: &Clipboard) {
+        assert_eq!((a.width, a.height), (b.width, b.height));
+        assert_eq!(a.cells, b.cells);
+        assert_eq!(a.decay, b.decay);
+        assert_eq!(a.food.len(), b.food.len());
+        for (fa, fb) in a.food.iter().zip(b.food.iter()) {
+            assert!(fa.pos().distance(fb.pos()) < 1e-4, "{:?} != {:?}", fa.pos(), fb.pos());
+        }
+    }
+
+    #[test]
+    fn rotated_turns_counter_clockwise() {
+        let clipboard = sample();
+        let rotated = clipboard.rotated();
+        assert_eq!((rotated.width, rotated.height), (2, 3));
+        // Bottom row becomes the right column, bottom-left ends up bottom-right
+        assert_eq!(cell(&rotated, 1, 0), cell(&clipboard, 0, 0));
+        assert_eq!(cell(&rotated, 1, 2), cell(&clipboard, 2, 0));
+        assert_eq!(cell(&rotated, 0, 0), cell(&clipboard, 0, 1));
+        // Food stays on the cell it was on
+        assert_eq!(food_cells(&rotated), vec![(0, 2)]);
+        assert_eq!(cell(&rotated, 0, 2), cell(&clipboard, 2, 1));
+    }
+
+    #[test]
+    fn four_rotations_are_identity() {
+        let clipboard = sample();
+        let turned = clipboard.rotated().rotated().rotated().rotated();
+        assert_same(&turned, &clipboard);
+    }
+
+    #[test]
+    fn mirrored_flips_one_axis() {
+        let clipboard = sample();
+
+        let flipped = clipboard.mirrored_x();
+        assert_eq!((flipped.width, flipped.height), (3, 2));
+        assert_eq!(cell(&flipped, 0, 0), cell(&clipboard, 2, 0));
+        assert_eq!(cell(&flipped, 1, 1), cell(&clipboard, 1, 1));
+        assert_eq!(food_cells(&flipped), vec![(0, 1)]);
+
+        let flipped = clipboard.mirrored_y();
+        assert_eq!(cell(&flipped, 0, 0), cell(&clipboard, 0, 1));
+        assert_eq!(food_cells(&flipped), vec![(2, 0)]);
+
+        assert_same(&clipboard.mirrored_x().mirrored_x(), &clipboard);
+        assert_same(&clipboard.mirrored_y().mirrored_y(), &clipboard);
+    }
+
+    #[test]
+    fn mirroring_both_axes_is_a_half_turn() {
+        let clipboard = sample();
+        assert_same(&clipboard.mirrored_x().mirrored_y(), &clipboard.rotated().rotated());
+    }
+}
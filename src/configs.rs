@@ -23,6 +23,10 @@ pub const ANT_TARGET_AUTO_PULL_RADIUS: f32 = 100.0;
 pub const ANT_SENSOR_ANGLE: f32 = 45.0; 
 pub const ANT_SENSOR_DIST: f32 = 15.0; // Closer sensors for tighter path following
 pub const ANT_SENSOR_RADIUS: usize = 3; 
+pub const AVOIDANCE_PROBE_RADIUS: f32 = 5.0;
+pub const STUCK_CHECK_INTERVAL: f32 = 2.0;
+pub const STUCK_DISTANCE: f32 = 15.0; // moved less than this between checks
+pub const PICKUP_COLOR_SECS: f32 = 60.0; // "time since pickup" colour range
 
 // Ant Colony
 pub const HOME_LOCATION: (f32, f32) = (759.0, -350.0);
@@ -32,8 +36,10 @@ pub const HOME_RADIUS: f32 = 30.0;
 
 // Pheromones
 pub const MAX_PHEROMONE_STRENGTH: f32 = 300.0; // Higher cap
+pub const HOME_PH_STRENGTH: f32 = 100000.0; // Signal at the nest, guides ants home
 pub const PH_DECAY_RATE: f32 = 0.15; // Moderate decay
 pub const PH_DECAY_INTERVAL: f32 = 0.1;
+pub const MAX_DECAY_FACTOR: f32 = 5.0; // Upper end of the decay brush
 pub const PH_IMG_UPDATE_SEC: f32 = 0.1;
 pub const PH_GRID_VIZ_MIN_STRENGTH: u8 = 1;
 pub const PH_UNIT_GRID_SIZE: usize = 5;
@@ -42,6 +48,8 @@ pub const PH_KD_TREE_UPDATE_INTERVAL: f32 = 2.0;
 pub const PH_CACHE_GRID_SIZE: i32 = 10;
 pub const PH_COLOR_TO_FOOD: (u8, u8, u8) = (2, 79, 2);
 pub const PH_COLOR_TO_HOME: (u8, u8, u8) = (200, 81, 112);
+pub const PH_COLOR_TO_FOOD_MAX: (u8, u8, u8) = (0, 0, 255); // Deep Blue
+pub const PH_COLOR_TO_HOME_MAX: (u8, u8, u8) = (255, 0, 0); // Deep Red
 pub const PH_GRID_OPACITY: u8 = 255;
 
 // Path Viz
@@ -55,9 +63,91 @@ pub const VIZ_MAX_COLOR_STRENGTH: f32 = 50.0;
 // pub const FOOD_LOCATION: (f32, f32) = (-400.0, 300.0);
 pub const FOOD_LOCATION: (f32, f32) = (-750.0, 400.0);
 pub const FOOD_PICKUP_RADIUS: f32 = 30.0;
-pub const FOOD_SPRITE_SCALE: f32 = 0.25;
+pub const FOOD_STORAGE: i32 = 1000;
+pub const FOOD_SPRITE_SCALE: f32 = 0.25; // At FOOD_STORAGE, grows and shrinks with the storage left
+pub const FOOD_SPRITE_MIN_SCALE: f32 = 0.08;
+pub const FOOD_SPRITE_MAX_SCALE: f32 = 0.6;
+pub const FOOD_QUALITY: f32 = 1.0;
+pub const MAX_FOOD_QUALITY: f32 = 5.0;
+pub const MAX_FOOD_STORAGE: i32 = 10_000;
+pub const MAX_FOOD_PICKUP_RADIUS: f32 = 100.0;
+pub const MAX_FOOD_REGROW_RATE: f32 = 50.0; // Units per second
+
+// Food spawner
+pub const FOOD_SPAWN_INTERVAL: f32 = 20.0; // Sim seconds between new sources
+pub const FOOD_SPAWN_MIN_DIST: f32 = 300.0;
+pub const FOOD_SPAWN_MAX_DIST: f32 = 800.0;
+pub const FOOD_SPAWN_MAX_RANGE: f32 = 2000.0;
+pub const FOOD_SPAWN_MAX_SOURCES: usize = 5;
+pub const FOOD_SPAWN_STORAGE: i32 = 300;
+pub const FOOD_SPAWN_CLEARANCE: f32 = 20.0; // No walls this close to a new source
+pub const FOOD_SPAWN_ATTEMPTS: usize = 50;
+
+// Double bridge scenario
+pub const BRIDGE_CORRIDOR_WIDTH: f32 = 60.0;
+pub const BRIDGE_FOOD_STORAGE: i32 = 100_000;
+pub const BRIDGE_BIN_SECS: f32 = 10.0;
+
+// Map generation
+pub const MAPGEN_SCALE: f32 = 40.0;
+pub const MAPGEN_CLEARING_RADIUS: f32 = 40.0; // Open ground around nest and food
+pub const MAPGEN_CLEARANCE: f32 = 10.0; // Cells closer to a wall don't count as passable
+pub const MAPGEN_CORRIDOR_RADIUS: f32 = 20.0;
+pub const MAPGEN_MAX_RECTANGLES: usize = 10_000;
+
+// Recording
+pub const RECORD_KEYFRAME_SECS: f32 = 0.5;
+pub const RECORD_PHEROMONE_EVERY: usize = 4; // keyframes
+
+// Inspector
+pub const ANT_PICK_RADIUS: f32 = 20.0;
+pub const TRAIL_LENGTH: usize = 1000; // points
+pub const TRAIL_MIN_STEP: f32 = 3.0;
+pub const TRAIL_MAX_MARKERS: usize = 100;
+
+// Overlays
+pub const FIELD_LATTICE_SPACING: f32 = 40.0;
+pub const FIELD_ARROW_LENGTH: f32 = 18.0;
+pub const FIELD_UPDATE_SECS: f32 = 0.25;
+pub const FIELD_STEER_HEADINGS: usize = 8;
+
+// Trail network
+pub const NETWORK_THRESHOLD: f32 = 20.0;
+pub const NETWORK_MIN_SPUR_LENGTH: f32 = 20.0;
+pub const NETWORK_UPDATE_SECS: f32 = 1.0;
+
+// Occupancy
+pub const OCCUPANCY_IMG_UPDATE_SECS: f32 = 0.5;
+
+// Frame export
+pub const FRAME_INTERVAL_SECS: f32 = 0.5;
+pub const FRAME_CELL_PX: usize = 2; // png pixels per grid cell
+
+// Shape tools
+pub const SHAPE_ELLIPSE_SEGMENTS: usize = 48;
+pub const SHAPE_CLOSE_RADIUS: f32 = 15.0; // Clicking this close to the first vertex closes a polygon
+
+// Editor history
+pub const EDITOR_HISTORY_LIMIT: usize = 200;
+pub const HISTORY_FOOD_MATCH_RADIUS: f32 = 1.0; // Undo removes the food placed at this spot
+
+// Doors and sliding walls
+pub const DYNAMIC_WALL_RADIUS: f32 = 10.0; // Brush radius when a map or script gives none
+pub const DYNAMIC_WALL_COLOR: [u8; 4] = [150, 110, 60, 255];
+pub const DOOR_CLOSED_SECS: f32 = 30.0;
+pub const DOOR_OPEN_SECS: f32 = 30.0;
+pub const DOOR_SLIDE_DIST: f32 = 100.0;
+pub const DOOR_SLIDE_SECS: f32 = 20.0;
+pub const MAX_DOOR_SECS: f32 = 300.0;
+pub const DOOR_REMOVE_RADIUS: f32 = 20.0; // Remove clicks hit walls drawn this close
+
+// Scripts
+pub const SCRIPT_WALL_RADIUS: f32 = 10.0; // Brush radius of scripted walls
+
+// Rewind
+pub const REWIND_SNAPSHOT_SECS: f32 = 2.0;
+pub const REWIND_CAPACITY: usize = 90;
 
-// Sprites
 // Sprites
 pub const SPRITE_ANT: &str = "ant.png";
 pub const SPRITE_ANT_WITH_FOOD: &str = "ant_with_food.png";
@@ -69,3 +159,16 @@ pub const SPRITE_FOOD: &str = "food.png";
 pub const ANT_ANIMATION_SPEED: f32 = 0.05;
 pub const ANT_SHEET_COLS: usize = 2;
 pub const ANT_SHEET_ROWS: usize = 2;
+
+// Files
+pub const USER_CONFIG_FILE: &str = "user_config.json";
+pub const USER_MAP_FILE: &str = "user_map.json";
+pub const BRIDGE_CSV_FILE: &str = "branch_counts.csv";
+pub const RECORDING_FILE: &str = "recording.json";
+pub const TRAIL_NETWORK_JSON_FILE: &str = "trail_network.json";
+pub const TRAIL_NETWORK_GRAPHML_FILE: &str = "trail_network.graphml";
+pub const OCCUPANCY_PNG_FILE: &str = "occupancy.png";
+pub const OCCUPANCY_CSV_FILE: &str = "occupancy.csv";
+pub const FRAMES_DIR: &str = "frames";
+pub const GENERATED_MAP_FILE: &str = "generated_map.json";
+pub const SCRIPT_FILE: &str = "scenario_script.json";
@@ -0,0 +1,327 @@
+//! Obstacles that change over the run: doors that open and close on a schedule and
+//! walls that slide along a path. They are saved with the map and can be added by
+//! scripts. Each frame the cells they cover at the current sim time are written into
+//! `ObstacleMap`, so avoidance and wall collision treat them like painted walls,
+//! both in the app and headless.
+
+use crate::{
+    gui::{cursor_world_pos, EditorState, EditorTool, SimClock},
+    map::{MapSize, ObstacleMap},
+    *,
+};
+use bevy::prelude::*;
+use bevy_pancam::PanCam;
+use serde::{Deserialize, Serialize};
+
+pub struct DoorsPlugin;
+
+const PREVIEW_COLOR: Color = Color::rgb(1.5, 1.5, 1.5);
+const PATH_COLOR: Color = Color::rgba(0.4, 0.8, 1.0, 0.6);
+const ERASE_COLOR: Color = Color::rgb(2.0, 0.5, 0.5);
+
+/// A wall segment drawn with a round brush of `radius`, present while its schedule says so
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug)]
+pub struct DynamicWall {
+    pub from: (f32, f32),
+    pub to: (f32, f32),
+    #[serde(default = "default_radius")]
+    pub radius: f32,
+    pub schedule: WallSchedule,
+}
+
+fn default_radius() -> f32 {
+    DYNAMIC_WALL_RADIUS
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum WallSchedule {
+    /// Closed for `closed_secs`, then open for `open_secs`, repeating.
+    /// `offset_secs` shifts the cycle, e.g. for doors that alternate
+    Door {
+        closed_secs: f32,
+        open_secs: f32,
+        #[serde(default)]
+        offset_secs: f32,
+    },
+    /// Always present, moving from its position through the offsets in `path` and back
+    /// again. One way takes `secs`, at constant speed along the path
+    Slide {
+        path: Vec<(f32, f32)>,
+        secs: f32,
+        #[serde(default)]
+        offset_secs: f32,
+    },
+}
+
+impl DynamicWall {
+    pub fn new(from: Vec2, to: Vec2, radius: f32, schedule: WallSchedule) -> Self {
+        Self {
+            from: (from.x, from.y),
+            to: (to.x, to.y),
+            radius,
+            schedule,
+        }
+    }
+
+    /// End points of the wall at `secs` of sim time, None while a door is open
+    pub fn segment_at(&self, secs: f32) -> Option<(Vec2, Vec2)> {
+        let (from, to) = (Vec2::new(self.from.0, self.from.1), Vec2::new(self.to.0, self.to.1));
+        match &self.schedule {
+            WallSchedule::Door { closed_secs, open_secs, offset_secs } => {
+                let period = closed_secs + open_secs;
+                if period <= 0.0 {
+                    return Some((from, to));
+                }
+                let phase = (secs + offset_secs).rem_euclid(period);
+                (phase < *closed_secs).then_some((from, to))
+            }
+            WallSchedule::Slide { path, secs: leg_secs, offset_secs } => {
+                let offset = if *leg_secs > 0.0 {
+                    // Ping-pong: 0..1 on the way out, 1..2 on the way back
+                    let t = ((secs + offset_secs) / leg_secs).rem_euclid(2.0);
+                    path_point(&slide_points(path), if t > 1.0 { 2.0 - t } else { t })
+                } else {
+                    Vec2::ZERO
+                };
+                Some((from + offset, to + offset))
+            }
+        }
+    }
+
+    /// Distance from a point to the wall at its base position
+    pub fn distance_to(&self, pos: Vec2) -> f32 {
+        let (from, to) = (Vec2::new(self.from.0, self.from.1), Vec2::new(self.to.0, self.to.1));
+        let along = to - from;
+        let t = ((pos - from).dot(along) / along.length_squared().max(f32::EPSILON)).clamp(0.0, 1.0);
+        pos.distance(from + along * t)
+    }
+
+    pub fn label(&self) -> String {
+        match &self.schedule {
+            WallSchedule::Door { closed_secs, open_secs, .. } => {
+                format!("door {:?} -> {:?} ({} s closed, {} s open)", self.from, self.to, closed_secs, open_secs)
+            }
+            WallSchedule::Slide { secs, .. } => format!("sliding wall {:?} -> {:?} ({} s)", self.from, self.to, secs),
+        }
+    }
+}
+
+/// Offsets a sliding wall passes through, starting at its base position
+fn slide_points(path: &[(f32, f32)]) -> Vec<Vec2> {
+    std::iter::once(Vec2::ZERO)
+        .chain(path.iter().map(|&(x, y)| Vec2::new(x, y)))
+        .collect()
+}
+
+/// Point at fraction `t` of the polyline's length
+fn path_point(points: &[Vec2], t: f32) -> Vec2 {
+    let total: f32 = points.windows(2).map(|s| s[0].distance(s[1])).sum();
+    let mut left = total * t.clamp(0.0, 1.0);
+    for segment in points.windows(2) {
+        let len = segment[0].distance(segment[1]);
+        if left <= len && len > 0.0 {
+            return segment[0].lerp(segment[1], left / len);
+        }
+        left -= len;
+    }
+    points.last().copied().unwrap_or(Vec2::ZERO)
+}
+
+/// Settings of the door tool
+pub struct DoorTool {
+    /// Sliding walls instead of doors
+    pub is_slide: bool,
+    pub closed_secs: f32,
+    pub open_secs: f32,
+    /// Where a sliding wall moves to, relative to where it was drawn
+    pub slide_offset: Vec2,
+    pub slide_secs: f32,
+    /// Clicks remove dynamic walls instead of drawing them
+    pub is_remove: bool,
+    /// Where the drag of the wall being drawn started
+    pub drag_start: Option<Vec2>,
+}
+
+impl Default for DoorTool {
+    fn default() -> Self {
+        Self {
+            is_slide: false,
+            closed_secs: DOOR_CLOSED_SECS,
+            open_secs: DOOR_OPEN_SECS,
+            slide_offset: Vec2::new(0.0, DOOR_SLIDE_DIST),
+            slide_secs: DOOR_SLIDE_SECS,
+            is_remove: false,
+            drag_start: None,
+        }
+    }
+}
+
+impl DoorTool {
+    /// Wall dragged from `from` to `to` with the current settings
+    pub fn wall(&self, from: Vec2, to: Vec2, radius: f32) -> DynamicWall {
+        let schedule = if self.is_slide {
+            WallSchedule::Slide {
+                path: vec![(self.slide_offset.x, self.slide_offset.y)],
+                secs: self.slide_secs,
+                offset_secs: 0.0,
+            }
+        } else {
+            WallSchedule::Door {
+                closed_secs: self.closed_secs,
+                open_secs: self.open_secs,
+                offset_secs: 0.0,
+            }
+        };
+        DynamicWall::new(from, to, radius, schedule)
+    }
+}
+
+impl Plugin for DoorsPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(Update, (update_dynamic_walls, draw_dynamic_walls));
+    }
+}
+
+/// Also runs while paused, so walls added in the editor show up right away
+fn update_dynamic_walls(mut obstacle_map: ResMut<ObstacleMap>, map_size: Res<MapSize>, clock: Res<SimClock>) {
+    if obstacle_map.dynamic_walls.is_empty() && obstacle_map.dynamic_cells.is_empty() {
+        return;
+    }
+    let cells = obstacle_map.dynamic_cells_at(clock.elapsed_secs, map_size.width, map_size.height);
+    // Only touch the map when a cell changed, so the texture isn't redrawn every frame
+    if cells != obstacle_map.dynamic_cells {
+        obstacle_map.dynamic_cells = cells;
+    }
+}
+
+/// With the door tool selected, shows every dynamic wall where it was drawn, the
+/// paths of sliding walls and the wall being dragged
+fn draw_dynamic_walls(
+    mut gizmos: Gizmos,
+    editor_state: Res<EditorState>,
+    obstacle_map: Res<ObstacleMap>,
+    windows: Query<&Window>,
+    camera_q: Query<(&Camera, &GlobalTransform), With<PanCam>>,
+) {
+    if editor_state.selected_tool != EditorTool::Door {
+        return;
+    }
+    for wall in obstacle_map.dynamic_walls.iter() {
+        let (from, to) = (Vec2::new(wall.from.0, wall.from.1), Vec2::new(wall.to.0, wall.to.1));
+        gizmos.line_2d(from, to, PREVIEW_COLOR);
+        if let WallSchedule::Slide { path, .. } = &wall.schedule {
+            let center = (from + to) / 2.0;
+            let points = slide_points(path).into_iter().map(|p| center + p);
+            gizmos.linestrip_2d(points, PATH_COLOR);
+        }
+    }
+
+    let Some(cursor) = cursor_world_pos(&windows, &camera_q) else {
+        return;
+    };
+    let door = &editor_state.door;
+    if door.is_remove {
+        gizmos.circle_2d(cursor, DOOR_REMOVE_RADIUS, ERASE_COLOR);
+        return;
+    }
+    if let Some(start) = door.drag_start {
+        gizmos.line_2d(start, cursor, PREVIEW_COLOR);
+        if door.is_slide {
+            let center = (start + cursor) / 2.0;
+            gizmos.line_2d(center, center + door.slide_offset, PATH_COLOR);
+        }
+    }
+    gizmos.circle_2d(cursor, editor_state.brush_size, PREVIEW_COLOR);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_near(a: Vec2, b: Vec2) {
+        assert!(a.abs_diff_eq(b, 1e-4), "{:?} != {:?}", a, b);
+    }
+
+    fn door(closed_secs: f32, open_secs: f32, offset_secs: f32) -> DynamicWall {
+        let schedule = WallSchedule::Door { closed_secs, open_secs, offset_secs };
+        DynamicWall::new(Vec2::ZERO, Vec2::new(0.0, 50.0), DYNAMIC_WALL_RADIUS, schedule)
+    }
+
+    fn slide(path: Vec<(f32, f32)>, secs: f32, offset_secs: f32) -> DynamicWall {
+        let schedule = WallSchedule::Slide { path, secs, offset_secs };
+        DynamicWall::new(Vec2::ZERO, Vec2::new(0.0, 50.0), DYNAMIC_WALL_RADIUS, schedule)
+    }
+
+    #[test]
+    fn door_is_closed_then_open() {
+        let wall = door(2.0, 3.0, 0.0);
+        let closed = Some((Vec2::ZERO, Vec2::new(0.0, 50.0)));
+        assert_eq!(wall.segment_at(0.0), closed);
+        assert_eq!(wall.segment_at(1.9), closed);
+        assert_eq!(wall.segment_at(2.1), None);
+        assert_eq!(wall.segment_at(4.9), None);
+        assert_eq!(wall.segment_at(5.5), closed);
+        assert_eq!(wall.segment_at(-1.0), None);
+    }
+
+    #[test]
+    fn door_offset_shifts_the_cycle() {
+        let wall = door(2.0, 3.0, 2.5);
+        assert_eq!(wall.segment_at(0.0), None);
+        assert!(wall.segment_at(2.6).is_some());
+    }
+
+    #[test]
+    fn door_without_period_stays_closed() {
+        let wall = door(0.0, 0.0, 0.0);
+        assert!(wall.segment_at(0.0).is_some());
+        assert!(wall.segment_at(12.3).is_some());
+    }
+
+    #[test]
+    fn slide_goes_along_the_path_and_back() {
+        // 10 units right then 20 up, 30 long in 3 s
+        let wall = slide(vec![(10.0, 0.0), (10.0, 20.0)], 3.0, 0.0);
+        let offset_at = |secs: f32| wall.segment_at(secs).unwrap().0;
+        assert_near(offset_at(0.0), Vec2::ZERO);
+        assert_near(offset_at(0.5), Vec2::new(5.0, 0.0));
+        assert_near(offset_at(1.5), Vec2::new(10.0, 5.0));
+        assert_near(offset_at(3.0), Vec2::new(10.0, 20.0));
+        assert_near(offset_at(4.5), Vec2::new(10.0, 5.0));
+        assert_near(offset_at(6.0), Vec2::ZERO);
+        assert_near(offset_at(6.5), Vec2::new(5.0, 0.0));
+
+        // Both ends move together
+        let (from, to) = wall.segment_at(1.5).unwrap();
+        assert_near(to - from, Vec2::new(0.0, 50.0));
+    }
+
+    #[test]
+    fn slide_offset_and_zero_duration() {
+        let wall = slide(vec![(30.0, 0.0)], 3.0, 1.0);
+        assert_near(wall.segment_at(0.0).unwrap().0, Vec2::new(10.0, 0.0));
+
+        let wall = slide(vec![(30.0, 0.0)], 0.0, 0.0);
+        assert_near(wall.segment_at(1.0).unwrap().0, Vec2::ZERO);
+    }
+
+    #[test]
+    fn path_point_uses_length_fractions() {
+        let points = slide_points(&[(10.0, 0.0), (10.0, 0.0), (10.0, 30.0)]);
+        assert_near(path_point(&points, 0.0), Vec2::ZERO);
+        assert_near(path_point(&points, 0.25), Vec2::new(10.0, 0.0));
+        assert_near(path_point(&points, 0.5), Vec2::new(10.0, 10.0));
+        assert_near(path_point(&points, 1.0), Vec2::new(10.0, 30.0));
+        // Fractions outside 0..1 stay at the ends
+        assert_near(path_point(&points, -0.5), Vec2::ZERO);
+        assert_near(path_point(&points, 1.5), Vec2::new(10.0, 30.0));
+    }
+
+    #[test]
+    fn path_point_without_length() {
+        assert_near(path_point(&[], 0.5), Vec2::ZERO);
+        assert_near(path_point(&slide_points(&[]), 0.5), Vec2::ZERO);
+        assert_near(path_point(&slide_points(&[(0.0, 0.0)]), 0.5), Vec2::ZERO);
+    }
+}
@@ -0,0 +1,236 @@
+//! Food that changes over the run: sources that regrow their storage, and a spawner
+//! that places new sources at random open cells. New sources land in a distance band
+//! around the nest, at most one every `interval` seconds of sim time and only while
+//! fewer than `max_sources` are on the map.
+
+use crate::{
+    ant::{spawn_food, Food},
+    gui::{run_if_not_paused, SimClock, SimSettings},
+    map::{FoodSpec, MapSize, Nest, ObstacleMap, Terrain},
+    *,
+};
+use bevy::prelude::*;
+use bevy_egui::{egui, EguiContexts};
+use rand::{thread_rng, Rng};
+use serde::{Deserialize, Serialize};
+use std::f32::consts::TAU;
+
+pub struct FoodPlugin;
+
+/// Storage a source regains over time
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Debug)]
+pub struct Regrow {
+    /// Storage stops growing here
+    pub capacity: i32,
+    /// Units per second of sim time
+    pub rate: f32,
+}
+
+impl Regrow {
+    /// Adds `dt` seconds of growth, `regrown` carries the fraction of a unit between calls
+    pub fn grow(&self, storage: &mut i32, regrown: &mut f32, dt: f32) {
+        if *storage >= self.capacity {
+            *regrown = 0.0;
+            return;
+        }
+        *regrown += self.rate * dt;
+        let whole = regrown.floor();
+        *regrown -= whole;
+        *storage = (*storage + whole as i32).min(self.capacity);
+    }
+}
+
+/// Where, how often and what kind of food the spawner places
+#[derive(Clone, Copy, Debug)]
+pub struct FoodSpawnRules {
+    /// Sim seconds between two new sources
+    pub interval: f32,
+    /// Distance band from the nest
+    pub min_dist: f32,
+    pub max_dist: f32,
+    /// No new source while this many are on the map
+    pub max_sources: usize,
+    pub storage: i32,
+    pub quality: f32,
+    /// Units per second, 0 for sources that disappear when eaten
+    pub regrow_rate: f32,
+}
+
+impl Default for FoodSpawnRules {
+    fn default() -> Self {
+        Self {
+            interval: FOOD_SPAWN_INTERVAL,
+            min_dist: FOOD_SPAWN_MIN_DIST,
+            max_dist: FOOD_SPAWN_MAX_DIST,
+            max_sources: FOOD_SPAWN_MAX_SOURCES,
+            storage: FOOD_SPAWN_STORAGE,
+            quality: FOOD_QUALITY,
+            regrow_rate: 0.0,
+        }
+    }
+}
+
+impl FoodSpawnRules {
+    /// Random open spot in the distance band, None if no attempt found one
+    pub fn pick_pos(
+        &self,
+        nest: Vec2,
+        obstacle_map: &ObstacleMap,
+        map_size: &MapSize,
+        rng: &mut impl Rng,
+    ) -> Option<Vec2> {
+        let (w, h) = (map_size.width, map_size.height);
+        let (min_sq, max_sq) = (self.min_dist * self.min_dist, self.max_dist * self.max_dist);
+        (0..FOOD_SPAWN_ATTEMPTS).find_map(|_| {
+            // Uniform over the area of the ring, not bunched up at its inner edge
+            let dist = rng.gen_range(min_sq..=max_sq.max(min_sq)).sqrt();
+            let pos = nest + Vec2::from_angle(rng.gen_range(0.0..TAU)) * dist;
+            let is_open = obstacle_map.terrain_at(pos.x, pos.y, w, h) == Terrain::Open
+                && !obstacle_map.is_obstacle_in_radius(pos.x, pos.y, FOOD_SPAWN_CLEARANCE, w, h);
+            is_open.then_some(pos)
+        })
+    }
+
+    pub fn spec(&self, pos: Vec2) -> FoodSpec {
+        FoodSpec {
+            quality: self.quality,
+            regrow: (self.regrow_rate > 0.0).then_some(Regrow {
+                capacity: self.storage,
+                rate: self.regrow_rate,
+            }),
+            ..FoodSpec::new(pos.x, pos.y, self.storage)
+        }
+    }
+}
+
+#[derive(Resource, Default)]
+pub struct FoodSpawner {
+    pub is_enabled: bool,
+    pub rules: FoodSpawnRules,
+    /// Sim time of the last spawn
+    last_spawn: f32,
+}
+
+impl Plugin for FoodPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(FoodSpawner::default())
+            .add_systems(Update, (regrow_food, spawn_new_food).run_if(run_if_not_paused))
+            .add_systems(Update, food_spawner_ui);
+    }
+}
+
+fn regrow_food(mut food_query: Query<&mut Food>, time: Res<Time>) {
+    let dt = time.delta_seconds();
+    for mut food in food_query.iter_mut() {
+        let Some(regrow) = food.regrow else {
+            continue;
+        };
+        // Full sources stay untouched so their sprite isn't updated every frame
+        if food.storage >= regrow.capacity {
+            continue;
+        }
+        let food = food.as_mut();
+        regrow.grow(&mut food.storage, &mut food.regrown, dt);
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn spawn_new_food(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    mut spawner: ResMut<FoodSpawner>,
+    clock: Res<SimClock>,
+    obstacle_map: Res<ObstacleMap>,
+    map_size: Res<MapSize>,
+    nest: Res<Nest>,
+    food_query: Query<With<Food>>,
+) {
+    // The clock went back on a reset or rewind
+    if clock.elapsed_secs < spawner.last_spawn {
+        spawner.last_spawn = clock.elapsed_secs;
+    }
+    if !spawner.is_enabled || clock.elapsed_secs - spawner.last_spawn < spawner.rules.interval {
+        return;
+    }
+    spawner.last_spawn = clock.elapsed_secs;
+    if food_query.iter().len() >= spawner.rules.max_sources {
+        return;
+    }
+    if let Some(pos) = spawner.rules.pick_pos(nest.0, &obstacle_map, &map_size, &mut thread_rng()) {
+        spawn_food(&mut commands, &asset_server, spawner.rules.spec(pos));
+    }
+}
+
+fn food_spawner_ui(
+    mut contexts: EguiContexts,
+    mut spawner: ResMut<FoodSpawner>,
+    settings: Res<SimSettings>,
+    food_query: Query<With<Food>>,
+) {
+    if !settings.is_show_menu {
+        return;
+    }
+
+    let ctx = contexts.ctx_mut();
+    egui::Window::new("Food Spawner")
+        .default_pos(egui::pos2(W - 340.0, H - 840.0))
+        .default_open(false)
+        .show(ctx, |ui| {
+            ui.checkbox(&mut spawner.is_enabled, "Spawn food");
+            let rules = &mut spawner.rules;
+            ui.add(egui::Slider::new(&mut rules.interval, 1.0..=120.0).text("Every (s)"));
+            ui.add(egui::Slider::new(&mut rules.min_dist, 0.0..=FOOD_SPAWN_MAX_RANGE).text("Min dist"));
+            ui.add(egui::Slider::new(&mut rules.max_dist, 0.0..=FOOD_SPAWN_MAX_RANGE).text("Max dist"));
+            rules.max_dist = rules.max_dist.max(rules.min_dist);
+            ui.add(egui::Slider::new(&mut rules.max_sources, 1..=50).text("Max sources"));
+            ui.add(egui::Slider::new(&mut rules.storage, 1..=MAX_FOOD_STORAGE).text("Storage"));
+            ui.add(egui::Slider::new(&mut rules.quality, 0.1..=MAX_FOOD_QUALITY).text("Quality"));
+            ui.add(egui::Slider::new(&mut rules.regrow_rate, 0.0..=MAX_FOOD_REGROW_RATE).text("Regrow /s"));
+            ui.label(format!("{} sources on the map", food_query.iter().len()));
+        });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn grow_carries_fractions_between_calls() {
+        let regrow = Regrow { capacity: 10, rate: 1.0 };
+        let (mut storage, mut regrown) = (0, 0.0);
+        for _ in 0..3 {
+            regrow.grow(&mut storage, &mut regrown, 0.25);
+        }
+        assert_eq!(storage, 0);
+        assert_eq!(regrown, 0.75);
+
+        regrow.grow(&mut storage, &mut regrown, 0.25);
+        assert_eq!(storage, 1);
+        assert_eq!(regrown, 0.0);
+    }
+
+    #[test]
+    fn grow_adds_several_units_in_a_long_step() {
+        let regrow = Regrow { capacity: 10, rate: 4.0 };
+        let (mut storage, mut regrown) = (2, 0.0);
+        regrow.grow(&mut storage, &mut regrown, 1.125);
+        assert_eq!(storage, 6);
+        assert_eq!(regrown, 0.5);
+    }
+
+    #[test]
+    fn grow_stops_at_capacity() {
+        let regrow = Regrow { capacity: 5, rate: 4.0 };
+        let (mut storage, mut regrown) = (3, 0.0);
+        regrow.grow(&mut storage, &mut regrown, 10.0);
+        assert_eq!(storage, 5);
+
+        // A full source doesn't bank growth for after the next pickup
+        regrown = 0.75;
+        regrow.grow(&mut storage, &mut regrown, 1.0);
+        assert_eq!((storage, regrown), (5, 0.0));
+        storage -= 1;
+        regrow.grow(&mut storage, &mut regrown, 0.125);
+        assert_eq!((storage, regrown), (4, 0.5));
+    }
+}
@@ -0,0 +1,304 @@
+//! PNG frame sequence export.
+//! A small software rasteriser draws the pheromone layers, walls, nest, food and
+//! ants into an RGBA buffer, so frames can be written without a window: from the
+//! "Frames" window of the app, or with `ants frames` from a headless run.
+
+use crate::{
+    ant::{Ant, AntTask, CurrentTask, Food},
+    grid::add_map_to_grid_img,
+    gui::{SimClock, SimConfig, SimSettings},
+    headless::HeadlessSim,
+    map::{MapSize, Nest, ObstacleMap},
+    pheromone::Pheromones,
+    utils::save_png,
+    *,
+};
+use bevy::prelude::*;
+use bevy_egui::{egui, EguiContexts};
+
+pub struct FramesPlugin;
+
+/// Everything that ends up in a frame
+pub struct FrameScene<'a> {
+    pub map_size: MapSize,
+    pub pheromones: &'a Pheromones,
+    pub obstacle_map: &'a ObstacleMap,
+    pub nest: Vec2,
+    pub food: Vec<Vec2>,
+    pub ants: Vec<(Vec2, AntTask)>,
+    pub max_pheromone_strength: f32,
+    pub is_log_scale: bool,
+}
+
+impl<'a> FrameScene<'a> {
+    pub fn from_headless(sim: &'a HeadlessSim) -> Self {
+        Self {
+            map_size: sim.map_size,
+            pheromones: &sim.pheromones,
+            obstacle_map: &sim.obstacle_map,
+            nest: sim.home_pos,
+            food: sim.food.iter().map(|f| f.pos).collect(),
+            ants: sim.ants.iter().map(|a| (a.pos, a.task)).collect(),
+            max_pheromone_strength: sim.config.max_pheromone_strength,
+            is_log_scale: false,
+        }
+    }
+}
+
+pub struct Frame {
+    pub width: usize,
+    pub height: usize,
+    pub rgba: Vec<u8>,
+}
+
+const NEST_COLOR: (u8, u8, u8) = (170, 110, 60);
+const FOOD_COLOR: (u8, u8, u8) = (255, 200, 40);
+// Same hues as `task_color`, without the bloom headroom
+const FIND_FOOD_COLOR: (u8, u8, u8) = (110, 110, 255);
+const FIND_HOME_COLOR: (u8, u8, u8) = (120, 255, 120);
+
+impl Frame {
+    /// Renders the scene with `cell_px` png pixels per pheromone grid cell
+    pub fn render(scene: &FrameScene, cell_px: usize) -> Self {
+        let map_size = scene.map_size;
+        let (w, h) = (
+            map_size.width as usize / PH_UNIT_GRID_SIZE,
+            map_size.height as usize / PH_UNIT_GRID_SIZE,
+        );
+
+        // Same cell image as the pheromone sprite
+        let mut cells = vec![0; w * h * 4];
+        for (grid, high_color) in [
+            (&scene.pheromones.to_home, PH_COLOR_TO_HOME_MAX),
+            (&scene.pheromones.to_food, PH_COLOR_TO_FOOD_MAX),
+        ] {
+            add_map_to_grid_img(
+                grid.get_signals(),
+                &mut cells,
+                true,
+                map_size.width,
+                map_size.height,
+                scene.max_pheromone_strength,
+                grid.color,
+                high_color,
+                scene.is_log_scale,
+            );
+        }
+
+        // Flatten onto the background, terrain and decay tint are drawn on top like the obstacle sprite
+        let unit = PH_UNIT_GRID_SIZE as f32;
+        let over = |c: [u8; 3], a: u8, under: [u8; 3]| {
+            let a = a as f32 / 255.0;
+            [0, 1, 2].map(|i| (c[i] as f32 * a + under[i] as f32 * (1.0 - a)) as u8)
+        };
+        for row in 0..h {
+            for col in 0..w {
+                let idx = (row * w + col) * 4;
+                let x = col as f32 * unit - map_size.width / 2.0 + unit / 2.0;
+                let y = map_size.height / 2.0 - row as f32 * unit - unit / 2.0;
+                let ph = over(
+                    [cells[idx], cells[idx + 1], cells[idx + 2]],
+                    cells[idx + 3],
+                    [BG_COLOR.0, BG_COLOR.1, BG_COLOR.2],
+                );
+                let [r, g, b, a] = scene.obstacle_map.cell_color_at(x, y, map_size.width, map_size.height);
+                let [r, g, b] = over([r, g, b], a, ph);
+                cells[idx..idx + 4].copy_from_slice(&[r, g, b, 255]);
+            }
+        }
+
+        // Nearest neighbour upscale
+        let (width, height) = (w * cell_px, h * cell_px);
+        let mut rgba = vec![0; width * height * 4];
+        for y in 0..height {
+            for x in 0..width {
+                let src = ((y / cell_px) * w + x / cell_px) * 4;
+                let dst = (y * width + x) * 4;
+                rgba[dst..dst + 4].copy_from_slice(&cells[src..src + 4]);
+            }
+        }
+
+        let mut frame = Self { width, height, rgba };
+        let px_per_unit = cell_px as f32 / unit;
+        let to_px = |p: Vec2| {
+            Vec2::new(
+                (p.x + map_size.width / 2.0) * px_per_unit,
+                (map_size.height / 2.0 - p.y) * px_per_unit,
+            )
+        };
+        frame.fill_circle(to_px(scene.nest), HOME_RADIUS * px_per_unit, NEST_COLOR);
+        for food in scene.food.iter() {
+            frame.fill_circle(to_px(*food), FOOD_PICKUP_RADIUS * 0.5 * px_per_unit, FOOD_COLOR);
+        }
+        let ant_radius = (2.0 * px_per_unit).max(1.0);
+        for (pos, task) in scene.ants.iter() {
+            let color = match task {
+                AntTask::FindFood => FIND_FOOD_COLOR,
+                AntTask::FindHome => FIND_HOME_COLOR,
+            };
+            frame.fill_circle(to_px(*pos), ant_radius, color);
+        }
+        frame
+    }
+
+    fn fill_circle(&mut self, center: Vec2, radius: f32, color: (u8, u8, u8)) {
+        let (x0, x1) = ((center.x - radius).floor().max(0.0) as usize, (center.x + radius).ceil() as usize);
+        let (y0, y1) = ((center.y - radius).floor().max(0.0) as usize, (center.y + radius).ceil() as usize);
+        for y in y0..y1.min(self.height) {
+            for x in x0..x1.min(self.width) {
+                let d = Vec2::new(x as f32 + 0.5, y as f32 + 0.5) - center;
+                if d.length_squared() <= radius * radius {
+                    let idx = (y * self.width + x) * 4;
+                    self.rgba[idx..idx + 4].copy_from_slice(&[color.0, color.1, color.2, 255]);
+                }
+            }
+        }
+    }
+
+    pub fn save(self, path: &str) -> Result<(), String> {
+        save_png(path, self.width, self.height, self.rgba)
+    }
+}
+
+/// Writes numbered frames to a directory
+pub struct FrameWriter {
+    dir: String,
+    next_index: usize,
+}
+
+impl FrameWriter {
+    pub fn new(dir: &str) -> Result<Self, String> {
+        std::fs::create_dir_all(dir).map_err(|e| format!("{}: {}", dir, e))?;
+        Ok(Self {
+            dir: dir.to_string(),
+            next_index: 0,
+        })
+    }
+
+    pub fn write(&mut self, frame: Frame) -> Result<String, String> {
+        let path = format!("{}/frame_{:05}.png", self.dir, self.next_index);
+        frame.save(&path)?;
+        self.next_index += 1;
+        Ok(path)
+    }
+
+    pub fn frame_count(&self) -> usize {
+        self.next_index
+    }
+}
+
+#[derive(Resource)]
+pub struct FrameExport {
+    pub writer: Option<FrameWriter>,
+    /// Sim seconds between frames
+    pub interval_secs: f32,
+    pub cell_px: usize,
+    next_secs: f32,
+}
+
+impl Default for FrameExport {
+    fn default() -> Self {
+        Self {
+            writer: None,
+            interval_secs: FRAME_INTERVAL_SECS,
+            cell_px: FRAME_CELL_PX,
+            next_secs: 0.0,
+        }
+    }
+}
+
+impl Plugin for FramesPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(FrameExport::default())
+            .add_systems(Update, (export_frames, frames_ui));
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn export_frames(
+    mut export: ResMut<FrameExport>,
+    settings: Res<SimSettings>,
+    config: Res<SimConfig>,
+    clock: Res<SimClock>,
+    pheromones: Res<Pheromones>,
+    obstacle_map: Res<ObstacleMap>,
+    map_size: Res<MapSize>,
+    nest: Res<Nest>,
+    food_query: Query<&Transform, With<Food>>,
+    ant_query: Query<(&Transform, &CurrentTask), With<Ant>>,
+) {
+    if export.writer.is_none() || !settings.is_running() {
+        return;
+    }
+    // The clock went back after a reset or a rewind
+    if clock.elapsed_secs + export.interval_secs < export.next_secs {
+        export.next_secs = clock.elapsed_secs;
+    }
+    if clock.elapsed_secs < export.next_secs {
+        return;
+    }
+    export.next_secs = clock.elapsed_secs + export.interval_secs;
+
+    let scene = FrameScene {
+        map_size: *map_size,
+        pheromones: &pheromones,
+        obstacle_map: &obstacle_map,
+        nest: nest.0,
+        food: food_query.iter().map(|tr| tr.translation.truncate()).collect(),
+        ants: ant_query
+            .iter()
+            .map(|(tr, task)| (tr.translation.truncate(), task.0))
+            .collect(),
+        max_pheromone_strength: config.max_pheromone_strength,
+        is_log_scale: settings.is_ph_log_scale,
+    };
+    let frame = Frame::render(&scene, export.cell_px);
+    if let Some(writer) = export.writer.as_mut() {
+        if let Err(e) = writer.write(frame) {
+            eprintln!("Failed to write frame: {}", e);
+            export.writer = None;
+        }
+    }
+}
+
+fn frames_ui(
+    mut contexts: EguiContexts,
+    mut export: ResMut<FrameExport>,
+    settings: Res<SimSettings>,
+    clock: Res<SimClock>,
+) {
+    if !settings.is_show_menu {
+        return;
+    }
+
+    let ctx = contexts.ctx_mut();
+    egui::Window::new("Frames")
+        .default_pos(egui::pos2(W - 340.0, H - 700.0))
+        .default_open(false)
+        .show(ctx, |ui| {
+            let export = &mut *export;
+            if let Some(writer) = &export.writer {
+                ui.label(format!("Writing to {}/: {} frames", FRAMES_DIR, writer.frame_count()));
+                if ui.button("Stop").clicked() {
+                    export.writer = None;
+                }
+                return;
+            }
+            ui.add(
+                egui::Slider::new(&mut export.interval_secs, 0.05..=10.0)
+                    .logarithmic(true)
+                    .text("Every (sim s)"),
+            );
+            ui.add(egui::Slider::new(&mut export.cell_px, 1..=5).text("Px per cell"));
+            if ui.button("Start").clicked() {
+                match FrameWriter::new(FRAMES_DIR) {
+                    Ok(writer) => {
+                        println!("Writing frames to {}/", FRAMES_DIR);
+                        export.writer = Some(writer);
+                        export.next_secs = clock.elapsed_secs;
+                    }
+                    Err(e) => eprintln!("Failed to start frame export: {}", e),
+                }
+            }
+        });
+}
@@ -7,7 +7,6 @@ use kd_tree::KdTree;
 use std::{cmp, collections::HashMap};
 
 pub struct DecayGrid {
-    max_allowed_value: f32,
     values: HashMap<(i32, i32), f32>,
 }
 
@@ -23,7 +22,7 @@ impl WorldGrid {
     pub fn new(color: (u8, u8, u8), signals: HashMap<(i32, i32), f32>) -> Self {
         Self {
             color,
-            signals: DecayGrid::new(signals, MAX_PHEROMONE_STRENGTH),
+            signals: DecayGrid::new(signals),
             tree: None,
             steer_cache: HashMap::new(),
         }
@@ -39,6 +38,12 @@ impl WorldGrid {
         self.signals.add_value(&key, value, value * 0.25, max_val);
     }
 
+    /// Overwrites the signal of the cell containing `pos`
+    pub fn set_value_at(&mut self, pos: Vec2, value: f32) {
+        let key = self.get_ph_key(pos.x as i32, pos.y as i32);
+        self.signals.values.insert(key, value);
+    }
+
     pub fn update_tree(&mut self) {
         let mut pts = Vec::new();
         for (k, &v) in self.signals.values.iter() {
@@ -116,8 +121,18 @@ impl WorldGrid {
         None
     }
 
-    pub fn decay_signals(&mut self, rate: f32) {
-        self.signals.decay_values(rate);
+    /// Decays every cell by `rate` times the multiplier at the cell centre
+    pub fn decay_signals(&mut self, rate: f32, multiplier_at: impl Fn(Vec2) -> f32) {
+        let unit = PH_UNIT_GRID_SIZE as f32;
+        self.signals.decay_values(rate, |&(x, y)| {
+            // Keys truncate towards zero, so negative cells extend below their corner and
+            // cell 0 spans both sides of the axis
+            let center = |k: i32| match k {
+                0 => 0.0,
+                _ => k as f32 * unit + unit / 2.0 * (k as f32).signum(),
+            };
+            multiplier_at(Vec2::new(center(x), center(y)))
+        });
     }
 
     pub fn drop_zero_signals(&mut self) {
@@ -128,6 +143,12 @@ impl WorldGrid {
         self.signals.get_values()
     }
 
+    /// Signal stored in the cell containing the given world position
+    pub fn get_value_at(&self, pos: Vec2) -> f32 {
+        let key = self.get_ph_key(pos.x as i32, pos.y as i32);
+        self.signals.get_value(&key)
+    }
+
     pub fn get_signals_size(&self) -> usize {
         self.signals.values.len()
     }
@@ -149,11 +170,8 @@ impl WorldGrid {
 }
 
 impl DecayGrid {
-    pub fn new(values: HashMap<(i32, i32), f32>, max_allowed_value: f32) -> Self {
-        Self {
-            values,
-            max_allowed_value,
-        }
+    pub fn new(values: HashMap<(i32, i32), f32>) -> Self {
+        Self { values }
     }
 
     pub fn add_value(&mut self, key: &(i32, i32), value: f32, increment_value: f32, max_val: f32) {
@@ -171,9 +189,10 @@ impl DecayGrid {
         }
     }
 
-    pub fn decay_values(&mut self, decay_rate: f32) {
-        for (_, v) in self.values.iter_mut() {
-            *v = f32::max(*v - decay_rate, 0.0);
+    /// Subtracts `decay_rate` scaled by the per-cell multiplier from every value
+    pub fn decay_values(&mut self, decay_rate: f32, multiplier: impl Fn(&(i32, i32)) -> f32) {
+        for (k, v) in self.values.iter_mut() {
+            *v = f32::max(*v - decay_rate * multiplier(k), 0.0);
         }
     }
 
@@ -184,17 +203,23 @@ impl DecayGrid {
     pub fn get_values(&self) -> &HashMap<(i32, i32), f32> {
         &self.values
     }
+
+    pub fn get_value(&self, key: &(i32, i32)) -> f32 {
+        self.values.get(key).copied().unwrap_or(0.0)
+    }
 }
 
+#[allow(clippy::too_many_arguments)]
 pub fn add_map_to_grid_img(
     map: &HashMap<(i32, i32), f32>,
-    img_bytes: &mut Vec<u8>,
+    img_bytes: &mut [u8],
     use_grid_pos: bool,
     map_w_pixels: f32,
     map_h_pixels: f32,
     max_val: f32, // From config
     base_color: (u8, u8, u8),
     high_color: (u8, u8, u8),
+    log_scale: bool,
 ) {
     let w = map_w_pixels as usize / PH_UNIT_GRID_SIZE;
     for (k, v) in map.iter() {
@@ -217,16 +242,20 @@ pub fn add_map_to_grid_img(
         }
 
         let idx = y * w as i32 + x;
+        // Interpolate color based on value / max_val
+        let t = ph_scale(*v, max_val, log_scale);
         // Strength determines opacity mostly
-        let strength = cmp::min((*v as u32).saturating_mul(5), u8::MAX.into()) as u8;
+        let strength = if log_scale {
+            (t * u8::MAX as f32) as u8
+        } else {
+            cmp::min((*v as u32).saturating_mul(5), u8::MAX.into()) as u8
+        };
 
         let idx = (idx as usize).saturating_mul(4);
         if idx.saturating_add(3) >= img_bytes.len() || strength < PH_GRID_VIZ_MIN_STRENGTH {
             continue;
         }
         
-        // Interpolate color based on value / max_val
-        let t = (*v / max_val).clamp(0.0, 1.0);
         let r = (base_color.0 as f32 * (1.0 - t) + high_color.0 as f32 * t) as u8;
         let g = (base_color.1 as f32 * (1.0 - t) + high_color.1 as f32 * t) as u8;
         let b = (base_color.2 as f32 * (1.0 - t) + high_color.2 as f32 * t) as u8;
@@ -237,3 +266,207 @@ pub fn add_map_to_grid_img(
         img_bytes[idx + 2] = b;
     }
 }
+
+/// Position of a value on the colour ramp, 0..1. The log scale keeps weak trails
+/// visible when `max_val` is large
+pub fn ph_scale(v: f32, max_val: f32, log_scale: bool) -> f32 {
+    let t = if log_scale {
+        v.max(0.0).ln_1p() / max_val.ln_1p()
+    } else {
+        v / max_val
+    };
+    t.clamp(0.0, 1.0)
+}
+
+/// Inverse of `ph_scale`, the value that lands at `t` on the ramp
+pub fn ph_unscale(t: f32, max_val: f32, log_scale: bool) -> f32 {
+    if log_scale {
+        (t * max_val.ln_1p()).exp() - 1.0
+    } else {
+        t * max_val
+    }
+}
+
+/// Isolines of a sparse grid with marching squares. Corners are the grid keys, the
+/// returned segments are in key coordinates together with the index of their level.
+/// `min_key`/`max_key` bound the sampled area, missing keys count as 0
+pub fn contour_segments(
+    map: &HashMap<(i32, i32), f32>,
+    min_key: (i32, i32),
+    max_key: (i32, i32),
+    levels: &[f32],
+) -> Vec<(usize, Vec2, Vec2)> {
+    let nx = (max_key.0 - min_key.0 + 1).max(0) as usize;
+    let ny = (max_key.1 - min_key.1 + 1).max(0) as usize;
+    let mut dense = vec![0.0; nx * ny];
+    for (&(x, y), &v) in map.iter() {
+        let (ix, iy) = (x - min_key.0, y - min_key.1);
+        if ix >= 0 && iy >= 0 && (ix as usize) < nx && (iy as usize) < ny {
+            dense[iy as usize * nx + ix as usize] = v;
+        }
+    }
+
+    let Some(min_level) = levels.iter().copied().reduce(f32::min) else {
+        return Vec::new();
+    };
+    let mut segments = Vec::new();
+    for iy in 0..ny.saturating_sub(1) {
+        for ix in 0..nx.saturating_sub(1) {
+            // Corners counter-clockwise from bottom left
+            let a = dense[iy * nx + ix];
+            let b = dense[iy * nx + ix + 1];
+            let c = dense[(iy + 1) * nx + ix + 1];
+            let d = dense[(iy + 1) * nx + ix];
+            // Most of the map is empty
+            if a.max(b).max(c).max(d) < min_level {
+                continue;
+            }
+            let origin = Vec2::new((ix as i32 + min_key.0) as f32, (iy as i32 + min_key.1) as f32);
+
+            for (level_idx, &level) in levels.iter().enumerate() {
+                let crossing = |v0: f32, v1: f32, p0: Vec2, p1: Vec2| {
+                    ((v0 >= level) != (v1 >= level)).then(|| p0.lerp(p1, (level - v0) / (v1 - v0)))
+                };
+                let (pa, pb, pc, pd) = (Vec2::ZERO, Vec2::X, Vec2::ONE, Vec2::Y);
+                // Bottom, right, top, left
+                let edges = [
+                    crossing(a, b, pa, pb),
+                    crossing(b, c, pb, pc),
+                    crossing(c, d, pc, pd),
+                    crossing(d, a, pd, pa),
+                ];
+                let found: Vec<Vec2> = edges.iter().flatten().copied().collect();
+                match found.len() {
+                    2 => segments.push((level_idx, origin + found[0], origin + found[1])),
+                    4 => {
+                        // Saddle, the centre decides which diagonal is connected
+                        let is_center_high = (a + b + c + d) / 4.0 >= level;
+                        let pairs = if is_center_high == (a >= level) {
+                            [(0, 1), (2, 3)]
+                        } else {
+                            [(3, 0), (1, 2)]
+                        };
+                        for (i, j) in pairs {
+                            segments.push((level_idx, origin + found[i], origin + found[j]));
+                        }
+                    }
+                    _ => {}
+                }
+            }
+        }
+    }
+    segments
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_near(a: Vec2, b: Vec2) {
+        assert!(a.distance(b) < 1e-4, "{:?} != {:?}", a, b);
+    }
+
+    /// Map of one cell with corners a, b, c, d counter-clockwise from (0, 0)
+    fn cell(a: f32, b: f32, c: f32, d: f32) -> HashMap<(i32, i32), f32> {
+        HashMap::from([((0, 0), a), ((1, 0), b), ((1, 1), c), ((0, 1), d)])
+    }
+
+    #[test]
+    fn ph_unscale_inverts_ph_scale() {
+        for log_scale in [false, true] {
+            for v in [0.0, 1.0, 12.5, 300.0, 4999.0] {
+                let t = ph_scale(v, 5000.0, log_scale);
+                let back = ph_unscale(t, 5000.0, log_scale);
+                assert!((back - v).abs() < v.max(1.0) * 1e-3, "{} -> {} -> {}", v, t, back);
+            }
+        }
+    }
+
+    #[test]
+    fn ph_scale_clamps_to_ramp() {
+        assert_eq!(ph_scale(-5.0, 100.0, false), 0.0);
+        assert_eq!(ph_scale(500.0, 100.0, false), 1.0);
+        assert_eq!(ph_scale(500.0, 100.0, true), 1.0);
+        // The log scale lifts weak signals
+        assert!(ph_scale(10.0, 100.0, true) > ph_scale(10.0, 100.0, false));
+    }
+
+    #[test]
+    fn contour_cuts_off_single_high_corner() {
+        let segments = contour_segments(&cell(1.0, 0.0, 0.0, 0.0), (0, 0), (1, 1), &[0.5]);
+        assert_eq!(segments.len(), 1);
+        let (level, p, q) = segments[0];
+        assert_eq!(level, 0);
+        assert_near(p, Vec2::new(0.5, 0.0));
+        assert_near(q, Vec2::new(0.0, 0.5));
+    }
+
+    #[test]
+    fn contour_saddle_with_high_centre_separates_low_corners() {
+        // a and c high, centre average 0.6 is above the level
+        let segments = contour_segments(&cell(1.0, 0.2, 1.0, 0.2), (0, 0), (1, 1), &[0.5]);
+        assert_eq!(segments.len(), 2);
+        for (_, p, q) in segments {
+            // Each segment cuts off b (bottom right) or d (top left)
+            let near_b = p.y.abs() < 1e-4 && (q.x - 1.0).abs() < 1e-4;
+            let near_d = (p.y - 1.0).abs() < 1e-4 && q.x.abs() < 1e-4;
+            assert!(near_b || near_d, "{:?} -> {:?}", p, q);
+        }
+    }
+
+    #[test]
+    fn contour_saddle_with_low_centre_separates_high_corners() {
+        // a and c high, centre average 0.3 is below the level
+        let segments = contour_segments(&cell(0.6, 0.0, 0.6, 0.0), (0, 0), (1, 1), &[0.5]);
+        assert_eq!(segments.len(), 2);
+        for (_, p, q) in segments {
+            // Each segment cuts off a (bottom left) or c (top right)
+            let near_a = p.x.abs() < 1e-4 && q.y.abs() < 1e-4;
+            let near_c = (p.x - 1.0).abs() < 1e-4 && (q.y - 1.0).abs() < 1e-4;
+            assert!(near_a || near_c, "{:?} -> {:?}", p, q);
+        }
+    }
+
+    #[test]
+    fn contour_levels_are_reported_separately() {
+        let segments = contour_segments(&cell(1.0, 0.0, 0.0, 0.0), (0, 0), (1, 1), &[0.25, 0.75, 2.0]);
+        let levels: Vec<usize> = segments.iter().map(|s| s.0).collect();
+        assert_eq!(levels, vec![0, 1]);
+    }
+
+    #[test]
+    fn contour_of_flat_or_empty_map_is_empty() {
+        assert!(contour_segments(&cell(1.0, 1.0, 1.0, 1.0), (0, 0), (1, 1), &[0.5]).is_empty());
+        assert!(contour_segments(&HashMap::new(), (0, 0), (4, 4), &[0.5]).is_empty());
+        assert!(contour_segments(&cell(1.0, 0.0, 0.0, 0.0), (0, 0), (1, 1), &[]).is_empty());
+    }
+
+    #[test]
+    fn decay_samples_the_center_of_each_cell() {
+        let unit = PH_UNIT_GRID_SIZE as f32;
+        let keys = [(0, 0), (2, 0), (-2, 0), (0, 3), (0, -3), (-1, -1)];
+        let mut grid = WorldGrid::new((0, 0, 0), keys.iter().map(|k| (*k, 10.0)).collect());
+
+        // Every cell reads the multiplier at its own centre, the ones of cell 0 on the axis
+        let sampled = std::cell::RefCell::new(vec![]);
+        grid.decay_signals(1.0, |p| {
+            sampled.borrow_mut().push(p);
+            1.0
+        });
+        let mut sampled = sampled.into_inner();
+        sampled.sort_by(|a, b| a.x.total_cmp(&b.x).then(a.y.total_cmp(&b.y)));
+        let mut expected = [
+            Vec2::ZERO,
+            Vec2::new(2.5 * unit, 0.0),
+            Vec2::new(-2.5 * unit, 0.0),
+            Vec2::new(0.0, 3.5 * unit),
+            Vec2::new(0.0, -3.5 * unit),
+            Vec2::new(-1.5 * unit, -1.5 * unit),
+        ];
+        expected.sort_by(|a, b| a.x.total_cmp(&b.x).then(a.y.total_cmp(&b.y)));
+        assert_eq!(sampled.len(), expected.len());
+        for (a, b) in sampled.iter().zip(expected.iter()) {
+            assert_near(*a, *b);
+        }
+    }
+}
@@ -1,8 +1,20 @@
-use crate::{ant::{Ant, Food}, *};
+use crate::{
+    ant::{
+        colony_color, spawn_food, task_color, Ant, AntEvent, AntEventKind, AntTask, Food, MOVING_COLOR,
+        NEVER_PICKED_UP_COLOR, STUCK_COLOR,
+    },
+    *,
+};
 use bevy::prelude::*;
 use bevy_egui::{egui, EguiContexts, EguiPlugin};
 use bevy_pancam::PanCam;
-use crate::map::{MapSize, ObstacleMap};
+use crate::clipboard::{CellRegion, Clipboard, ClipboardCommand};
+use crate::doors::{DoorTool, DynamicWall};
+use crate::food::Regrow;
+use crate::history::{resized_layout, Edit, EditHistory, HistoryCommand};
+use crate::map::{BrushShape, FoodSpec, LoadMapEvent, MapLayout, MapSize, Nest, ObstacleMap, Terrain};
+use crate::mapgen::{generate, MapGenKind, MapGenSettings};
+use crate::shapes::{drag_actions, outline_actions, polygon_outline, ShapeDraft, ShapeKind};
 use serde::{Deserialize, Serialize};
 
 #[derive(PartialEq, Clone, Copy, Debug)]
@@ -10,14 +22,44 @@ pub enum EditorTool {
     None,
     BrushObstacle,
     EraserObstacle,
+    PaintTerrain,
+    PaintDecay,
     PlaceFood,
     RemoveFood,
+    MoveNest,
+    Shape,
+    Select,
+    Door,
 }
 
 #[derive(Resource)]
 pub struct EditorState {
     pub selected_tool: EditorTool,
     pub brush_size: f32,
+    pub brush_shape: BrushShape,
+    /// Drawn by the shape tool
+    pub shape: ShapeKind,
+    /// Shapes carve open ground instead of walls
+    pub is_shape_erase: bool,
+    /// Painted by the terrain tool
+    pub terrain: Terrain,
+    /// Pheromone decay multiplier painted by the decay tool
+    pub decay_factor: f32,
+    /// Food sources placed by the food tool
+    pub food_storage: i32,
+    pub food_pickup_radius: f32,
+    pub food_quality: f32,
+    /// Units per second the placed food regrows, 0 for static food
+    pub food_regrow_rate: f32,
+    /// Used by the "Generate" menu
+    pub mapgen: MapGenSettings,
+    /// Region picked with the select tool
+    pub selection: Option<CellRegion>,
+    pub clipboard: Option<Clipboard>,
+    /// Clicks with the select tool paste the clipboard
+    pub is_pasting: bool,
+    /// Doors and sliding walls drawn by the door tool
+    pub door: DoorTool,
 }
 
 impl Default for EditorState {
@@ -25,10 +67,79 @@ impl Default for EditorState {
         Self {
             selected_tool: EditorTool::None,
             brush_size: 20.0,
+            brush_shape: BrushShape::Round,
+            shape: ShapeKind::Line,
+            is_shape_erase: false,
+            terrain: Terrain::Sand,
+            decay_factor: 2.0,
+            food_storage: FOOD_STORAGE,
+            food_pickup_radius: FOOD_PICKUP_RADIUS,
+            food_quality: FOOD_QUALITY,
+            food_regrow_rate: 0.0,
+            mapgen: MapGenSettings::default(),
+            selection: None,
+            clipboard: None,
+            is_pasting: false,
+            door: DoorTool::default(),
         }
     }
 }
 
+/// What the ant tint shows
+#[derive(PartialEq, Clone, Copy, Debug)]
+pub enum AntColorMode {
+    Task,
+    PhStrength,
+    TimeSincePickup,
+    Colony,
+    Speed,
+    Stuck,
+}
+
+impl AntColorMode {
+    pub const ALL: [AntColorMode; 6] = [
+        AntColorMode::Task,
+        AntColorMode::PhStrength,
+        AntColorMode::TimeSincePickup,
+        AntColorMode::Colony,
+        AntColorMode::Speed,
+        AntColorMode::Stuck,
+    ];
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            AntColorMode::Task => "Task",
+            AntColorMode::PhStrength => "Ph Strength",
+            AntColorMode::TimeSincePickup => "Time since pickup",
+            AntColorMode::Colony => "Colony",
+            AntColorMode::Speed => "Speed",
+            AntColorMode::Stuck => "Stuck",
+        }
+    }
+}
+
+/// How the pheromone layers are drawn
+#[derive(PartialEq, Clone, Copy, Debug)]
+pub enum PhRenderMode {
+    /// One coloured pixel per grid cell
+    Cells,
+    /// Isolines at `contour_levels` strengths
+    Contours,
+}
+
+/// Blue -> green -> red ramp for `t` in 0..1, brighter than 1.0 so it glows with bloom
+pub fn color_ramp(t: f32) -> Color {
+    let t = t.clamp(0.0, 1.0);
+    let (r, g, b) = if t < 0.5 {
+        let u = t * 2.0;
+        (0.2, 0.4 + 1.6 * u, 2.0 * (1.0 - u) + 0.2)
+    } else {
+        let u = (t - 0.5) * 2.0;
+        (0.2 + 1.8 * u, 2.0 * (1.0 - u) + 0.3, 0.2)
+    };
+    Color::rgb(r, g, b)
+}
+
 pub struct GuiPlugin;
 
 #[derive(Resource)]
@@ -40,7 +151,71 @@ pub struct SimSettings {
     pub is_show_menu: bool,
     pub is_show_ants_path: bool,
     pub is_show_sensor_radius: bool,
+    /// Trajectory of the selected ants
+    pub is_show_trails: bool,
+    /// Limit the sensor/steering debug overlay to the selected ants
+    pub is_debug_selected_only: bool,
+    /// Tooltip with the raw grid values under the cursor
+    pub is_show_cell_info: bool,
+    /// Pheromone gradient arrows on a coarse lattice
+    pub is_show_vector_field: bool,
+    /// Task used for the net steering arrows of the vector field
+    pub field_task: AntTask,
+    pub color_mode: AntColorMode,
+    pub ph_render_mode: PhRenderMode,
+    /// Logarithmic pheromone colour scale, for both the cells and the contours
+    pub is_ph_log_scale: bool,
+    /// Number of isolines per pheromone layer
+    pub contour_levels: usize,
     pub is_paused: bool,
+    /// A recording is being played back, the live simulation is frozen
+    pub is_replaying: bool,
+    /// Frames left to run while paused
+    pub steps_remaining: u32,
+    /// Run while paused until an ant event of this kind happens
+    pub run_until: Option<AntEventKind>,
+    /// Step asked for this frame, started at the beginning of the next one
+    pub step_request: Option<StepRequest>,
+    /// Frames advanced by "Step N"
+    pub step_count: u32,
+}
+
+impl SimSettings {
+    /// True when the simulation systems should advance
+    pub fn is_running(&self) -> bool {
+        let is_stepping = self.steps_remaining > 0 || self.run_until.is_some();
+        (!self.is_paused || is_stepping) && !self.is_replaying
+    }
+
+    /// Pauses and advances the given number of frames
+    pub fn step(&mut self, frames: u32) {
+        self.request_step(StepRequest::Frames(frames));
+    }
+
+    /// Pauses and runs until the next ant event of the given kind
+    pub fn run_until(&mut self, kind: AntEventKind) {
+        self.request_step(StepRequest::Until(kind));
+    }
+
+    /// Stops any stepping now. The request only starts next frame, because the
+    /// simulation systems may already have run in this one
+    fn request_step(&mut self, request: StepRequest) {
+        self.is_paused = true;
+        self.steps_remaining = 0;
+        self.run_until = None;
+        self.step_request = Some(request);
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+pub enum StepRequest {
+    Frames(u32),
+    Until(AntEventKind),
+}
+
+/// Run condition for the simulation systems
+pub fn run_if_not_paused(settings: Res<SimSettings>) -> bool {
+    settings.is_running()
 }
 
 impl Default for SimSettings {
@@ -53,7 +228,21 @@ impl Default for SimSettings {
             is_show_menu: true,
             is_show_ants_path: false,
             is_show_sensor_radius: false,
+            is_show_trails: true,
+            is_debug_selected_only: false,
+            is_show_cell_info: true,
+            is_show_vector_field: false,
+            field_task: AntTask::FindFood,
+            color_mode: AntColorMode::Task,
+            ph_render_mode: PhRenderMode::Cells,
+            is_ph_log_scale: false,
+            contour_levels: 6,
             is_paused: false,
+            is_replaying: false,
+            steps_remaining: 0,
+            run_until: None,
+            step_request: None,
+            step_count: 10,
         }
     }
 }
@@ -61,14 +250,19 @@ impl Plugin for GuiPlugin {
     fn build(&self, app: &mut bevy::prelude::App) {
         app.insert_resource(SimSettings::default())
             .insert_resource(SimStatistics::default())
+            .insert_resource(SimClock::default())
             .insert_resource(SimConfig::default())
             .insert_resource(EditorState::default())
             .add_event::<ResetSimEvent>()
+            .add_event::<EditorActionEvent>()
             .add_systems(Update, settings_dialog)
             .add_systems(Update, settings_toggle)
+            .add_systems(Update, (advance_sim_clock, reset_sim_clock))
+            .add_systems(First, start_step)
+            .add_systems(Last, (stop_on_ant_event, consume_step).chain())
             .add_systems(Update, handle_camera_control)
             .add_systems(Update, editor_ui)
-            .add_systems(Update, handle_editor_input)
+            .add_systems(Update, (handle_editor_input, apply_editor_actions).chain())
             .add_plugins(EguiPlugin)
             .add_systems(Startup, (setup, configure_ui, load_config));
     }
@@ -77,16 +271,33 @@ impl Plugin for GuiPlugin {
 
 fn settings_toggle(
     mut settings: ResMut<SimSettings>,
-    mut ant_query: Query<&mut Visibility, With<Ant>>,
+    ant_query: Query<&mut Visibility, With<Ant>>,
     keys: Res<Input<KeyCode>>,
 ) {
+    // Ctrl shortcuts belong to the editor
+    if keys.any_pressed([KeyCode::ControlLeft, KeyCode::ControlRight]) {
+        return;
+    }
     if keys.just_pressed(KeyCode::Tab) {
         settings.is_show_menu = !settings.is_show_menu;
     }
     if keys.just_pressed(KeyCode::Space) {
         settings.is_paused = !settings.is_paused;
+        settings.steps_remaining = 0;
+        settings.run_until = None;
+        settings.step_request = None;
         println!("Paused: {}", settings.is_paused);
     }
+    if keys.just_pressed(KeyCode::Period) {
+        settings.step(1);
+    }
+    if keys.just_pressed(KeyCode::N) {
+        let frames = settings.step_count;
+        settings.step(frames);
+    }
+    if keys.just_pressed(KeyCode::G) {
+        settings.run_until(AntEventKind::Delivery);
+    }
     if keys.just_pressed(KeyCode::H) {
         settings.is_show_home_ph = !settings.is_show_home_ph;
     }
@@ -97,6 +308,9 @@ fn settings_toggle(
         settings.is_show_ants_path = !settings.is_show_ants_path;
         settings.is_show_sensor_radius = !settings.is_show_sensor_radius;
     }
+    if keys.just_pressed(KeyCode::V) {
+        settings.is_show_vector_field = !settings.is_show_vector_field;
+    }
     if keys.just_pressed(KeyCode::A) {
         settings.is_show_ants = !settings.is_show_ants;
         toggle_ant_visibility(ant_query, settings.is_show_ants);
@@ -106,6 +320,47 @@ fn settings_toggle(
 #[derive(Event)]
 pub struct ResetSimEvent;
 
+fn advance_sim_clock(mut clock: ResMut<SimClock>, settings: Res<SimSettings>, time: Res<Time>) {
+    if !settings.is_running() {
+        return;
+    }
+    clock.tick += 1;
+    clock.elapsed_secs += time.delta_seconds();
+}
+
+fn stop_on_ant_event(mut settings: ResMut<SimSettings>, mut events: EventReader<AntEvent>) {
+    let Some(kind) = settings.run_until else {
+        events.clear();
+        return;
+    };
+    if let Some(event) = events.iter().find(|e| e.kind == kind) {
+        settings.run_until = None;
+        println!("{:?} at ({:.0}, {:.0})", event.kind, event.pos.x, event.pos.y);
+    }
+}
+
+/// Starts the step requested last frame, before any simulation system runs
+fn start_step(mut settings: ResMut<SimSettings>) {
+    match settings.step_request.take() {
+        Some(StepRequest::Frames(frames)) => settings.steps_remaining = frames,
+        Some(StepRequest::Until(kind)) => settings.run_until = Some(kind),
+        None => {}
+    }
+}
+
+/// Counts down single steps at the end of the frame, so the whole frame ran
+fn consume_step(mut settings: ResMut<SimSettings>) {
+    if settings.is_paused && settings.steps_remaining > 0 && !settings.is_replaying {
+        settings.steps_remaining -= 1;
+    }
+}
+
+fn reset_sim_clock(mut events: EventReader<ResetSimEvent>, mut clock: ResMut<SimClock>) {
+    for _ in events.iter() {
+        *clock = SimClock::default();
+    }
+}
+
 #[derive(Resource, Serialize, Deserialize, Clone)]
 pub struct SimConfig {
     pub ph_decay_rate: f32,
@@ -137,6 +392,13 @@ impl Default for SimConfig {
     }
 }
 
+/// Simulated time, only advances while the simulation is running
+#[derive(Default, Resource)]
+pub struct SimClock {
+    pub tick: u64,
+    pub elapsed_secs: f32,
+}
+
 #[derive(Default, Resource)]
 pub struct SimStatistics {
     pub ph_home_size: u32,
@@ -145,6 +407,8 @@ pub struct SimStatistics {
     pub num_ants: usize,
     pub food_cache_size: u32,
     pub home_cache_size: u32,
+    /// Deliveries since the last reset, weighted by food quality
+    pub food_delivered: f32,
 }
 
 fn settings_dialog(
@@ -174,6 +438,7 @@ fn settings_dialog(
                     ui.label(format!("Home cache: {:?}", stats.home_cache_size));
                     ui.label(format!("Scan radius: {:?}", stats.scan_radius.round()));
                     ui.label(format!("Num ants: {:?}", stats.num_ants));
+                    ui.label(format!("Food delivered: {:.1}", stats.food_delivered));
                 });
             egui::CollapsingHeader::new("Settings")
                 .default_open(true)
@@ -182,10 +447,66 @@ fn settings_dialog(
                     ui.checkbox(&mut settings.is_show_food_ph, "Food ph");
                     ui.checkbox(&mut settings.is_show_ants_path, "Paths");
                     ui.checkbox(&mut settings.is_show_sensor_radius, "Radius");
+                    ui.checkbox(&mut settings.is_debug_selected_only, "Debug selected only");
+                    ui.checkbox(&mut settings.is_show_trails, "Selected trails");
+                    ui.checkbox(&mut settings.is_show_cell_info, "Cell tooltip");
+                    ui.horizontal(|ui| {
+                        ui.checkbox(&mut settings.is_show_vector_field, "Vector field");
+                        egui::ComboBox::from_id_source("field_task")
+                            .selected_text(format!("steer {:?}", settings.field_task))
+                            .show_ui(ui, |ui| {
+                                for task in [AntTask::FindFood, AntTask::FindHome] {
+                                    ui.selectable_value(&mut settings.field_task, task, format!("{:?}", task));
+                                }
+                            });
+                    });
+                    ui.horizontal(|ui| {
+                        ui.selectable_value(&mut settings.ph_render_mode, PhRenderMode::Cells, "Cells");
+                        ui.selectable_value(&mut settings.ph_render_mode, PhRenderMode::Contours, "Contours");
+                        ui.checkbox(&mut settings.is_ph_log_scale, "Log scale");
+                    });
+                    if settings.ph_render_mode == PhRenderMode::Contours {
+                        ui.add(egui::Slider::new(&mut settings.contour_levels, 1..=16).text("Contour levels"));
+                    }
                     ui.checkbox(&mut settings.is_camera_follow, "Camera follow");
                     if ui.checkbox(&mut settings.is_show_ants, "Ants").clicked() {
                         toggle_ant_visibility(ant_query, settings.is_show_ants);
                     };
+
+                    egui::ComboBox::from_label("Colour by")
+                        .selected_text(settings.color_mode.label())
+                        .show_ui(ui, |ui| {
+                            for mode in AntColorMode::ALL {
+                                ui.selectable_value(&mut settings.color_mode, mode, mode.label());
+                            }
+                        });
+                    color_legend(ui, settings.color_mode);
+                });
+
+            egui::CollapsingHeader::new("Stepping")
+                .default_open(false)
+                .show(ui, |ui| {
+                    ui.horizontal(|ui| {
+                        if ui.button("Step 1").clicked() {
+                            settings.step(1);
+                        }
+                        if ui.button("Step N").clicked() {
+                            let frames = settings.step_count;
+                            settings.step(frames);
+                        }
+                        ui.add(egui::DragValue::new(&mut settings.step_count).clamp_range(1..=10000).prefix("N: "));
+                    });
+                    ui.horizontal(|ui| {
+                        if ui.button("Until Pickup").clicked() {
+                            settings.run_until(AntEventKind::Pickup);
+                        }
+                        if ui.button("Until Delivery").clicked() {
+                            settings.run_until(AntEventKind::Delivery);
+                        }
+                    });
+                    if let Some(kind) = settings.run_until {
+                        ui.label(format!("Running until {:?}...", kind));
+                    }
                 });
 
             egui::CollapsingHeader::new("Parameters")
@@ -210,6 +531,56 @@ fn settings_dialog(
         });
 }
 
+fn to_color32(color: Color) -> egui::Color32 {
+    // Tints go above 1.0 for bloom, scale down so the swatch keeps the hue
+    let [r, g, b, _] = color.as_rgba_f32();
+    let max = r.max(g).max(b).max(1.0);
+    egui::Color32::from_rgb((r / max * 255.0) as u8, (g / max * 255.0) as u8, (b / max * 255.0) as u8)
+}
+
+fn legend_swatch(ui: &mut egui::Ui, color: Color, label: &str) {
+    ui.horizontal(|ui| {
+        let (rect, _) = ui.allocate_exact_size(egui::vec2(14.0, 14.0), egui::Sense::hover());
+        ui.painter().rect_filled(rect, 2.0, to_color32(color));
+        ui.label(label);
+    });
+}
+
+fn legend_ramp(ui: &mut egui::Ui, low: &str, high: &str) {
+    ui.horizontal(|ui| {
+        ui.label(low);
+        let (rect, _) = ui.allocate_exact_size(egui::vec2(120.0, 14.0), egui::Sense::hover());
+        let steps = 24;
+        let step_w = rect.width() / steps as f32;
+        for i in 0..steps {
+            let x = rect.left() + i as f32 * step_w;
+            let step = egui::Rect::from_min_size(egui::pos2(x, rect.top()), egui::vec2(step_w + 0.5, rect.height()));
+            ui.painter().rect_filled(step, 0.0, to_color32(color_ramp(i as f32 / (steps - 1) as f32)));
+        }
+        ui.label(high);
+    });
+}
+
+fn color_legend(ui: &mut egui::Ui, mode: AntColorMode) {
+    match mode {
+        AntColorMode::Task => {
+            legend_swatch(ui, task_color(AntTask::FindFood), "Searching for food");
+            legend_swatch(ui, task_color(AntTask::FindHome), "Carrying food home");
+        }
+        AntColorMode::PhStrength => legend_ramp(ui, "0", &format!("{:.0}", ANT_INITIAL_PH_STRENGTH)),
+        AntColorMode::TimeSincePickup => {
+            legend_ramp(ui, &format!("{:.0}s+", PICKUP_COLOR_SECS), "just now");
+            legend_swatch(ui, NEVER_PICKED_UP_COLOR, "Never picked up food");
+        }
+        AntColorMode::Colony => legend_swatch(ui, colony_color(0), "Colony 1"),
+        AntColorMode::Speed => legend_ramp(ui, "still", "full speed"),
+        AntColorMode::Stuck => {
+            legend_swatch(ui, STUCK_COLOR, &format!("Moved < {:.0} in {:.0}s", STUCK_DISTANCE, STUCK_CHECK_INTERVAL));
+            legend_swatch(ui, MOVING_COLOR, "Moving");
+        }
+    }
+}
+
 fn toggle_ant_visibility(mut ant_query: Query<&mut Visibility, With<Ant>>, is_visible: bool) {
     for mut ant in ant_query.iter_mut() {
         if is_visible {
@@ -251,10 +622,18 @@ fn handle_camera_control(
     }
 }
 
+#[allow(clippy::too_many_arguments)]
 fn editor_ui(
     mut contexts: EguiContexts,
     mut editor_state: ResMut<EditorState>,
     mut map_size: ResMut<MapSize>,
+    obstacle_map: Res<ObstacleMap>,
+    nest: Res<Nest>,
+    food_query: Query<(&Transform, &Food)>,
+    mut load_map_event: EventWriter<LoadMapEvent>,
+    mut history: ResMut<EditHistory>,
+    mut history_commands: EventWriter<HistoryCommand>,
+    mut clipboard_commands: EventWriter<ClipboardCommand>,
     settings: Res<SimSettings>,
     config: Res<SimConfig>,
 ) {
@@ -271,136 +650,635 @@ fn editor_ui(
                  ui.selectable_value(&mut editor_state.selected_tool, EditorTool::None, "View/Select");
                  ui.selectable_value(&mut editor_state.selected_tool, EditorTool::BrushObstacle, "Draw Wall");
                  ui.selectable_value(&mut editor_state.selected_tool, EditorTool::EraserObstacle, "Eraser");
+                 ui.selectable_value(&mut editor_state.selected_tool, EditorTool::PaintTerrain, "Terrain");
+                 ui.selectable_value(&mut editor_state.selected_tool, EditorTool::PaintDecay, "Decay");
                  ui.selectable_value(&mut editor_state.selected_tool, EditorTool::PlaceFood, "Place Food");
                  ui.selectable_value(&mut editor_state.selected_tool, EditorTool::RemoveFood, "Remove Food");
+                 ui.selectable_value(&mut editor_state.selected_tool, EditorTool::MoveNest, "Move Nest");
+                 ui.selectable_value(&mut editor_state.selected_tool, EditorTool::Shape, "Shapes");
+                 ui.selectable_value(&mut editor_state.selected_tool, EditorTool::Select, "Select Region");
+                 ui.selectable_value(&mut editor_state.selected_tool, EditorTool::Door, "Doors");
                  
-                 if editor_state.selected_tool == EditorTool::BrushObstacle || editor_state.selected_tool == EditorTool::EraserObstacle {
+                 if editor_state.selected_tool == EditorTool::PaintTerrain {
+                      egui::ComboBox::from_id_source("terrain_brush")
+                          .selected_text(editor_state.terrain.label())
+                          .show_ui(ui, |ui| {
+                              for terrain in Terrain::PAINTABLE {
+                                  ui.selectable_value(&mut editor_state.terrain, terrain, terrain.label());
+                              }
+                          });
+                 }
+                 if editor_state.selected_tool == EditorTool::PaintDecay {
+                      ui.add(
+                          egui::Slider::new(&mut editor_state.decay_factor, 0.0..=MAX_DECAY_FACTOR)
+                              .text("Decay x"),
+                      );
+                 }
+                 if editor_state.selected_tool == EditorTool::PlaceFood {
+                      ui.add(egui::Slider::new(&mut editor_state.food_storage, 1..=MAX_FOOD_STORAGE).text("Storage"));
+                      ui.add(
+                          egui::Slider::new(&mut editor_state.food_pickup_radius, 5.0..=MAX_FOOD_PICKUP_RADIUS)
+                              .text("Pickup Radius"),
+                      );
+                      ui.add(egui::Slider::new(&mut editor_state.food_quality, 0.1..=MAX_FOOD_QUALITY).text("Quality"));
+                      ui.add(
+                          egui::Slider::new(&mut editor_state.food_regrow_rate, 0.0..=MAX_FOOD_REGROW_RATE)
+                              .text("Regrow /s"),
+                      );
+                 }
+                 if editor_state.selected_tool == EditorTool::Select {
+                      let has_selection = editor_state.selection.is_some();
+                      let has_clipboard = editor_state.clipboard.is_some();
+                      for (label, command, is_enabled) in [
+                          ("Copy", ClipboardCommand::Copy, has_selection),
+                          ("Cut", ClipboardCommand::Cut, has_selection),
+                          ("Rotate", ClipboardCommand::Rotate, has_clipboard),
+                          ("Mirror H", ClipboardCommand::MirrorX, has_clipboard),
+                          ("Mirror V", ClipboardCommand::MirrorY, has_clipboard),
+                      ] {
+                          if ui.add_enabled(is_enabled, egui::Button::new(label)).clicked() {
+                              clipboard_commands.send(command);
+                          }
+                      }
+                      let paste = egui::SelectableLabel::new(editor_state.is_pasting, "Paste");
+                      if ui.add_enabled(has_clipboard, paste).clicked() {
+                          clipboard_commands.send(ClipboardCommand::Paste);
+                      }
+                 }
+                 if editor_state.selected_tool == EditorTool::Shape {
+                      egui::ComboBox::from_id_source("shape_kind")
+                          .selected_text(editor_state.shape.label())
+                          .show_ui(ui, |ui| {
+                              for shape in ShapeKind::ALL {
+                                  ui.selectable_value(&mut editor_state.shape, shape, shape.label());
+                              }
+                          });
+                      ui.checkbox(&mut editor_state.is_shape_erase, "Erase");
+                 }
+                 if editor_state.selected_tool == EditorTool::Door {
+                      let door = &mut editor_state.door;
+                      ui.checkbox(&mut door.is_remove, "Remove");
+                      ui.checkbox(&mut door.is_slide, "Slide");
+                      if door.is_slide {
+                          ui.add(egui::Slider::new(&mut door.slide_offset.x, -500.0..=500.0).text("Move X"));
+                          ui.add(egui::Slider::new(&mut door.slide_offset.y, -500.0..=500.0).text("Move Y"));
+                          ui.add(egui::Slider::new(&mut door.slide_secs, 1.0..=MAX_DOOR_SECS).text("One way (s)"));
+                      } else {
+                          ui.add(egui::Slider::new(&mut door.closed_secs, 0.0..=MAX_DOOR_SECS).text("Closed (s)"));
+                          ui.add(egui::Slider::new(&mut door.open_secs, 0.0..=MAX_DOOR_SECS).text("Open (s)"));
+                      }
                       ui.add(egui::Slider::new(&mut editor_state.brush_size, 5.0..=100.0).text("Brush Size"));
                  }
+                 let is_brush_tool = matches!(
+                     editor_state.selected_tool,
+                     EditorTool::BrushObstacle
+                         | EditorTool::EraserObstacle
+                         | EditorTool::PaintTerrain
+                         | EditorTool::PaintDecay
+                 );
+                 if is_brush_tool || (editor_state.selected_tool == EditorTool::Shape && editor_state.shape.is_outline()) {
+                      ui.add(egui::Slider::new(&mut editor_state.brush_size, 5.0..=100.0).text("Brush Size"));
+                      egui::ComboBox::from_id_source("brush_shape")
+                          .selected_text(editor_state.brush_shape.label())
+                          .show_ui(ui, |ui| {
+                              for shape in [BrushShape::Round, BrushShape::Square] {
+                                  ui.selectable_value(&mut editor_state.brush_shape, shape, shape.label());
+                              }
+                          });
+                 }
                  
+                 ui.separator();
+                 if ui.add_enabled(history.can_undo(), egui::Button::new("Undo")).clicked() {
+                     history_commands.send(HistoryCommand::Undo);
+                 }
+                 if ui.add_enabled(history.can_redo(), egui::Button::new("Redo")).clicked() {
+                     history_commands.send(HistoryCommand::Redo);
+                 }
+
+                 let current_layout = |map_size: MapSize| {
+                     let food = food_query
+                         .iter()
+                         .map(|(tr, food)| food.spec(tr.translation.truncate()))
+                         .collect();
+                     MapLayout::capture(&obstacle_map, map_size, &nest, food)
+                 };
+
                  ui.separator();
                  ui.label("Map Size:");
-                 ui.add(egui::Slider::new(&mut map_size.width, 500.0..=5000.0).text("W"));
-                 ui.add(egui::Slider::new(&mut map_size.height, 500.0..=5000.0).text("H"));
+                 let size_before = *map_size;
+                 let w = ui.add(egui::Slider::new(&mut map_size.width, 500.0..=5000.0).text("W"));
+                 let h = ui.add(egui::Slider::new(&mut map_size.height, 500.0..=5000.0).text("H"));
+                 // One history entry per drag, the grid is only cleared later by resize_obstacle_map
+                 if (w.changed() || h.changed()) && history.resize_from.is_none() {
+                     history.resize_from = Some(current_layout(size_before));
+                 }
+                 if !w.dragged() && !h.dragged() {
+                     if let Some(before) = history.resize_from.take() {
+                         let after = resized_layout(&before, *map_size);
+                         history.push(Edit::layout(before, after));
+                     }
+                 }
                  
                  ui.separator();
                  if ui.button("Save Config").clicked() {
                      let saved = SavedConfig {
                          sim_config: (*config).clone(),
-                         map_size: (*map_size).clone(),
+                         map_size: *map_size,
                      };
-                     if let Ok(json) = serde_json::to_string_pretty(&saved) {
-                         // write to current working directory
-                         if let Err(e) = std::fs::write("user_config.json", json) {
-                             eprintln!("Failed to save config: {}", e);
-                         } else {
-                             println!("Saved config to user_config.json");
+                     // write to current working directory
+                     match saved.save(USER_CONFIG_FILE) {
+                         Ok(_) => println!("Saved config to {}", USER_CONFIG_FILE),
+                         Err(e) => eprintln!("Failed to save config: {}", e),
+                     }
+                 }
+                 if ui.button("Save Map").clicked() {
+                     match current_layout(*map_size).save(USER_MAP_FILE) {
+                         Ok(_) => println!("Saved map to {}", USER_MAP_FILE),
+                         Err(e) => eprintln!("Failed to save map: {}", e),
+                     }
+                 }
+                 if ui.button("Load Map").clicked() {
+                     match MapLayout::load(USER_MAP_FILE) {
+                         Ok(layout) => {
+                             history.push(Edit::layout(current_layout(*map_size), layout.clone()));
+                             load_map_event.send(LoadMapEvent { layout, reset: false });
+                             println!("Loaded map from {}", USER_MAP_FILE);
                          }
+                         Err(e) => eprintln!("Failed to load map: {}", e),
                      }
                  }
+                 ui.menu_button("Generate", |ui| {
+                     let mapgen = &mut editor_state.mapgen;
+                     egui::ComboBox::from_id_source("mapgen_kind")
+                         .selected_text(mapgen.kind.label())
+                         .show_ui(ui, |ui| {
+                             for kind in MapGenKind::ALL {
+                                 ui.selectable_value(&mut mapgen.kind, kind, kind.label());
+                             }
+                         });
+                     let density_text = if mapgen.kind == MapGenKind::Maze { "Walls kept" } else { "Density" };
+                     ui.add(egui::Slider::new(&mut mapgen.density, 0.0..=1.0).text(density_text));
+                     ui.add(egui::Slider::new(&mut mapgen.scale, 10.0..=200.0).text("Scale"));
+                     ui.horizontal(|ui| {
+                         ui.add(egui::DragValue::new(&mut mapgen.seed).prefix("Seed "));
+                         if ui.button("Random").clicked() {
+                             mapgen.seed = rand::random::<u32>() as u64;
+                         }
+                     });
+                     // Keeps the nest and food, replaces walls and starts over
+                     if ui.button("Generate").clicked() {
+                         let before = current_layout(*map_size);
+                         let layout = generate(&before, mapgen);
+                         history.push(Edit::layout(before, layout.clone()));
+                         load_map_event.send(LoadMapEvent { layout, reset: true });
+                         ui.close_menu();
+                     }
+                 });
              });
         });
 }
 
-fn handle_editor_input(
-    mut commands: Commands,
-    asset_server: Res<AssetServer>,
-    mut obstacle_map: ResMut<ObstacleMap>,
+/// A single edit made with the editor tools. Edits are sent as events rather than
+/// applied in place so they can be logged by the recorder
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub enum EditorAction {
+    Stroke {
+        from: (f32, f32),
+        to: (f32, f32),
+        is_obstacle: bool,
+        brush_size: f32,
+        #[serde(default)]
+        brush_shape: BrushShape,
+    },
+    /// Terrain brush stroke
+    Paint {
+        from: (f32, f32),
+        to: (f32, f32),
+        terrain: Terrain,
+        brush_size: f32,
+        #[serde(default)]
+        brush_shape: BrushShape,
+    },
+    /// Decay multiplier brush stroke
+    PaintDecay {
+        from: (f32, f32),
+        to: (f32, f32),
+        factor: f32,
+        brush_size: f32,
+        #[serde(default)]
+        brush_shape: BrushShape,
+    },
+    /// Walls (or open ground) on the rectangle spanned by two corners
+    FillRect {
+        from: (f32, f32),
+        to: (f32, f32),
+        is_obstacle: bool,
+    },
+    /// Walls (or open ground) on the connected area of equal cells under the point
+    FloodFill { x: f32, y: f32, is_obstacle: bool },
+    PlaceFood(FoodSpec),
+    RemoveFood { x: f32, y: f32, radius: f32 },
+    /// Removes the food sources placed with this name
+    RemoveNamedFood { name: String },
+    MoveNest { x: f32, y: f32 },
+    /// Sets single cells to a terrain and decay multiplier, sent by undo/redo
+    SetCells { cells: Vec<(usize, Terrain, f32)> },
+    /// Clears walls, terrain, decay and food of a region
+    ClearRegion { region: CellRegion },
+    /// Clipboard pasted with its bottom-left cell at `(col, row)`
+    Paste { col: isize, row: isize, clipboard: Clipboard },
+    AddDynamicWall(DynamicWall),
+    /// Removes every door or sliding wall drawn within `radius` of the point
+    RemoveDynamicWalls { x: f32, y: f32, radius: f32 },
+}
+
+impl EditorAction {
+    /// Applies the wall, terrain, decay and dynamic wall part of the action, food and
+    /// nest are handled by `apply_editor_actions`
+    pub fn apply_to_obstacles(&self, obstacle_map: &mut ObstacleMap, map_size: &MapSize) {
+        match *self {
+            EditorAction::Stroke { from, to, is_obstacle, brush_size, brush_shape } => {
+                obstacle_map.set_obstacle_line(
+                    Vec2::new(from.0, from.1),
+                    Vec2::new(to.0, to.1),
+                    map_size.width,
+                    map_size.height,
+                    is_obstacle,
+                    brush_size,
+                    brush_shape,
+                );
+            }
+            EditorAction::Paint { from, to, terrain, brush_size, brush_shape } => {
+                obstacle_map.set_terrain_line(
+                    Vec2::new(from.0, from.1),
+                    Vec2::new(to.0, to.1),
+                    map_size.width,
+                    map_size.height,
+                    terrain,
+                    brush_size,
+                    brush_shape,
+                );
+            }
+            EditorAction::PaintDecay { from, to, factor, brush_size, brush_shape } => {
+                obstacle_map.set_decay_line(
+                    Vec2::new(from.0, from.1),
+                    Vec2::new(to.0, to.1),
+                    map_size.width,
+                    map_size.height,
+                    factor,
+                    brush_size,
+                    brush_shape,
+                );
+            }
+            EditorAction::FillRect { from, to, is_obstacle } => {
+                let terrain = if is_obstacle { Terrain::Wall } else { Terrain::Open };
+                obstacle_map.fill_rect(
+                    Vec2::new(from.0, from.1),
+                    Vec2::new(to.0, to.1),
+                    map_size.width,
+                    map_size.height,
+                    terrain,
+                );
+            }
+            EditorAction::FloodFill { x, y, is_obstacle } => {
+                let terrain = if is_obstacle { Terrain::Wall } else { Terrain::Open };
+                obstacle_map.flood_fill(x, y, map_size.width, map_size.height, terrain);
+            }
+            EditorAction::ClearRegion { region } => {
+                for (_, _, idx) in region.cells(obstacle_map) {
+                    obstacle_map.grid[idx] = Terrain::Open;
+                    obstacle_map.decay[idx] = 1.0;
+                }
+            }
+            EditorAction::Paste { col, row, ref clipboard } => clipboard.paste_cells(col, row, obstacle_map),
+            EditorAction::SetCells { ref cells } => {
+                for &(idx, terrain, factor) in cells.iter() {
+                    // Recorded on a map of another size
+                    if idx < obstacle_map.grid.len() {
+                        obstacle_map.grid[idx] = terrain;
+                        obstacle_map.decay[idx] = factor;
+                    }
+                }
+            }
+            EditorAction::AddDynamicWall(ref wall) => obstacle_map.dynamic_walls.push(wall.clone()),
+            EditorAction::RemoveDynamicWalls { x, y, radius } => {
+                let pos = Vec2::new(x, y);
+                obstacle_map.dynamic_walls.retain(|w| w.distance_to(pos) >= radius);
+            }
+            _ => {}
+        }
+    }
+}
+
+#[derive(Event)]
+pub struct EditorActionEvent {
+    pub action: EditorAction,
+    /// Sent by undo/redo, so it is already in the edit history
+    pub is_undo_redo: bool,
+}
+
+impl EditorActionEvent {
+    pub fn new(action: EditorAction) -> Self {
+        Self { action, is_undo_redo: false }
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+pub(crate) fn handle_editor_input(
+    mut editor_state: ResMut<EditorState>,
+    settings: Res<SimSettings>,
+    obstacle_map: Res<ObstacleMap>,
     map_size: Res<MapSize>,
-    editor_state: Res<EditorState>,
     windows: Query<&Window>,
     camera_q: Query<(&Camera, &GlobalTransform), With<PanCam>>,
     mouse_btn: Res<Input<MouseButton>>,
-    food_query: Query<(Entity, &Transform), With<Food>>,
+    keys: Res<Input<KeyCode>>,
     mut contexts: EguiContexts,
+    mut actions: EventWriter<EditorActionEvent>,
+    mut draft: ResMut<ShapeDraft>,
     mut last_drag_pos: Local<Option<Vec2>>,
+    mut selection_start: Local<Option<Vec2>>,
 ) {
-    if editor_state.selected_tool == EditorTool::None { 
+    if editor_state.selected_tool != EditorTool::Shape && (draft.drag_start.is_some() || !draft.vertices.is_empty()) {
+        draft.clear();
+    }
+    // Edits during replay would be overwritten by the recording
+    if editor_state.selected_tool == EditorTool::None || settings.is_replaying {
         *last_drag_pos = None;
+        draft.clear();
+        editor_state.door.drag_start = None;
         return; 
     }
     
     // Check if mouse is interacting with UI
     let ctx = contexts.ctx_mut();
+    if editor_state.selected_tool == EditorTool::Shape && !ctx.wants_keyboard_input() {
+        if keys.just_pressed(KeyCode::Return) && draft.vertices.len() >= 3 {
+            let outline = polygon_outline(&draft.vertices);
+            for action in outline_actions(&outline, &editor_state) {
+                actions.send(EditorActionEvent::new(action));
+            }
+            draft.vertices.clear();
+        }
+        if keys.just_pressed(KeyCode::Back) {
+            draft.vertices.pop();
+        }
+    }
     if ctx.is_pointer_over_area() || ctx.wants_pointer_input() {
         *last_drag_pos = None;
+        // A drag released over the UI is dropped
+        if !mouse_btn.pressed(MouseButton::Left) {
+            draft.drag_start = None;
+            editor_state.door.drag_start = None;
+        }
         return;
     }
 
-    if camera_q.is_empty() { return; }
-    
     // Reset drag if mouse not pressed
     if !mouse_btn.pressed(MouseButton::Left) {
         *last_drag_pos = None;
     }
 
-    let (camera, camera_transform) = camera_q.single();
-    if let Some(window) = windows.iter().next() {
-        if let Some(cursor_pos) = window.cursor_position() {
-             if let Some(ray) = camera.viewport_to_world(camera_transform, cursor_pos) {
-                 let world_pos = ray.origin.truncate();
-                 
-                  match editor_state.selected_tool {
-                      EditorTool::BrushObstacle | EditorTool::EraserObstacle => {
-                          if mouse_btn.pressed(MouseButton::Left) {
-                              let is_brush = editor_state.selected_tool == EditorTool::BrushObstacle;
-                              
-                              // Interpolation Logic
-                              let start = last_drag_pos.unwrap_or(world_pos);
-                              let dist = start.distance(world_pos);
-                              let step = (editor_state.brush_size * 0.25).max(1.0);
-                              
-                              if dist > step {
-                                  let steps = (dist / step).ceil() as i32;
-                                  for i in 0..=steps {
-                                       let t = i as f32 / steps as f32;
-                                       let p = start.lerp(world_pos, t);
-                                       obstacle_map.set_obstacle(p.x, p.y, map_size.width, map_size.height, is_brush, editor_state.brush_size);
-                                  }
-                              } else {
-                                  obstacle_map.set_obstacle(world_pos.x, world_pos.y, map_size.width, map_size.height, is_brush, editor_state.brush_size);
-                              }
-                              
-                              *last_drag_pos = Some(world_pos);
-                          }
-                      },
-                      EditorTool::PlaceFood => {
-                          if mouse_btn.just_pressed(MouseButton::Left) {
-                              commands.spawn((
-                                  SpriteBundle {
-                                      texture: asset_server.load(SPRITE_FOOD),
-                                      transform: Transform::from_xyz(world_pos.x, world_pos.y, 2.0)
-                                          .with_scale(Vec3::splat(FOOD_SPRITE_SCALE)),
-                                      sprite: Sprite {
-                                          color: Color::rgb(1.5, 1.5, 1.5),
-                                          ..default()
-                                      },
-                                      ..default()
-                                  },
-                                  Food { storage: 1000 },
-                              ));
-                          }
-                      },
-                      EditorTool::RemoveFood => {
-                          if mouse_btn.pressed(MouseButton::Left) {
-                              for (entity, tr) in food_query.iter() {
-                                  if tr.translation.truncate().distance_squared(world_pos) < 30.0 * 30.0 {
-                                      commands.entity(entity).despawn();
-                                  }
-                              }
-                          }
-                      },
-                      _ => {},
-                  }
-             }
+    if let Some(world_pos) = cursor_world_pos(&windows, &camera_q) {
+        match editor_state.selected_tool {
+            EditorTool::BrushObstacle | EditorTool::EraserObstacle
+                if mouse_btn.pressed(MouseButton::Left) => {
+                    let is_brush = editor_state.selected_tool == EditorTool::BrushObstacle;
+                    
+                    // Interpolate from the last drag position so fast strokes have no gaps
+                    let start = last_drag_pos.unwrap_or(world_pos);
+                    actions.send(EditorActionEvent::new(EditorAction::Stroke {
+                        from: (start.x, start.y),
+                        to: (world_pos.x, world_pos.y),
+                        is_obstacle: is_brush,
+                        brush_size: editor_state.brush_size,
+                        brush_shape: editor_state.brush_shape,
+                    }));
+                    
+                    *last_drag_pos = Some(world_pos);
+            },
+            EditorTool::PaintTerrain if mouse_btn.pressed(MouseButton::Left) => {
+                    let start = last_drag_pos.unwrap_or(world_pos);
+                    actions.send(EditorActionEvent::new(EditorAction::Paint {
+                        from: (start.x, start.y),
+                        to: (world_pos.x, world_pos.y),
+                        terrain: editor_state.terrain,
+                        brush_size: editor_state.brush_size,
+                        brush_shape: editor_state.brush_shape,
+                    }));
+                    *last_drag_pos = Some(world_pos);
+            },
+            EditorTool::PaintDecay if mouse_btn.pressed(MouseButton::Left) => {
+                    let start = last_drag_pos.unwrap_or(world_pos);
+                    actions.send(EditorActionEvent::new(EditorAction::PaintDecay {
+                        from: (start.x, start.y),
+                        to: (world_pos.x, world_pos.y),
+                        factor: editor_state.decay_factor,
+                        brush_size: editor_state.brush_size,
+                        brush_shape: editor_state.brush_shape,
+                    }));
+                    *last_drag_pos = Some(world_pos);
+            },
+            EditorTool::PlaceFood if mouse_btn.just_pressed(MouseButton::Left) => {
+                    actions.send(EditorActionEvent::new(EditorAction::PlaceFood(FoodSpec {
+                        x: world_pos.x,
+                        y: world_pos.y,
+                        storage: editor_state.food_storage,
+                        pickup_radius: editor_state.food_pickup_radius,
+                        quality: editor_state.food_quality,
+                        // Regrows back up to the storage it was placed with
+                        regrow: (editor_state.food_regrow_rate > 0.0).then_some(Regrow {
+                            capacity: editor_state.food_storage,
+                            rate: editor_state.food_regrow_rate,
+                        }),
+                        name: None,
+                    })));
+            },
+            EditorTool::RemoveFood if mouse_btn.pressed(MouseButton::Left) => {
+                    actions.send(EditorActionEvent::new(EditorAction::RemoveFood {
+                        x: world_pos.x,
+                        y: world_pos.y,
+                        radius: 30.0,
+                    }));
+            },
+            EditorTool::MoveNest if mouse_btn.just_pressed(MouseButton::Left) => {
+                    actions.send(EditorActionEvent::new(EditorAction::MoveNest {
+                        x: world_pos.x,
+                        y: world_pos.y,
+                    }));
+            },
+            EditorTool::Shape => {
+                for action in shape_input(&editor_state, &mut draft, &mouse_btn, world_pos) {
+                    actions.send(EditorActionEvent::new(action));
+                }
+            },
+            EditorTool::Door if editor_state.door.is_remove && mouse_btn.just_pressed(MouseButton::Left) => {
+                actions.send(EditorActionEvent::new(EditorAction::RemoveDynamicWalls {
+                    x: world_pos.x,
+                    y: world_pos.y,
+                    radius: DOOR_REMOVE_RADIUS,
+                }));
+            },
+            // No drag to start while removing
+            EditorTool::Door if editor_state.door.is_remove => {},
+            EditorTool::Door => {
+                if mouse_btn.just_pressed(MouseButton::Left) {
+                    editor_state.door.drag_start = Some(world_pos);
+                }
+                let is_released = mouse_btn.just_released(MouseButton::Left);
+                if let Some(start) = editor_state.door.drag_start.filter(|_| is_released) {
+                    editor_state.door.drag_start = None;
+                    let wall = editor_state.door.wall(start, world_pos, editor_state.brush_size);
+                    actions.send(EditorActionEvent::new(EditorAction::AddDynamicWall(wall)));
+                }
+            },
+            EditorTool::Select if editor_state.is_pasting => {
+                if let Some(clipboard) = editor_state.clipboard.as_ref().filter(|_| mouse_btn.just_pressed(MouseButton::Left)) {
+                    let (col, row) = clipboard.origin_centered_at(world_pos, &map_size);
+                    actions.send(EditorActionEvent::new(EditorAction::Paste {
+                        col,
+                        row,
+                        clipboard: clipboard.clone(),
+                    }));
+                }
+            },
+            EditorTool::Select => {
+                if mouse_btn.just_pressed(MouseButton::Left) {
+                    *selection_start = Some(world_pos);
+                }
+                if !mouse_btn.pressed(MouseButton::Left) {
+                    *selection_start = None;
+                }
+                if let Some(start) = *selection_start {
+                    editor_state.selection = Some(CellRegion::from_corners(start, world_pos, &obstacle_map, &map_size));
+                }
+            },
+            _ => {},
+        }
+    }
+}
+
+/// Advances the shape being drawn, returns the actions of a finished shape
+fn shape_input(
+    editor_state: &EditorState,
+    draft: &mut ShapeDraft,
+    mouse_btn: &Input<MouseButton>,
+    world_pos: Vec2,
+) -> Vec<EditorAction> {
+    let is_obstacle = !editor_state.is_shape_erase;
+    match editor_state.shape {
+        ShapeKind::FloodFill if mouse_btn.just_pressed(MouseButton::Left) => {
+            vec![EditorAction::FloodFill { x: world_pos.x, y: world_pos.y, is_obstacle }]
+        }
+        ShapeKind::Polygon if mouse_btn.just_pressed(MouseButton::Left) => {
+            let closes = draft.vertices.len() >= 3 && draft.vertices[0].distance(world_pos) < SHAPE_CLOSE_RADIUS;
+            if closes {
+                let outline = polygon_outline(&draft.vertices);
+                draft.vertices.clear();
+                outline_actions(&outline, editor_state)
+            } else {
+                draft.vertices.push(world_pos);
+                vec![]
+            }
+        }
+        ShapeKind::Polygon | ShapeKind::FloodFill => vec![],
+        _ => {
+            if mouse_btn.just_pressed(MouseButton::Left) {
+                draft.drag_start = Some(world_pos);
+            }
+            match draft.drag_start {
+                Some(start) if mouse_btn.just_released(MouseButton::Left) => {
+                    draft.drag_start = None;
+                    drag_actions(start, world_pos, editor_state)
+                }
+                _ => vec![],
+            }
+        }
+    }
+}
+
+/// World position under the mouse cursor, if it is inside the window
+pub fn cursor_world_pos(
+    windows: &Query<&Window>,
+    camera_q: &Query<(&Camera, &GlobalTransform), With<PanCam>>,
+) -> Option<Vec2> {
+    let (camera, camera_transform) = camera_q.get_single().ok()?;
+    let cursor_pos = windows.iter().next()?.cursor_position()?;
+    camera
+        .viewport_to_world(camera_transform, cursor_pos)
+        .map(|ray| ray.origin.truncate())
+}
+
+pub(crate) fn apply_editor_actions(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    mut events: EventReader<EditorActionEvent>,
+    mut obstacle_map: ResMut<ObstacleMap>,
+    map_size: Res<MapSize>,
+    mut nest: ResMut<Nest>,
+    food_query: Query<(Entity, &Transform, &Food)>,
+) {
+    for EditorActionEvent { action, .. } in events.iter() {
+        match *action {
+            EditorAction::Stroke { .. }
+            | EditorAction::Paint { .. }
+            | EditorAction::PaintDecay { .. }
+            | EditorAction::FillRect { .. }
+            | EditorAction::FloodFill { .. }
+            | EditorAction::SetCells { .. }
+            | EditorAction::AddDynamicWall(_)
+            | EditorAction::RemoveDynamicWalls { .. } => action.apply_to_obstacles(&mut obstacle_map, &map_size),
+            EditorAction::MoveNest { x, y } => nest.0 = Vec2::new(x, y),
+            EditorAction::ClearRegion { region } => {
+                action.apply_to_obstacles(&mut obstacle_map, &map_size);
+                for (entity, tr, _) in food_query.iter() {
+                    if region.contains(tr.translation.truncate(), &map_size) {
+                        commands.entity(entity).despawn();
+                    }
+                }
+            }
+            EditorAction::Paste { col, row, ref clipboard } => {
+                action.apply_to_obstacles(&mut obstacle_map, &map_size);
+                for f in clipboard.food_at(col, row, &map_size) {
+                    spawn_food(&mut commands, &asset_server, f);
+                }
+            }
+            EditorAction::PlaceFood(ref spec) => spawn_food(&mut commands, &asset_server, spec.clone()),
+            EditorAction::RemoveFood { x, y, radius } => {
+                let pos = Vec2::new(x, y);
+                for (entity, tr, _) in food_query.iter() {
+                    if tr.translation.truncate().distance_squared(pos) < radius * radius {
+                        commands.entity(entity).despawn();
+                    }
+                }
+            }
+            EditorAction::RemoveNamedFood { ref name } => {
+                for (entity, _, food) in food_query.iter() {
+                    if food.name.as_ref() == Some(name) {
+                        commands.entity(entity).despawn();
+                    }
+                }
+            }
         }
     }
 }
 
 #[derive(Serialize, Deserialize)]
-struct SavedConfig {
-    sim_config: SimConfig,
-    map_size: MapSize,
+pub struct SavedConfig {
+    pub sim_config: SimConfig,
+    pub map_size: MapSize,
+}
+
+impl SavedConfig {
+    pub fn load(path: &str) -> Result<Self, String> {
+        let content = std::fs::read_to_string(path).map_err(|e| format!("{}: {}", path, e))?;
+        serde_json::from_str(&content).map_err(|e| format!("{}: {}", path, e))
+    }
+
+    pub fn save(&self, path: &str) -> Result<(), String> {
+        let json = serde_json::to_string_pretty(self).map_err(|e| e.to_string())?;
+        std::fs::write(path, json).map_err(|e| format!("{}: {}", path, e))
+    }
 }
 
 fn load_config(
@@ -408,15 +1286,15 @@ fn load_config(
     mut map_size: ResMut<MapSize>,
 ) {
     // Try to load user_config.json
-    if let Ok(content) = std::fs::read_to_string("user_config.json") {
-       if let Ok(saved) = serde_json::from_str::<SavedConfig>(&content) {
+    if std::path::Path::new(USER_CONFIG_FILE).exists() {
+       if let Ok(saved) = SavedConfig::load(USER_CONFIG_FILE) {
            *sim_config = saved.sim_config;
            *map_size = saved.map_size;
-           println!("Loaded user config from user_config.json");
+           println!("Loaded user config from {}", USER_CONFIG_FILE);
        } else {
-           println!("Failed to parse user_config.json");
+           println!("Failed to parse {}", USER_CONFIG_FILE);
        }
     } else {
-        println!("No user_config.json found, using defaults.");
+        println!("No {} found, using defaults.", USER_CONFIG_FILE);
     }
 }
@@ -0,0 +1,325 @@
+//! Window-less version of the simulation for batch experiments.
+//! Runs the same steering/collision code as the `ant.rs` systems, but on a
+//! fixed time step with a seeded rng so runs are reproducible.
+
+use crate::{
+    ant::{
+        ant_speed, find_pull_target, integrate_position, obstacle_avoidance, resolve_wall_collision,
+        sensor_steer_target, steer_acceleration, AntTask,
+    },
+    food::Regrow,
+    gui::{EditorAction, SimConfig},
+    map::{FoodSpec, MapLayout, MapSize, ObstacleMap},
+    pheromone::Pheromones,
+    utils::get_rand_unit_vec2_with,
+    *,
+};
+use bevy::math::{vec2, Vec2};
+use rand::{rngs::StdRng, Rng, SeedableRng};
+
+pub const HEADLESS_DT: f32 = 1.0 / TARGET_FPS as f32;
+
+pub struct HeadlessAnt {
+    pub pos: Vec2,
+    pub velocity: Vec2,
+    pub task: AntTask,
+    pub ph_strength: f32,
+    /// Quality of the food being carried home
+    pub carried: f32,
+    acceleration: Vec2,
+    decision_timer: f32,
+}
+
+impl HeadlessAnt {
+    fn new(pos: Vec2, rng: &mut StdRng) -> Self {
+        Self {
+            pos,
+            velocity: get_rand_unit_vec2_with(rng),
+            task: AntTask::FindFood,
+            ph_strength: ANT_INITIAL_PH_STRENGTH,
+            carried: FOOD_QUALITY,
+            acceleration: Vec2::ZERO,
+            decision_timer: rng.gen_range(0.0..0.1),
+        }
+    }
+}
+
+pub struct HeadlessFood {
+    pub pos: Vec2,
+    pub storage: i32,
+    pub pickup_radius: f32,
+    pub quality: f32,
+    pub regrow: Option<Regrow>,
+    regrown: f32,
+    pub name: Option<String>,
+}
+
+impl HeadlessFood {
+    fn new(spec: &FoodSpec) -> Self {
+        Self {
+            pos: spec.pos(),
+            storage: spec.storage,
+            pickup_radius: spec.pickup_radius,
+            quality: spec.quality,
+            regrow: spec.regrow,
+            regrown: 0.0,
+            name: spec.name.clone(),
+        }
+    }
+}
+
+/// Stand-in for bevy's `on_timer` run condition
+struct Interval {
+    period: f32,
+    elapsed: f32,
+}
+
+impl Interval {
+    fn new(period: f32) -> Self {
+        Self { period, elapsed: 0.0 }
+    }
+
+    fn tick(&mut self, dt: f32) -> bool {
+        self.elapsed += dt;
+        if self.elapsed >= self.period {
+            self.elapsed -= self.period;
+            return true;
+        }
+        false
+    }
+}
+
+pub struct HeadlessSim {
+    pub config: SimConfig,
+    pub map_size: MapSize,
+    pub obstacle_map: ObstacleMap,
+    pub pheromones: Pheromones,
+    pub ants: Vec<HeadlessAnt>,
+    pub food: Vec<HeadlessFood>,
+    pub home_pos: Vec2,
+    pub tick: u64,
+    /// Deliveries weighted by food quality
+    pub food_delivered: f32,
+
+    rng: StdRng,
+    drop_timer: Interval,
+    collision_timer: Interval,
+    strength_decay_timer: Interval,
+    ph_decay_timer: Interval,
+    cleanup_timer: Interval,
+}
+
+impl HeadlessSim {
+    pub fn new(config: SimConfig, layout: &MapLayout, seed: u64) -> Self {
+        let mut rng = StdRng::seed_from_u64(seed);
+        let home_pos = vec2(layout.nest.0, layout.nest.1);
+
+        let ants = (0..config.ants_count).map(|_| HeadlessAnt::new(home_pos, &mut rng)).collect();
+
+        let food = layout.food.iter().map(HeadlessFood::new).collect();
+
+        Self {
+            config,
+            map_size: layout.map_size,
+            obstacle_map: layout.to_obstacle_map(),
+            pheromones: Pheromones::new(home_pos),
+            ants,
+            food,
+            home_pos,
+            tick: 0,
+            food_delivered: 0.0,
+            rng,
+            drop_timer: Interval::new(ANT_PH_DROP_INTERVAL),
+            collision_timer: Interval::new(0.1),
+            strength_decay_timer: Interval::new(ANT_PH_STRENGTH_DECAY_INTERVAL),
+            ph_decay_timer: Interval::new(PH_DECAY_INTERVAL),
+            cleanup_timer: Interval::new(2.0),
+        }
+    }
+
+    /// Simulated time in seconds
+    pub fn elapsed_secs(&self) -> f32 {
+        self.tick as f32 * HEADLESS_DT
+    }
+
+    pub fn run(&mut self, ticks: u64) {
+        for _ in 0..ticks {
+            self.step();
+        }
+    }
+
+    /// Spawns ants at the nest or drops ants until there are `config.ants_count`
+    pub fn match_ants_count(&mut self) {
+        let target = self.config.ants_count;
+        while self.ants.len() < target {
+            let ant = HeadlessAnt::new(self.home_pos, &mut self.rng);
+            self.ants.push(ant);
+        }
+        self.ants.truncate(target);
+    }
+
+    /// Applies a map edit the way `apply_editor_actions` does in the app
+    pub fn apply_edit(&mut self, action: &EditorAction) {
+        match action {
+            EditorAction::PlaceFood(spec) => self.food.push(HeadlessFood::new(spec)),
+            EditorAction::RemoveFood { x, y, radius } => {
+                let pos = vec2(*x, *y);
+                self.food.retain(|f| f.pos.distance_squared(pos) >= radius * radius);
+            }
+            EditorAction::RemoveNamedFood { name } => self.food.retain(|f| f.name.as_ref() != Some(name)),
+            EditorAction::MoveNest { x, y } => {
+                self.home_pos = vec2(*x, *y);
+                self.pheromones.set_home(self.home_pos);
+            }
+            EditorAction::ClearRegion { region } => {
+                action.apply_to_obstacles(&mut self.obstacle_map, &self.map_size);
+                let map_size = self.map_size;
+                self.food.retain(|f| !region.contains(f.pos, &map_size));
+            }
+            EditorAction::Paste { col, row, clipboard } => {
+                action.apply_to_obstacles(&mut self.obstacle_map, &self.map_size);
+                let food = clipboard.food_at(*col, *row, &self.map_size);
+                self.food.extend(food.iter().map(HeadlessFood::new));
+            }
+            _ => action.apply_to_obstacles(&mut self.obstacle_map, &self.map_size),
+        }
+    }
+
+    /// Advances the simulation by one frame of HEADLESS_DT
+    pub fn step(&mut self) {
+        let dt = HEADLESS_DT;
+
+        // Doors and sliding walls move with the sim time, like update_dynamic_walls
+        let (w, h) = (self.map_size.width, self.map_size.height);
+        self.obstacle_map.dynamic_cells = self.obstacle_map.dynamic_cells_at(self.elapsed_secs(), w, h);
+
+        if self.drop_timer.tick(dt) {
+            self.drop_pheromones();
+        }
+
+        for ant in self.ants.iter_mut() {
+            ant.acceleration += obstacle_avoidance(
+                ant.pos,
+                ant.velocity,
+                &self.obstacle_map,
+                &self.map_size,
+                &mut self.rng,
+            );
+            resolve_wall_collision(
+                &mut ant.pos,
+                &mut ant.velocity,
+                &mut ant.acceleration,
+                &self.obstacle_map,
+                &self.map_size,
+                &mut self.rng,
+            );
+        }
+
+        if self.collision_timer.tick(dt) {
+            self.check_home_food_collisions();
+        }
+
+        for food in self.food.iter_mut() {
+            if let Some(regrow) = food.regrow {
+                regrow.grow(&mut food.storage, &mut food.regrown, dt);
+            }
+        }
+
+        self.update_directions(dt);
+
+        if self.strength_decay_timer.tick(dt) {
+            for ant in self.ants.iter_mut() {
+                ant.ph_strength = f32::max(ant.ph_strength - self.config.ant_ph_strength_decay_rate, 0.0);
+            }
+        }
+
+        for ant in self.ants.iter_mut() {
+            let speed = ant_speed(&self.obstacle_map, &self.map_size, ant.pos);
+            ant.pos = integrate_position(ant.pos, &mut ant.velocity, &mut ant.acceleration, speed);
+        }
+
+        if self.ph_decay_timer.tick(dt) {
+            let (obstacle_map, map_size) = (&self.obstacle_map, &self.map_size);
+            let multiplier_at = |p: Vec2| obstacle_map.decay_factor_at(p.x, p.y, map_size.width, map_size.height);
+            self.pheromones.to_food.decay_signals(self.config.ph_decay_rate, multiplier_at);
+            self.pheromones.to_home.decay_signals(self.config.ph_decay_rate, multiplier_at);
+        }
+        if self.cleanup_timer.tick(dt) {
+            self.pheromones.to_food.drop_zero_signals();
+            self.pheromones.to_home.drop_zero_signals();
+        }
+
+        self.tick += 1;
+    }
+
+    fn drop_pheromones(&mut self) {
+        let max_val = self.config.max_pheromone_strength;
+        for ant in self.ants.iter() {
+            let key = (ant.pos.x as i32, ant.pos.y as i32);
+            match ant.task {
+                AntTask::FindFood => self.pheromones.to_home.emit_signal(&key, ant.ph_strength, max_val),
+                AntTask::FindHome => self.pheromones.to_food.emit_signal(&key, ant.ph_strength, max_val),
+            }
+        }
+    }
+
+    fn update_directions(&mut self, dt: f32) {
+        self.pheromones.clear_cache();
+
+        for ant in self.ants.iter_mut() {
+            ant.decision_timer -= dt;
+            if ant.decision_timer > 0.0 {
+                continue;
+            }
+            ant.decision_timer = self.config.ant_update_interval + self.rng.gen_range(-0.01..0.01);
+
+            let target = find_pull_target(
+                &ant.task,
+                ant.pos,
+                self.food.iter().filter(|f| f.storage > 0).map(|f| f.pos),
+                self.home_pos,
+                &self.config,
+                &self.obstacle_map,
+                &self.map_size,
+            )
+            .or_else(|| sensor_steer_target(&ant.task, ant.pos, ant.velocity, &self.pheromones, &self.config));
+
+            let terrain = self.obstacle_map.terrain_at(ant.pos.x, ant.pos.y, self.map_size.width, self.map_size.height);
+            ant.acceleration +=
+                steer_acceleration(target, ant.pos, ant.velocity, &self.config, terrain.turn_factor(), &mut self.rng);
+        }
+    }
+
+    fn check_home_food_collisions(&mut self) {
+        for ant in self.ants.iter_mut() {
+            if ant.pos.distance_squared(self.home_pos) < HOME_RADIUS * HOME_RADIUS {
+                // Visiting home recharges the scent supply, and drops off any food
+                ant.ph_strength = ANT_INITIAL_PH_STRENGTH;
+                if ant.task == AntTask::FindHome {
+                    ant.task = AntTask::FindFood;
+                    self.food_delivered += ant.carried;
+
+                    let angle = self.rng.gen_range(-0.5..0.5);
+                    ant.velocity = Vec2::from_angle(angle).rotate(-ant.velocity);
+                }
+            }
+
+            if ant.task != AntTask::FindFood {
+                continue;
+            }
+            if let Some(food) = self
+                .food
+                .iter_mut()
+                .find(|f| f.storage > 0 && ant.pos.distance_squared(f.pos) < f.pickup_radius * f.pickup_radius)
+            {
+                ant.velocity *= -1.0;
+                ant.task = AntTask::FindHome;
+                ant.ph_strength = ANT_INITIAL_PH_STRENGTH * food.quality;
+                ant.carried = food.quality;
+                food.storage -= 1;
+            }
+        }
+
+        self.food.retain(|f| f.storage > 0 || f.regrow.is_some());
+    }
+}
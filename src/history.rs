@@ -0,0 +1,346 @@
+//! Undo/redo for the map editor.
+//! Brush strokes and shapes are stored as the cells they changed, from the press to
+//! the release of the mouse button. Food, nest, door and single-cell changes are undone by sending the
+//! inverse `EditorAction`s, so the recorder logs undo and redo like any other edit.
+//! Cut and paste store the cells of their region as it was before.
+//! Map loads, generated maps and resizes store the whole layout before and after.
+
+use crate::{
+    ant::Food,
+    clipboard::CellRegion,
+    gui::{apply_editor_actions, handle_editor_input, EditorAction, EditorActionEvent, SimSettings},
+    map::{FoodSpec, LoadMapEvent, MapLayout, MapSize, Nest, ObstacleMap, Terrain},
+    *,
+};
+use bevy::prelude::*;
+use bevy_egui::EguiContexts;
+use std::collections::{HashSet, VecDeque};
+
+pub struct HistoryPlugin;
+
+pub enum Edit {
+    /// Undone and redone by sending editor actions
+    Actions {
+        undo: Vec<EditorAction>,
+        redo: Vec<EditorAction>,
+    },
+    /// Undone and redone by loading the whole map
+    Layout { before: Box<MapLayout>, after: Box<MapLayout> },
+}
+
+impl Edit {
+    pub fn layout(before: MapLayout, after: MapLayout) -> Self {
+        Edit::Layout {
+            before: Box::new(before),
+            after: Box::new(after),
+        }
+    }
+}
+
+/// Cells as they were when the current stroke started
+struct StrokeStart {
+    grid: Vec<Terrain>,
+    decay: Vec<f32>,
+}
+
+#[derive(Resource, Default)]
+pub struct EditHistory {
+    undo: VecDeque<Edit>,
+    redo: Vec<Edit>,
+    stroke: Option<StrokeStart>,
+    /// Food removed since the remove tool was pressed, undone as one edit
+    removed_food: Vec<(Entity, FoodSpec)>,
+    /// Map before the size sliders started moving
+    pub resize_from: Option<MapLayout>,
+}
+
+impl EditHistory {
+    pub fn push(&mut self, edit: Edit) {
+        if self.undo.len() >= EDITOR_HISTORY_LIMIT {
+            self.undo.pop_front();
+        }
+        self.undo.push_back(edit);
+        self.redo.clear();
+    }
+
+    pub fn can_undo(&self) -> bool {
+        !self.undo.is_empty()
+    }
+
+    pub fn can_redo(&self) -> bool {
+        !self.redo.is_empty()
+    }
+
+    fn is_editing(&self) -> bool {
+        self.stroke.is_some() || !self.removed_food.is_empty() || self.resize_from.is_some()
+    }
+}
+
+/// Layout after resizing to `map_size`, the grid is cleared when its cell count changes
+pub fn resized_layout(layout: &MapLayout, map_size: MapSize) -> MapLayout {
+    let before = ObstacleMap::new(layout.map_size.width, layout.map_size.height);
+    let after = ObstacleMap::new(map_size.width, map_size.height);
+    let mut resized = layout.clone();
+    resized.map_size = map_size;
+    if before.width != after.width || before.height != after.height {
+        resized.walls.clear();
+        resized.terrain.clear();
+        resized.decay.clear();
+    }
+    resized
+}
+
+#[derive(Event)]
+pub enum HistoryCommand {
+    Undo,
+    Redo,
+}
+
+impl Plugin for HistoryPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(EditHistory::default())
+            .add_event::<HistoryCommand>()
+            .add_systems(
+                Update,
+                (
+                    undo_redo.before(handle_editor_input),
+                    record_edits.after(handle_editor_input).before(apply_editor_actions),
+                ),
+            );
+    }
+}
+
+fn undo_redo(
+    mut contexts: EguiContexts,
+    keys: Res<Input<KeyCode>>,
+    settings: Res<SimSettings>,
+    mut history: ResMut<EditHistory>,
+    mut commands: EventReader<HistoryCommand>,
+    mut actions: EventWriter<EditorActionEvent>,
+    mut load_map_event: EventWriter<LoadMapEvent>,
+) {
+    let mut requested: Vec<bool> = commands.iter().map(|c| matches!(c, HistoryCommand::Undo)).collect();
+    let ctrl = keys.any_pressed([KeyCode::ControlLeft, KeyCode::ControlRight]);
+    let shift = keys.any_pressed([KeyCode::ShiftLeft, KeyCode::ShiftRight]);
+    if ctrl && !contexts.ctx_mut().wants_keyboard_input() {
+        if keys.just_pressed(KeyCode::Z) {
+            requested.push(!shift);
+        }
+        if keys.just_pressed(KeyCode::Y) {
+            requested.push(false);
+        }
+    }
+    // Undoing half a stroke would leave the other half in the wrong edit
+    if settings.is_replaying || history.is_editing() {
+        return;
+    }
+
+    for is_undo in requested {
+        let edit = if is_undo { history.undo.pop_back() } else { history.redo.pop() };
+        let Some(edit) = edit else {
+            continue;
+        };
+        match &edit {
+            Edit::Actions { undo, redo } => {
+                let sent = if is_undo { undo } else { redo };
+                for action in sent.iter() {
+                    actions.send(EditorActionEvent {
+                        action: action.clone(),
+                        is_undo_redo: true,
+                    });
+                }
+            }
+            Edit::Layout { before, after } => {
+                let layout = if is_undo { before } else { after };
+                load_map_event.send(LoadMapEvent {
+                    layout: (**layout).clone(),
+                    reset: false,
+                });
+            }
+        }
+        if is_undo {
+            history.redo.push(edit);
+        } else {
+            history.undo.push_back(edit);
+        }
+    }
+}
+
+/// Turns the editor actions of this frame into history entries, before they are applied
+#[allow(clippy::too_many_arguments)]
+pub(crate) fn record_edits(
+    mut history: ResMut<EditHistory>,
+    mut events: EventReader<EditorActionEvent>,
+    settings: Res<SimSettings>,
+    mouse_btn: Res<Input<MouseButton>>,
+    obstacle_map: Res<ObstacleMap>,
+    map_size: Res<MapSize>,
+    nest: Res<Nest>,
+    food_query: Query<(Entity, &Transform, &Food)>,
+) {
+    // Edits shown by a replay are not the user's
+    if settings.is_replaying {
+        events.clear();
+        return;
+    }
+    let mut is_painting = false;
+    for EditorActionEvent { action, is_undo_redo } in events.iter() {
+        if *is_undo_redo {
+            continue;
+        }
+        match *action {
+            EditorAction::Stroke { .. }
+            | EditorAction::Paint { .. }
+            | EditorAction::PaintDecay { .. }
+            | EditorAction::FillRect { .. }
+            | EditorAction::FloodFill { .. } => {
+                is_painting = true;
+                if history.stroke.is_none() {
+                    history.stroke = Some(StrokeStart {
+                        grid: obstacle_map.grid.clone(),
+                        decay: obstacle_map.decay.clone(),
+                    });
+                }
+            }
+            EditorAction::SetCells { .. } => {}
+            EditorAction::PlaceFood(ref spec) => history.push(Edit::Actions {
+                undo: vec![EditorAction::RemoveFood { x: spec.x, y: spec.y, radius: HISTORY_FOOD_MATCH_RADIUS }],
+                redo: vec![action.clone()],
+            }),
+            EditorAction::RemoveFood { x, y, radius } => {
+                let pos = Vec2::new(x, y);
+                let already_removed: HashSet<Entity> = history.removed_food.iter().map(|(e, _)| *e).collect();
+                for (entity, tr, food) in food_query.iter() {
+                    let food_pos = tr.translation.truncate();
+                    if food_pos.distance_squared(pos) < radius * radius && !already_removed.contains(&entity) {
+                        history.removed_food.push((entity, food.spec(food_pos)));
+                    }
+                }
+            }
+            EditorAction::RemoveNamedFood { ref name } => {
+                let undo: Vec<EditorAction> = food_query
+                    .iter()
+                    .filter(|(_, _, food)| food.name.as_ref() == Some(name))
+                    .map(|(_, tr, food)| EditorAction::PlaceFood(food.spec(tr.translation.truncate())))
+                    .collect();
+                if !undo.is_empty() {
+                    history.push(Edit::Actions {
+                        undo,
+                        redo: vec![action.clone()],
+                    });
+                }
+            }
+            EditorAction::ClearRegion { region } => {
+                let mut undo = vec![region_cells(&region, &obstacle_map)];
+                for (_, tr, food) in food_query.iter() {
+                    let pos = tr.translation.truncate();
+                    if region.contains(pos, &map_size) {
+                        undo.push(EditorAction::PlaceFood(food.spec(pos)));
+                    }
+                }
+                history.push(Edit::Actions {
+                    undo,
+                    redo: vec![action.clone()],
+                });
+            }
+            EditorAction::Paste { col, row, ref clipboard } => {
+                let mut undo = vec![region_cells(&clipboard.region_at(col, row), &obstacle_map)];
+                undo.extend(
+                    clipboard
+                        .food_at(col, row, &map_size)
+                        .iter()
+                        .map(|f| EditorAction::RemoveFood { x: f.x, y: f.y, radius: HISTORY_FOOD_MATCH_RADIUS }),
+                );
+                history.push(Edit::Actions {
+                    undo,
+                    redo: vec![action.clone()],
+                });
+            }
+            EditorAction::AddDynamicWall(ref wall) => history.push(Edit::Actions {
+                undo: vec![EditorAction::RemoveDynamicWalls {
+                    x: wall.from.0,
+                    y: wall.from.1,
+                    radius: HISTORY_FOOD_MATCH_RADIUS,
+                }],
+                redo: vec![action.clone()],
+            }),
+            EditorAction::RemoveDynamicWalls { x, y, radius } => {
+                let pos = Vec2::new(x, y);
+                let undo: Vec<EditorAction> = obstacle_map
+                    .dynamic_walls
+                    .iter()
+                    .filter(|w| w.distance_to(pos) < radius)
+                    .map(|w| EditorAction::AddDynamicWall(w.clone()))
+                    .collect();
+                if !undo.is_empty() {
+                    history.push(Edit::Actions {
+                        undo,
+                        redo: vec![action.clone()],
+                    });
+                }
+            }
+            EditorAction::MoveNest { x, y } => history.push(Edit::Actions {
+                undo: vec![EditorAction::MoveNest { x: nest.0.x, y: nest.0.y }],
+                redo: vec![EditorAction::MoveNest { x, y }],
+            }),
+        }
+    }
+
+    // Strokes and food removal end when the button is released. Shapes are sent on
+    // release, so wait until they are applied
+    if mouse_btn.pressed(MouseButton::Left) || is_painting {
+        return;
+    }
+    if let Some(start) = history.stroke.take() {
+        if let Some(edit) = stroke_edit(&start, &obstacle_map) {
+            history.push(edit);
+        }
+    }
+    if !history.removed_food.is_empty() {
+        let removed: Vec<FoodSpec> = history.removed_food.drain(..).map(|(_, spec)| spec).collect();
+        history.push(Edit::Actions {
+            undo: removed
+                .iter()
+                .map(|f| EditorAction::PlaceFood(f.clone()))
+                .collect(),
+            redo: removed
+                .iter()
+                .map(|f| EditorAction::RemoveFood { x: f.x, y: f.y, radius: HISTORY_FOOD_MATCH_RADIUS })
+                .collect(),
+        });
+    }
+}
+
+/// Restores the current cells of a region
+fn region_cells(region: &CellRegion, obstacle_map: &ObstacleMap) -> EditorAction {
+    let cells = region
+        .cells(obstacle_map)
+        .into_iter()
+        .map(|(_, _, idx)| (idx, obstacle_map.grid[idx], obstacle_map.decay[idx]))
+        .collect();
+    EditorAction::SetCells { cells }
+}
+
+/// Cells changed since the stroke started, None if nothing changed
+fn stroke_edit(start: &StrokeStart, obstacle_map: &ObstacleMap) -> Option<Edit> {
+    // The map was resized or loaded mid-stroke, the indices no longer match
+    if start.grid.len() != obstacle_map.grid.len() {
+        return None;
+    }
+    let (mut before, mut after) = (vec![], vec![]);
+    let old_cells = start.grid.iter().zip(start.decay.iter());
+    let new_cells = obstacle_map.grid.iter().zip(obstacle_map.decay.iter());
+    for (idx, (old, new)) in old_cells.zip(new_cells).enumerate() {
+        if old != new {
+            before.push((idx, *old.0, *old.1));
+            after.push((idx, *new.0, *new.1));
+        }
+    }
+    if before.is_empty() {
+        return None;
+    }
+    Some(Edit::Actions {
+        undo: vec![EditorAction::SetCells { cells: before }],
+        redo: vec![EditorAction::SetCells { cells: after }],
+    })
+}
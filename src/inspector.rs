@@ -0,0 +1,324 @@
+//! Click-to-select ants and a panel showing their internal state.
+//! Clicking an ant with the "View/Select" tool selects it, shift-click adds to or
+//! removes from the selection. The camera follows the first selected ant, and
+//! selected ants leave a trail of where they went.
+
+use crate::{
+    ant::{Ant, AntEvent, AntEventKind, AntTask, CurrentTask, DecisionTimer, PhStrength, SteerState, Velocity},
+    gui::{cursor_world_pos, EditorState, EditorTool, SimSettings},
+    *,
+};
+use bevy::prelude::*;
+use bevy_egui::{egui, EguiContexts};
+use bevy_pancam::PanCam;
+use std::collections::{HashMap, VecDeque};
+
+pub struct InspectorPlugin;
+
+/// Ants picked in the view, the first one is the one the camera follows
+#[derive(Resource, Default)]
+pub struct SelectedAnts(pub Vec<Entity>);
+
+impl SelectedAnts {
+    pub fn primary(&self) -> Option<Entity> {
+        self.0.first().copied()
+    }
+
+    pub fn contains(&self, entity: Entity) -> bool {
+        self.0.contains(&entity)
+    }
+}
+
+/// Rolling position history of a selected ant
+#[derive(Default)]
+pub struct Trail {
+    pub points: VecDeque<(Vec2, AntTask)>,
+    pub markers: VecDeque<(Vec2, AntEventKind)>,
+}
+
+#[derive(Resource, Default)]
+pub struct AntTrails(pub HashMap<Entity, Trail>);
+
+impl Plugin for InspectorPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(SelectedAnts::default())
+            .insert_resource(AntTrails::default())
+            .add_systems(Update, (prune_selection, select_ant, record_trails).chain())
+            .add_systems(Update, (draw_selection, draw_trails, draw_sensor_labels, inspector_ui));
+    }
+}
+
+/// Drops ants that were despawned, e.g. by a reset or the ant count slider
+fn prune_selection(mut selected: ResMut<SelectedAnts>, ant_query: Query<(), With<Ant>>) {
+    if selected.0.iter().any(|e| !ant_query.contains(*e)) {
+        selected.0.retain(|e| ant_query.contains(*e));
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn select_ant(
+    mut contexts: EguiContexts,
+    mut selected: ResMut<SelectedAnts>,
+    mut settings: ResMut<SimSettings>,
+    editor_state: Res<EditorState>,
+    mouse_btn: Res<Input<MouseButton>>,
+    keys: Res<Input<KeyCode>>,
+    windows: Query<&Window>,
+    camera_q: Query<(&Camera, &GlobalTransform), With<PanCam>>,
+    ant_query: Query<(Entity, &Transform), With<Ant>>,
+) {
+    if editor_state.selected_tool != EditorTool::None || !mouse_btn.just_pressed(MouseButton::Left) {
+        return;
+    }
+    let ctx = contexts.ctx_mut();
+    if ctx.is_pointer_over_area() || ctx.wants_pointer_input() {
+        return;
+    }
+    let Some(world_pos) = cursor_world_pos(&windows, &camera_q) else {
+        return;
+    };
+
+    let closest = ant_query
+        .iter()
+        .map(|(entity, tr)| (entity, tr.translation.truncate().distance_squared(world_pos)))
+        .filter(|(_, dist_sq)| *dist_sq < ANT_PICK_RADIUS * ANT_PICK_RADIUS)
+        .min_by(|a, b| a.1.total_cmp(&b.1))
+        .map(|(entity, _)| entity);
+
+    let is_adding = keys.pressed(KeyCode::ShiftLeft) || keys.pressed(KeyCode::ShiftRight);
+    match closest {
+        Some(entity) if is_adding => {
+            if selected.contains(entity) {
+                selected.0.retain(|e| *e != entity);
+            } else {
+                selected.0.push(entity);
+            }
+        }
+        Some(entity) => {
+            selected.0 = vec![entity];
+            settings.is_camera_follow = true;
+        }
+        None if !is_adding => selected.0.clear(),
+        None => {}
+    }
+}
+
+fn record_trails(
+    mut trails: ResMut<AntTrails>,
+    mut events: EventReader<AntEvent>,
+    selected: Res<SelectedAnts>,
+    settings: Res<SimSettings>,
+    ant_query: Query<(&Transform, &CurrentTask), With<Ant>>,
+) {
+    trails.0.retain(|entity, _| selected.contains(*entity));
+    if !settings.is_running() {
+        events.clear();
+        return;
+    }
+
+    for entity in selected.0.iter() {
+        let Ok((transform, task)) = ant_query.get(*entity) else {
+            continue;
+        };
+        let pos = transform.translation.truncate();
+        let trail = trails.0.entry(*entity).or_default();
+        let is_new_point = trail
+            .points
+            .back()
+            .map(|(last, last_task)| {
+                last.distance_squared(pos) >= TRAIL_MIN_STEP * TRAIL_MIN_STEP || *last_task != task.0
+            })
+            .unwrap_or(true);
+        if is_new_point {
+            if trail.points.len() >= TRAIL_LENGTH {
+                trail.points.pop_front();
+            }
+            trail.points.push_back((pos, task.0));
+        }
+    }
+
+    for event in events.iter() {
+        let Some(trail) = trails.0.get_mut(&event.entity) else {
+            continue;
+        };
+        // An ant sliding along a wall hits it every frame, one marker per spot is enough
+        let is_repeat = trail
+            .markers
+            .back()
+            .map(|(pos, kind)| *kind == event.kind && pos.distance_squared(event.pos) < 10.0 * 10.0)
+            .unwrap_or(false);
+        if is_repeat {
+            continue;
+        }
+        if trail.markers.len() >= TRAIL_MAX_MARKERS {
+            trail.markers.pop_front();
+        }
+        trail.markers.push_back((event.pos, event.kind));
+    }
+}
+
+fn draw_trails(mut gizmos: Gizmos, trails: Res<AntTrails>, settings: Res<SimSettings>) {
+    if !settings.is_show_trails {
+        return;
+    }
+
+    for trail in trails.0.values() {
+        // Older segments fade out
+        let n = trail.points.len() as f32;
+        for (i, (a, b)) in trail.points.iter().zip(trail.points.iter().skip(1)).enumerate() {
+            let alpha = (i + 1) as f32 / n;
+            let color = match b.1 {
+                AntTask::FindFood => Color::rgba(0.4, 0.6, 1.0, alpha),
+                AntTask::FindHome => Color::rgba(0.3, 1.0, 0.3, alpha),
+            };
+            gizmos.line_2d(a.0, b.0, color);
+        }
+
+        let n = trail.markers.len() as f32;
+        for (i, (pos, kind)) in trail.markers.iter().enumerate() {
+            let alpha = 0.3 + 0.7 * (i + 1) as f32 / n;
+            match kind {
+                AntEventKind::Pickup => {
+                    gizmos.circle_2d(*pos, 8.0, Color::rgba(1.0, 1.0, 0.2, alpha));
+                }
+                AntEventKind::Delivery => {
+                    gizmos.circle_2d(*pos, 8.0, Color::rgba(0.2, 1.0, 1.0, alpha));
+                }
+                AntEventKind::WallHit => {
+                    let color = Color::rgba(1.0, 0.2, 0.2, alpha);
+                    gizmos.line_2d(*pos + Vec2::new(-4.0, -4.0), *pos + Vec2::new(4.0, 4.0), color);
+                    gizmos.line_2d(*pos + Vec2::new(-4.0, 4.0), *pos + Vec2::new(4.0, -4.0), color);
+                }
+            }
+        }
+    }
+}
+
+fn draw_selection(
+    mut gizmos: Gizmos,
+    selected: Res<SelectedAnts>,
+    ant_query: Query<(&Transform, &SteerState), With<Ant>>,
+) {
+    for (i, entity) in selected.0.iter().enumerate() {
+        let Ok((transform, steer)) = ant_query.get(*entity) else {
+            continue;
+        };
+        let pos = transform.translation.truncate();
+        let color = if i == 0 { Color::YELLOW } else { Color::ORANGE };
+        gizmos.circle_2d(pos, ANT_PICK_RADIUS, color);
+        if let Some(target) = steer.target {
+            gizmos.line_2d(pos, target, color);
+        }
+    }
+}
+
+/// Numeric sensor values next to the debug overlay, only for selected ants to stay readable
+fn draw_sensor_labels(
+    mut contexts: EguiContexts,
+    settings: Res<SimSettings>,
+    selected: Res<SelectedAnts>,
+    camera_q: Query<(&Camera, &GlobalTransform), With<PanCam>>,
+    ant_query: Query<&SteerState, With<Ant>>,
+) {
+    if !settings.is_show_ants_path || selected.0.is_empty() {
+        return;
+    }
+    let Ok((camera, camera_transform)) = camera_q.get_single() else {
+        return;
+    };
+
+    let painter = contexts.ctx_mut().layer_painter(egui::LayerId::new(
+        egui::Order::Background,
+        egui::Id::new("sensor_labels"),
+    ));
+    for steer in selected.0.iter().filter_map(|e| ant_query.get(*e).ok()) {
+        for (p, v) in steer.sensors.positions.iter().zip(steer.sensors.values) {
+            let Some(screen_pos) = camera.world_to_viewport(camera_transform, p.extend(0.0)) else {
+                continue;
+            };
+            painter.text(
+                egui::pos2(screen_pos.x, screen_pos.y - 6.0),
+                egui::Align2::CENTER_BOTTOM,
+                format!("{:.0}", v),
+                egui::FontId::monospace(12.0),
+                egui::Color32::WHITE,
+            );
+        }
+    }
+}
+
+#[allow(clippy::type_complexity)]
+fn inspector_ui(
+    mut contexts: EguiContexts,
+    mut selected: ResMut<SelectedAnts>,
+    settings: Res<SimSettings>,
+    ant_query: Query<
+        (&Transform, &Velocity, &CurrentTask, &PhStrength, &DecisionTimer, &SteerState),
+        With<Ant>,
+    >,
+) {
+    if !settings.is_show_menu || selected.0.is_empty() {
+        return;
+    }
+
+    let ctx = contexts.ctx_mut();
+    let mut deselect = None;
+
+    egui::Window::new("Inspector")
+        .default_pos(egui::pos2(W - 340.0, 80.0))
+        .show(ctx, |ui| {
+            for (i, entity) in selected.0.iter().enumerate() {
+                let Ok((transform, velocity, task, ph_strength, timer, steer)) = ant_query.get(*entity) else {
+                    continue;
+                };
+                let pos = transform.translation.truncate();
+                let title = format!("Ant {:?}{}", entity, if i == 0 { " (followed)" } else { "" });
+
+                egui::CollapsingHeader::new(title)
+                    .id_source(entity)
+                    .default_open(i == 0)
+                    .show(ui, |ui| {
+                        egui::Grid::new(("inspector", *entity)).num_columns(2).show(ui, |ui| {
+                            ui.label("Position");
+                            ui.label(format!("({:.1}, {:.1})", pos.x, pos.y));
+                            ui.end_row();
+                            ui.label("Velocity");
+                            ui.label(format!(
+                                "({:.2}, {:.2}) |{:.2}|",
+                                velocity.0.x,
+                                velocity.0.y,
+                                velocity.0.length()
+                            ));
+                            ui.end_row();
+                            ui.label("Task");
+                            ui.label(format!("{:?}", task.0));
+                            ui.end_row();
+                            ui.label("Ph Strength");
+                            ui.label(format!("{:.1}", ph_strength.0));
+                            ui.end_row();
+                            ui.label("Decision Timer");
+                            ui.label(format!("{:.3}s", timer.0));
+                            ui.end_row();
+                            ui.label("Sensors L/F/R");
+                            let [l, f, r] = steer.sensors.values;
+                            ui.label(format!("{:.1} / {:.1} / {:.1}", l, f, r));
+                            ui.end_row();
+                            ui.label("Steer Target");
+                            ui.label(match steer.target {
+                                Some(t) if steer.is_auto_pull => format!("({:.1}, {:.1}) auto pull", t.x, t.y),
+                                Some(t) => format!("({:.1}, {:.1}) sensors", t.x, t.y),
+                                None => "none, random walk".to_string(),
+                            });
+                            ui.end_row();
+                        });
+                        if ui.button("Deselect").clicked() {
+                            deselect = Some(*entity);
+                        }
+                    });
+            }
+        });
+
+    if let Some(entity) = deselect {
+        selected.0.retain(|e| *e != entity);
+    }
+}
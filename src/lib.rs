@@ -1,10 +1,28 @@
 pub mod ant;
+pub mod cli;
+pub mod clipboard;
 pub mod configs;
+pub mod doors;
+pub mod food;
+pub mod frames;
 pub mod grid;
 pub mod gui;
+pub mod headless;
+pub mod history;
+pub mod inspector;
 pub mod pathviz;
 pub mod pheromone;
+pub mod recording;
+pub mod rewind;
 pub mod map;
+pub mod mapgen;
+pub mod network;
+pub mod occupancy;
+pub mod optimizer;
+pub mod overlay;
+pub mod scenario;
+pub mod script;
+pub mod shapes;
 pub mod utils;
 
 pub use configs::*;
@@ -1,9 +1,23 @@
 use ants::{
     ant::{AntFollowCameraPos, AntPlugin},
+    clipboard::ClipboardPlugin,
+    doors::DoorsPlugin,
+    food::FoodPlugin,
+    frames::FramesPlugin,
     gui::{GuiPlugin, SimSettings},
+    history::HistoryPlugin,
+    inspector::InspectorPlugin,
+    overlay::OverlayPlugin,
     pathviz::PathVizPlugin,
     pheromone::PheromonePlugin,
+    recording::RecordingPlugin,
+    rewind::RewindPlugin,
     map::MapPlugin,
+    network::NetworkPlugin,
+    occupancy::OccupancyPlugin,
+    scenario::ScenarioPlugin,
+    script::ScriptPlugin,
+    shapes::ShapesPlugin,
     *,
 };
 use bevy::{
@@ -17,6 +31,9 @@ use std::{thread, time::{Duration, Instant}};
 #[derive(Component)]
 struct FollowCamera;
 
+#[derive(Component)]
+struct NestSprite;
+
 #[derive(Resource)]
 struct FrameLimiter {
     last_frame: Instant,
@@ -33,6 +50,12 @@ impl Default for FrameLimiter {
 }
 
 fn main() {
+    // Batch commands (e.g. `optimize`) run headless and exit
+    let args: Vec<String> = std::env::args().collect();
+    if ants::cli::run(&args) {
+        return;
+    }
+
     App::new()
         .init_resource::<FrameLimiter>()
         .add_plugins(
@@ -64,6 +87,7 @@ fn main() {
         .add_systems(Startup, setup)
         .add_systems(Update, ant_follow_camera)
         .add_systems(Update, update_border_size)
+        .add_systems(Update, update_nest_sprite)
         .add_systems(Last, limit_fps)
         // Internal Plugins
         .add_plugins(AntPlugin)
@@ -71,6 +95,20 @@ fn main() {
         .add_plugins(PathVizPlugin)
         .add_plugins(MapPlugin)
         .add_plugins(GuiPlugin)
+        .add_plugins(HistoryPlugin)
+        .add_plugins(ShapesPlugin)
+        .add_plugins(ClipboardPlugin)
+        .add_plugins(DoorsPlugin)
+        .add_plugins(FoodPlugin)
+        .add_plugins(ScenarioPlugin)
+        .add_plugins(ScriptPlugin)
+        .add_plugins(RecordingPlugin)
+        .add_plugins(RewindPlugin)
+        .add_plugins(InspectorPlugin)
+        .add_plugins(OverlayPlugin)
+        .add_plugins(NetworkPlugin)
+        .add_plugins(OccupancyPlugin)
+        .add_plugins(FramesPlugin)
         .run();
 }
 
@@ -112,16 +150,19 @@ fn setup(
         });
 
     // Ant colony sprite
-    commands.spawn(SpriteBundle {
-        texture: asset_server.load(SPRITE_ANT_COLONY),
-        sprite: Sprite {
-            color: Color::rgb(1.5, 1.5, 1.5),
-            ..default()
+    commands.spawn((
+        SpriteBundle {
+            texture: asset_server.load(SPRITE_ANT_COLONY),
+            sprite: Sprite {
+                color: Color::rgb(1.5, 1.5, 1.5),
+                ..default()
+            },
+            transform: Transform::from_xyz(HOME_LOCATION.0, HOME_LOCATION.1, 2.0)
+                .with_scale(Vec3::splat(HOME_SPRITE_SCALE)),
+            ..Default::default()
         },
-        transform: Transform::from_xyz(HOME_LOCATION.0, HOME_LOCATION.1, 2.0)
-            .with_scale(Vec3::splat(HOME_SPRITE_SCALE)),
-        ..Default::default()
-    });
+        NestSprite,
+    ));
 
     // Programmatic Border (Glass Tank Effect)
     // Create a 1x1 White Pixel Texture
@@ -198,6 +239,18 @@ fn update_border_size(
     }
 }
 
+fn update_nest_sprite(
+    nest: Res<ants::map::Nest>,
+    mut query: Query<&mut Transform, With<NestSprite>>,
+) {
+    if nest.is_changed() {
+        for mut transform in query.iter_mut() {
+            transform.translation.x = nest.0.x;
+            transform.translation.y = nest.0.y;
+        }
+    }
+}
+
 fn limit_fps(mut limiter: ResMut<FrameLimiter>, keys: Res<Input<KeyCode>>) {
     if keys.just_pressed(KeyCode::Minus) {
         if limiter.target_fps.is_none() {
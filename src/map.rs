@@ -1,5 +1,14 @@
 use bevy::prelude::*;
-use crate::configs::{W, H, PH_UNIT_GRID_SIZE};
+use crate::{
+    ant::{spawn_food, Food},
+    configs::{
+        W, H, PH_UNIT_GRID_SIZE, FOOD_LOCATION, FOOD_PICKUP_RADIUS, FOOD_QUALITY, FOOD_STORAGE, HOME_LOCATION,
+        MAX_DECAY_FACTOR, DYNAMIC_WALL_COLOR,
+    },
+    doors::DynamicWall,
+    food::Regrow,
+    gui::ResetSimEvent,
+};
 use serde::{Deserialize, Serialize};
 
 #[derive(Resource, Serialize, Deserialize, Clone, Copy)]
@@ -17,31 +26,120 @@ impl Default for MapSize {
     }
 }
 
-#[derive(Resource)]
-pub struct ObstacleMap {
-    pub grid: Vec<bool>, // true = obstacle
-    pub width: usize,
-    pub height: usize,
-    pub texture_handle: Handle<Image>,
+/// Footprint of the editor brush
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub enum BrushShape {
+    #[default]
+    Round,
+    /// Axis aligned, for clean straight walls
+    Square,
+}
+
+impl BrushShape {
+    pub fn label(&self) -> &'static str {
+        match self {
+            BrushShape::Round => "Round",
+            BrushShape::Square => "Square",
+        }
+    }
 }
 
-impl Default for ObstacleMap {
+/// Position of the ant colony
+#[derive(Resource, Clone, Copy)]
+pub struct Nest(pub Vec2);
+
+impl Default for Nest {
     fn default() -> Self {
-        Self {
-            grid: vec![], // Initialized in setup
-            width: 0,
-            height: 0,
-            texture_handle: Handle::default(),
+        Self(Vec2::new(HOME_LOCATION.0, HOME_LOCATION.1))
+    }
+}
+
+/// Ground type of a map cell
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub enum Terrain {
+    #[default]
+    Open,
+    Wall,
+    /// Slow
+    Sand,
+    /// Very slow, ants wade through it
+    Water,
+    /// Slow and ants turn more randomly
+    Rough,
+}
+
+impl Terrain {
+    /// Types painted with the terrain brush, walls have their own tool
+    pub const PAINTABLE: [Terrain; 4] = [Terrain::Sand, Terrain::Water, Terrain::Rough, Terrain::Open];
+
+    pub fn is_obstacle(&self) -> bool {
+        *self == Terrain::Wall
+    }
+
+    /// Multiplier of ANT_SPEED for ants on this terrain
+    pub fn speed_factor(&self) -> f32 {
+        match self {
+            Terrain::Open => 1.0,
+            Terrain::Wall => 0.0,
+            Terrain::Sand => 0.5,
+            Terrain::Water => 0.2,
+            Terrain::Rough => 0.7,
+        }
+    }
+
+    /// Multiplier of the random part of the steering
+    pub fn turn_factor(&self) -> f32 {
+        match self {
+            Terrain::Rough => 3.0,
+            _ => 1.0,
+        }
+    }
+
+    /// Colour in the obstacle texture, open ground is transparent
+    pub fn color(&self) -> [u8; 4] {
+        match self {
+            Terrain::Open => [0, 0, 0, 0],
+            Terrain::Wall => [100, 100, 100, 255],
+            Terrain::Sand => [194, 170, 110, 110],
+            Terrain::Water => [40, 90, 210, 140],
+            Terrain::Rough => [120, 90, 60, 140],
+        }
+    }
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            Terrain::Open => "Open",
+            Terrain::Wall => "Wall",
+            Terrain::Sand => "Sand",
+            Terrain::Water => "Water",
+            Terrain::Rough => "Rough",
         }
     }
 }
 
+#[derive(Resource, Default)]
+pub struct ObstacleMap {
+    pub grid: Vec<Terrain>,
+    /// Per-cell multiplier of the pheromone decay rate, 1.0 is the global rate
+    pub decay: Vec<f32>,
+    /// Doors and sliding walls, kept apart from `grid` so painting never erases them
+    pub dynamic_walls: Vec<DynamicWall>,
+    /// Cells the dynamic walls cover right now, empty when there are none
+    pub dynamic_cells: Vec<bool>,
+    pub width: usize,
+    pub height: usize,
+    pub texture_handle: Handle<Image>,
+}
+
 impl ObstacleMap {
     pub fn new(w: f32, h: f32) -> Self {
         let cols = (w as usize / PH_UNIT_GRID_SIZE) + 1;
         let rows = (h as usize / PH_UNIT_GRID_SIZE) + 1;
         Self {
-            grid: vec![false; cols * rows],
+            grid: vec![Terrain::Open; cols * rows],
+            decay: vec![1.0; cols * rows],
+            dynamic_walls: vec![],
+            dynamic_cells: vec![],
             width: cols,
             height: rows,
             texture_handle: Handle::default(),
@@ -80,7 +178,13 @@ impl ObstacleMap {
          if gx < 0 || gx >= self.width as isize || gy < 0 || gy >= self.height as isize {
              return true; // Treat OOB as obstacle
          }
-         self.grid[gy as usize * self.width + gx as usize]
+         let idx = gy as usize * self.width + gx as usize;
+         self.grid[idx].is_obstacle() || self.is_dynamic_wall(idx)
+    }
+
+    /// True if a door or sliding wall covers the cell at the moment
+    pub fn is_dynamic_wall(&self, idx: usize) -> bool {
+        self.dynamic_cells.get(idx).copied().unwrap_or(false)
     }
     
     pub fn is_obstacle(&self, x: f32, y: f32, map_w: f32, map_h: f32) -> bool {
@@ -89,25 +193,192 @@ impl ObstacleMap {
          self.is_obstacle_at_index(grid_x, grid_y)
     }
 
+    /// Terrain under a world position, outside the map counts as wall
+    pub fn terrain_at(&self, x: f32, y: f32, map_w: f32, map_h: f32) -> Terrain {
+        let gx = ((x + map_w / 2.0) / PH_UNIT_GRID_SIZE as f32) as isize;
+        let gy = ((y + map_h / 2.0) / PH_UNIT_GRID_SIZE as f32) as isize;
+        if gx < 0 || gx >= self.width as isize || gy < 0 || gy >= self.height as isize {
+            return Terrain::Wall;
+        }
+        let idx = gy as usize * self.width + gx as usize;
+        if self.is_dynamic_wall(idx) {
+            return Terrain::Wall;
+        }
+        self.grid[idx]
+    }
+
+    /// Decay multiplier under a world position, outside the map decays normally
+    pub fn decay_factor_at(&self, x: f32, y: f32, map_w: f32, map_h: f32) -> f32 {
+        let gx = ((x + map_w / 2.0) / PH_UNIT_GRID_SIZE as f32) as isize;
+        let gy = ((y + map_h / 2.0) / PH_UNIT_GRID_SIZE as f32) as isize;
+        if gx < 0 || gx >= self.width as isize || gy < 0 || gy >= self.height as isize {
+            return 1.0;
+        }
+        self.decay[gy as usize * self.width + gx as usize]
+    }
+
+    /// Colour of a cell in the obstacle texture: the terrain, and on open ground a
+    /// faint tint where the decay differs, warm for fast and cold for slow decay.
+    /// Doors and sliding walls have their own colour
+    pub fn cell_color(&self, idx: usize) -> [u8; 4] {
+        if self.is_dynamic_wall(idx) {
+            return DYNAMIC_WALL_COLOR;
+        }
+        let terrain = self.grid[idx];
+        let factor = self.decay[idx];
+        if terrain != Terrain::Open || factor == 1.0 {
+            return terrain.color();
+        }
+        if factor > 1.0 {
+            let t = ((factor - 1.0) / (MAX_DECAY_FACTOR - 1.0)).min(1.0);
+            [230, 90, 30, (30.0 + 70.0 * t) as u8]
+        } else {
+            [60, 200, 220, (30.0 + 70.0 * (1.0 - factor)) as u8]
+        }
+    }
+
+    /// `cell_color` under a world position, outside the map is drawn as wall
+    pub fn cell_color_at(&self, x: f32, y: f32, map_w: f32, map_h: f32) -> [u8; 4] {
+        let gx = ((x + map_w / 2.0) / PH_UNIT_GRID_SIZE as f32) as isize;
+        let gy = ((y + map_h / 2.0) / PH_UNIT_GRID_SIZE as f32) as isize;
+        if gx < 0 || gx >= self.width as isize || gy < 0 || gy >= self.height as isize {
+            return Terrain::Wall.color();
+        }
+        self.cell_color(gy as usize * self.width + gx as usize)
+    }
+
     pub fn set_obstacle(&mut self, x: f32, y: f32, map_w: f32, map_h: f32, is_obstacle: bool, brush_size: f32) {
+        let terrain = if is_obstacle { Terrain::Wall } else { Terrain::Open };
+        self.set_terrain(x, y, map_w, map_h, terrain, brush_size);
+    }
+
+    pub fn set_terrain(&mut self, x: f32, y: f32, map_w: f32, map_h: f32, terrain: Terrain, brush_size: f32) {
+        for idx in self.brush_cells(x, y, map_w, map_h, brush_size, BrushShape::Round) {
+            self.grid[idx] = terrain;
+        }
+    }
+
+    pub fn set_decay(&mut self, x: f32, y: f32, map_w: f32, map_h: f32, factor: f32, brush_size: f32) {
+        for idx in self.brush_cells(x, y, map_w, map_h, brush_size, BrushShape::Round) {
+            self.decay[idx] = factor;
+        }
+    }
+
+    /// Indices of the cells covered by the brush
+    fn brush_cells(&self, x: f32, y: f32, map_w: f32, map_h: f32, brush_size: f32, shape: BrushShape) -> Vec<usize> {
+        let mut cells = Vec::new();
         let center_grid_x = ((x + map_w / 2.0) / PH_UNIT_GRID_SIZE as f32) as isize;
         let center_grid_y = ((y + map_h / 2.0) / PH_UNIT_GRID_SIZE as f32) as isize;
         let radius = (brush_size / PH_UNIT_GRID_SIZE as f32).ceil() as isize;
 
         for dy in -radius..=radius {
             for dx in -radius..=radius {
-                if dx*dx + dy*dy > radius*radius { continue; }
+                if shape == BrushShape::Round && dx*dx + dy*dy > radius*radius { continue; }
                 
                 let gx = center_grid_x + dx;
                 let gy = center_grid_y + dy;
 
                 if gx >= 0 && gx < self.width as isize && gy >= 0 && gy < self.height as isize {
-                    self.grid[gy as usize * self.width + gx as usize] = is_obstacle;
+                    cells.push(gy as usize * self.width + gx as usize);
                 }
             }
         }
+        cells
     }
     
+    /// Cells covered by the dynamic walls at `secs` of sim time
+    pub fn dynamic_cells_at(&self, secs: f32, map_w: f32, map_h: f32) -> Vec<bool> {
+        if self.dynamic_walls.is_empty() {
+            return vec![];
+        }
+        let mut cells = vec![false; self.grid.len()];
+        for wall in self.dynamic_walls.iter() {
+            let Some((start, end)) = wall.segment_at(secs) else {
+                continue;
+            };
+            for p in stroke_points(start, end, wall.radius) {
+                for idx in self.brush_cells(p.x, p.y, map_w, map_h, wall.radius, BrushShape::Round) {
+                    cells[idx] = true;
+                }
+            }
+        }
+        cells
+    }
+
+    /// Paints a wall (or erases) along a brush stroke
+    #[allow(clippy::too_many_arguments)]
+    pub fn set_obstacle_line(&mut self, start: Vec2, end: Vec2, map_w: f32, map_h: f32, is_obstacle: bool, brush_size: f32, shape: BrushShape) {
+        let terrain = if is_obstacle { Terrain::Wall } else { Terrain::Open };
+        self.set_terrain_line(start, end, map_w, map_h, terrain, brush_size, shape);
+    }
+
+    /// Paints a brush stroke from `start` to `end`
+    #[allow(clippy::too_many_arguments)]
+    pub fn set_terrain_line(&mut self, start: Vec2, end: Vec2, map_w: f32, map_h: f32, terrain: Terrain, brush_size: f32, shape: BrushShape) {
+        for p in stroke_points(start, end, brush_size) {
+            for idx in self.brush_cells(p.x, p.y, map_w, map_h, brush_size, shape) {
+                self.grid[idx] = terrain;
+            }
+        }
+    }
+
+    /// Paints the decay multiplier along a brush stroke
+    #[allow(clippy::too_many_arguments)]
+    pub fn set_decay_line(&mut self, start: Vec2, end: Vec2, map_w: f32, map_h: f32, factor: f32, brush_size: f32, shape: BrushShape) {
+        for p in stroke_points(start, end, brush_size) {
+            for idx in self.brush_cells(p.x, p.y, map_w, map_h, brush_size, shape) {
+                self.decay[idx] = factor;
+            }
+        }
+    }
+
+    /// Sets every cell whose centre lies in the rectangle spanned by two corners
+    pub fn fill_rect(&mut self, a: Vec2, b: Vec2, map_w: f32, map_h: f32, terrain: Terrain) {
+        let (min, max) = (a.min(b), a.max(b));
+        let unit = PH_UNIT_GRID_SIZE as f32;
+        let to_grid = |v: f32, half: f32| ((v + half) / unit - 0.5).ceil() as isize;
+        let (x0, x1) = (to_grid(min.x, map_w / 2.0).max(0), to_grid(max.x, map_w / 2.0));
+        let (y0, y1) = (to_grid(min.y, map_h / 2.0).max(0), to_grid(max.y, map_h / 2.0));
+        for gy in y0..y1.min(self.height as isize) {
+            for gx in x0..x1.min(self.width as isize) {
+                self.grid[gy as usize * self.width + gx as usize] = terrain;
+            }
+        }
+    }
+
+    /// Sets the 4-connected area of cells with the same terrain as the cell under
+    /// `(x, y)`, stopping at any other terrain and the map edge
+    pub fn flood_fill(&mut self, x: f32, y: f32, map_w: f32, map_h: f32, terrain: Terrain) {
+        let gx = ((x + map_w / 2.0) / PH_UNIT_GRID_SIZE as f32) as isize;
+        let gy = ((y + map_h / 2.0) / PH_UNIT_GRID_SIZE as f32) as isize;
+        if gx < 0 || gx >= self.width as isize || gy < 0 || gy >= self.height as isize {
+            return;
+        }
+        let start = gy as usize * self.width + gx as usize;
+        let target = self.grid[start];
+        if target == terrain {
+            return;
+        }
+
+        let mut stack = vec![start];
+        self.grid[start] = terrain;
+        while let Some(idx) = stack.pop() {
+            let (cx, cy) = (idx % self.width, idx / self.width);
+            let neighbours = [
+                (cx > 0).then(|| idx - 1),
+                (cx + 1 < self.width).then(|| idx + 1),
+                (cy > 0).then(|| idx - self.width),
+                (cy + 1 < self.height).then(|| idx + self.width),
+            ];
+            for next in neighbours.into_iter().flatten() {
+                if self.grid[next] == target {
+                    self.grid[next] = terrain;
+                    stack.push(next);
+                }
+            }
+        }
+    }
+
     pub fn has_line_of_sight(&self, start: Vec2, end: Vec2, map_w: f32, map_h: f32) -> bool {
          let grid_x0 = ((start.x + map_w / 2.0) / PH_UNIT_GRID_SIZE as f32) as isize;
          let grid_y0 = ((start.y + map_h / 2.0) / PH_UNIT_GRID_SIZE as f32) as isize;
@@ -144,18 +415,228 @@ impl ObstacleMap {
     }
 
     pub fn clear(&mut self) {
-        self.grid.fill(false);
+        self.grid.fill(Terrain::Open);
+        self.decay.fill(1.0);
+        self.dynamic_walls.clear();
+        self.dynamic_cells.clear();
     }
 }
 
+/// Brush positions along a stroke, close enough that the stroke has no gaps
+fn stroke_points(start: Vec2, end: Vec2, brush_size: f32) -> Vec<Vec2> {
+    let dist = start.distance(end);
+    let step = (brush_size * 0.25).max(1.0);
+
+    if dist > step {
+        let steps = (dist / step).ceil() as i32;
+        (0..=steps).map(|i| start.lerp(end, i as f32 / steps as f32)).collect()
+    } else {
+        vec![end]
+    }
+}
+
+/// A food source as stored in a map file
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct FoodSpec {
+    pub x: f32,
+    pub y: f32,
+    pub storage: i32,
+    /// Ants closer than this pick up food
+    #[serde(default = "default_pickup_radius")]
+    pub pickup_radius: f32,
+    /// Value of one delivery, richer sources also get stronger trails
+    #[serde(default = "default_food_quality")]
+    pub quality: f32,
+    /// Regrowing sources stay on the map when eaten empty
+    #[serde(default)]
+    pub regrow: Option<Regrow>,
+    /// Lets scripts remove this source by name
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+}
+
+fn default_pickup_radius() -> f32 {
+    FOOD_PICKUP_RADIUS
+}
+
+fn default_food_quality() -> f32 {
+    FOOD_QUALITY
+}
+
+impl FoodSpec {
+    /// Source with the default pickup radius and quality
+    pub fn new(x: f32, y: f32, storage: i32) -> Self {
+        Self {
+            x,
+            y,
+            storage,
+            pickup_radius: FOOD_PICKUP_RADIUS,
+            quality: FOOD_QUALITY,
+            regrow: None,
+            name: None,
+        }
+    }
+
+    pub fn pos(&self) -> Vec2 {
+        Vec2::new(self.x, self.y)
+    }
+}
+
+/// Walls, terrain and food of an arena, saved as json so the same map can be
+/// reloaded in the editor or used for headless runs
+#[derive(Serialize, Deserialize, Clone)]
+pub struct MapLayout {
+    pub map_size: MapSize,
+    #[serde(default = "default_nest")]
+    pub nest: (f32, f32),
+    pub walls: Vec<usize>, // Indices into ObstacleMap::grid
+    /// Cells that are neither open nor wall
+    #[serde(default)]
+    pub terrain: Vec<(usize, Terrain)>,
+    /// Cells with a decay multiplier other than 1.0
+    #[serde(default)]
+    pub decay: Vec<(usize, f32)>,
+    pub food: Vec<FoodSpec>,
+    /// Doors and sliding walls
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub dynamic_walls: Vec<DynamicWall>,
+}
+
+fn default_nest() -> (f32, f32) {
+    HOME_LOCATION
+}
+
+impl MapLayout {
+    /// Empty arena with a single food source at FOOD_LOCATION
+    pub fn new(map_size: MapSize) -> Self {
+        Self {
+            map_size,
+            nest: HOME_LOCATION,
+            walls: vec![],
+            terrain: vec![],
+            decay: vec![],
+            food: vec![FoodSpec::new(FOOD_LOCATION.0, FOOD_LOCATION.1, FOOD_STORAGE)],
+            dynamic_walls: vec![],
+        }
+    }
+
+    pub fn capture(obstacle_map: &ObstacleMap, map_size: MapSize, nest: &Nest, food: Vec<FoodSpec>) -> Self {
+        let walls = obstacle_map
+            .grid
+            .iter()
+            .enumerate()
+            .filter(|(_, t)| t.is_obstacle())
+            .map(|(idx, _)| idx)
+            .collect();
+        let terrain = obstacle_map
+            .grid
+            .iter()
+            .enumerate()
+            .filter(|(_, t)| !matches!(t, Terrain::Open | Terrain::Wall))
+            .map(|(idx, t)| (idx, *t))
+            .collect();
+        let decay = obstacle_map
+            .decay
+            .iter()
+            .enumerate()
+            .filter(|(_, f)| **f != 1.0)
+            .map(|(idx, f)| (idx, *f))
+            .collect();
+
+        Self {
+            map_size,
+            nest: (nest.0.x, nest.0.y),
+            walls,
+            terrain,
+            decay,
+            food,
+            dynamic_walls: obstacle_map.dynamic_walls.clone(),
+        }
+    }
+
+    pub fn to_obstacle_map(&self) -> ObstacleMap {
+        let mut obstacle_map = ObstacleMap::new(self.map_size.width, self.map_size.height);
+        for &idx in self.walls.iter() {
+            if let Some(cell) = obstacle_map.grid.get_mut(idx) {
+                *cell = Terrain::Wall;
+            }
+        }
+        for &(idx, terrain) in self.terrain.iter() {
+            if let Some(cell) = obstacle_map.grid.get_mut(idx) {
+                *cell = terrain;
+            }
+        }
+        for &(idx, factor) in self.decay.iter() {
+            if let Some(cell) = obstacle_map.decay.get_mut(idx) {
+                *cell = factor;
+            }
+        }
+        obstacle_map.dynamic_walls = self.dynamic_walls.clone();
+        obstacle_map
+    }
+
+    pub fn load(path: &str) -> Result<Self, String> {
+        let content = std::fs::read_to_string(path).map_err(|e| format!("{}: {}", path, e))?;
+        serde_json::from_str(&content).map_err(|e| format!("{}: {}", path, e))
+    }
+
+    pub fn save(&self, path: &str) -> Result<(), String> {
+        let json = serde_json::to_string(self).map_err(|e| e.to_string())?;
+        std::fs::write(path, json).map_err(|e| format!("{}: {}", path, e))
+    }
+}
+
+/// Replaces the current walls, food and nest with the given layout
+#[derive(Event)]
+pub struct LoadMapEvent {
+    pub layout: MapLayout,
+    /// Also reset ants and pheromones, e.g. when starting a scenario
+    pub reset: bool,
+}
+
 pub struct MapPlugin;
 
 impl Plugin for MapPlugin {
     fn build(&self, app: &mut App) {
         app.insert_resource(MapSize::default())
            .insert_resource(ObstacleMap::new(W, H))
+           .insert_resource(Nest::default())
+           .add_event::<LoadMapEvent>()
            .add_systems(Startup, setup_obstacle_texture)
-           .add_systems(Update, (resize_obstacle_map, update_obstacle_texture));
+           .add_systems(Update, (load_map, resize_obstacle_map, update_obstacle_texture).chain());
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn load_map(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    mut events: EventReader<LoadMapEvent>,
+    mut reset_sim_event: EventWriter<ResetSimEvent>,
+    mut obstacle_map: ResMut<ObstacleMap>,
+    mut map_size: ResMut<MapSize>,
+    mut nest: ResMut<Nest>,
+    food_query: Query<Entity, With<Food>>,
+) {
+    for event in events.iter() {
+        let layout = &event.layout;
+        for entity in food_query.iter() {
+            commands.entity(entity).despawn();
+        }
+        for f in layout.food.iter() {
+            spawn_food(&mut commands, &asset_server, f.clone());
+        }
+
+        let texture_handle = obstacle_map.texture_handle.clone();
+        *obstacle_map = layout.to_obstacle_map();
+        obstacle_map.texture_handle = texture_handle;
+        *map_size = layout.map_size;
+        nest.0 = Vec2::new(layout.nest.0, layout.nest.1);
+
+        // Nest is already moved, so respawned ants start from the new one
+        if event.reset {
+            reset_sim_event.send(ResetSimEvent);
+        }
     }
 }
 
@@ -209,21 +690,34 @@ fn resize_obstacle_map(
             // Or better, creating a new struct instance but keeping texture handle?
             obstacle_map.width = new_w;
             obstacle_map.height = new_h;
-            obstacle_map.grid = vec![false; new_w * new_h];
+            obstacle_map.grid = vec![Terrain::Open; new_w * new_h];
+            obstacle_map.decay = vec![1.0; new_w * new_h];
+            // Dynamic walls are in world units and stay, their cells are redone next frame
+            obstacle_map.dynamic_cells.clear();
             
             // Allow OOB logic to work correctly now with new dimensions.
+        }
+    }
 
-            // Also resize texture
-            if let Some(image) = images.get_mut(&obstacle_map.texture_handle) {
-                 image.resize(bevy::render::render_resource::Extent3d {
-                    width: new_w as u32,
-                    height: new_h as u32,
-                    depth_or_array_layers: 1,
-                });
-                // Initialize with transparent
-                image.data = vec![0; new_w * new_h * 4];
-            }
+    // Also resize texture. Checked separately because a loaded map replaces
+    // the grid with the right dimensions already
+    let needs_resize = images
+        .get(&obstacle_map.texture_handle)
+        .map(|image| image.size().x as usize != obstacle_map.width || image.size().y as usize != obstacle_map.height)
+        .unwrap_or(false);
+    if needs_resize {
+        let (new_w, new_h) = (obstacle_map.width, obstacle_map.height);
+        if let Some(image) = images.get_mut(&obstacle_map.texture_handle) {
+             image.resize(bevy::render::render_resource::Extent3d {
+                width: new_w as u32,
+                height: new_h as u32,
+                depth_or_array_layers: 1,
+            });
+            // Initialize with transparent
+            image.data = vec![0; new_w * new_h * 4];
         }
+        // Make sure the new texture gets redrawn
+        obstacle_map.set_changed();
     }
 }
 
@@ -245,19 +739,12 @@ fn update_obstacle_texture(
                 
                 for x in 0..obstacle_map.width {
                     let idx = y * obstacle_map.width + x;
-                    let is_obs = obstacle_map.grid[idx];
+                    let color = obstacle_map.cell_color(idx);
                     
                     let pixel_idx = (img_y * obstacle_map.width + x) * 4;
                     
                     if pixel_idx + 3 < image.data.len() {
-                        if is_obs {
-                            image.data[pixel_idx] = 100;   // R
-                            image.data[pixel_idx + 1] = 100; // G
-                            image.data[pixel_idx + 2] = 100; // B
-                            image.data[pixel_idx + 3] = 255; // Alpha
-                        } else {
-                            image.data[pixel_idx + 3] = 0;   // Transparent
-                        }
+                        image.data[pixel_idx..pixel_idx + 4].copy_from_slice(&color);
                     }
                 }
             }
@@ -0,0 +1,487 @@
+//! Procedural arenas for experiments over families of maps.
+//! Each generator fills the walls of an `ObstacleMap` from a seed. Afterwards a
+//! clearing is cut around the nest and every food source, and corridors are carved
+//! wherever a food source cannot be reached from the nest, so every generated map
+//! can be foraged.
+
+use crate::{
+    map::{MapLayout, MapSize, Nest, ObstacleMap, Terrain},
+    *,
+};
+use bevy::math::Vec2;
+use rand::{rngs::StdRng, Rng, SeedableRng};
+use std::collections::VecDeque;
+
+#[derive(PartialEq, Clone, Copy, Debug)]
+pub enum MapGenKind {
+    /// Cellular automaton caves
+    Caves,
+    /// Recursive backtracker maze, braided into loops below full density
+    Maze,
+    /// Thresholded value noise
+    Rocks,
+    /// Random axis aligned rectangles
+    Rectangles,
+}
+
+impl MapGenKind {
+    pub const ALL: [MapGenKind; 4] = [
+        MapGenKind::Caves,
+        MapGenKind::Maze,
+        MapGenKind::Rocks,
+        MapGenKind::Rectangles,
+    ];
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            MapGenKind::Caves => "Caves",
+            MapGenKind::Maze => "Maze",
+            MapGenKind::Rocks => "Rocks",
+            MapGenKind::Rectangles => "Rectangles",
+        }
+    }
+
+    /// Name used on the command line
+    pub fn name(&self) -> &'static str {
+        match self {
+            MapGenKind::Caves => "caves",
+            MapGenKind::Maze => "maze",
+            MapGenKind::Rocks => "rocks",
+            MapGenKind::Rectangles => "rectangles",
+        }
+    }
+}
+
+impl std::str::FromStr for MapGenKind {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        MapGenKind::ALL
+            .into_iter()
+            .find(|k| k.name() == s)
+            .ok_or_else(|| format!("unknown generator: {}", s))
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct MapGenSettings {
+    pub kind: MapGenKind,
+    pub seed: u64,
+    /// Roughly the fraction of the map covered by walls. For mazes it is the share
+    /// of inner maze walls kept, 1.0 gives a perfect maze without loops
+    pub density: f32,
+    /// Feature size in world units: cave and rock size, maze corridor width,
+    /// typical rectangle side
+    pub scale: f32,
+}
+
+impl Default for MapGenSettings {
+    fn default() -> Self {
+        Self {
+            kind: MapGenKind::Caves,
+            seed: 0,
+            density: 0.45,
+            scale: MAPGEN_SCALE,
+        }
+    }
+}
+
+/// Walls of the obstacle grid, row 0 is the bottom of the map like `ObstacleMap::grid`
+struct WallGrid {
+    width: usize,
+    height: usize,
+    cells: Vec<bool>,
+}
+
+impl WallGrid {
+    fn new(width: usize, height: usize, is_wall: bool) -> Self {
+        Self {
+            width,
+            height,
+            cells: vec![is_wall; width * height],
+        }
+    }
+
+    fn get(&self, x: isize, y: isize) -> bool {
+        // Outside counts as wall, same as ObstacleMap
+        if x < 0 || y < 0 || x >= self.width as isize || y >= self.height as isize {
+            return true;
+        }
+        self.cells[y as usize * self.width + x as usize]
+    }
+
+    fn wall_neighbours(&self, x: usize, y: usize) -> usize {
+        let mut count = 0;
+        for dy in -1..=1 {
+            for dx in -1..=1 {
+                if (dx != 0 || dy != 0) && self.get(x as isize + dx, y as isize + dy) {
+                    count += 1;
+                }
+            }
+        }
+        count
+    }
+
+    /// One cellular automaton step, a cell becomes wall when `rule(is_wall, neighbours)`
+    fn step(&self, rule: impl Fn(bool, usize) -> bool) -> Self {
+        let mut next = Self::new(self.width, self.height, false);
+        for y in 0..self.height {
+            for x in 0..self.width {
+                next.cells[y * self.width + x] = rule(self.cells[y * self.width + x], self.wall_neighbours(x, y));
+            }
+        }
+        next
+    }
+
+    /// Scales a coarse grid up so each coarse cell covers `block` x `block` cells
+    fn upscale(&self, block: usize, width: usize, height: usize) -> Self {
+        let mut fine = Self::new(width, height, true);
+        for y in 0..height {
+            for x in 0..width {
+                fine.cells[y * width + x] = self.get((x / block) as isize, (y / block) as isize);
+            }
+        }
+        fine
+    }
+}
+
+/// Generates walls with the given settings, keeping the size, nest and food of `base`.
+/// Terrain, decay multipliers and dynamic walls of `base` are dropped.
+pub fn generate(base: &MapLayout, settings: &MapGenSettings) -> MapLayout {
+    let map_size = base.map_size;
+    let mut obstacle_map = ObstacleMap::new(map_size.width, map_size.height);
+    let mut rng = StdRng::seed_from_u64(settings.seed);
+    let (width, height) = (obstacle_map.width, obstacle_map.height);
+    let density = settings.density.clamp(0.0, 1.0);
+    let cells_per_unit = 1.0 / PH_UNIT_GRID_SIZE as f32;
+
+    let walls = match settings.kind {
+        MapGenKind::Caves => {
+            let block = ((settings.scale / 2.0 * cells_per_unit) as usize).max(1);
+            caves(width, height, block, density, &mut rng)
+        }
+        MapGenKind::Maze => {
+            let corridor = ((settings.scale * cells_per_unit) as usize).max(1);
+            maze(width, height, corridor, density, &mut rng)
+        }
+        MapGenKind::Rocks => rocks(width, height, 2.0 * settings.scale * cells_per_unit, density, &mut rng),
+        MapGenKind::Rectangles => rectangles(width, height, settings.scale * cells_per_unit, density, &mut rng),
+    };
+    for (cell, is_wall) in obstacle_map.grid.iter_mut().zip(walls.cells) {
+        if is_wall {
+            *cell = Terrain::Wall;
+        }
+    }
+
+    let nest = Vec2::new(base.nest.0, base.nest.1);
+    let food: Vec<Vec2> = base.food.iter().map(|f| Vec2::new(f.x, f.y)).collect();
+    connect(&mut obstacle_map, &map_size, nest, &food);
+
+    MapLayout::capture(&obstacle_map, map_size, &Nest(nest), base.food.clone())
+}
+
+fn caves(width: usize, height: usize, block: usize, density: f32, rng: &mut StdRng) -> WallGrid {
+    let (cw, ch) = (width.div_ceil(block), height.div_ceil(block));
+    let mut coarse = WallGrid::new(cw, ch, false);
+    for cell in coarse.cells.iter_mut() {
+        *cell = rng.gen::<f32>() < density;
+    }
+    // The usual 4-5 rule: walls survive with 4 wall neighbours, open cells fill with 5
+    for _ in 0..5 {
+        coarse = coarse.step(|is_wall, n| n >= 5 || (is_wall && n >= 4));
+    }
+    // Smooth the block corners at full resolution
+    let mut fine = coarse.upscale(block, width, height);
+    for _ in 0..2 {
+        fine = fine.step(|is_wall, n| n + is_wall as usize >= 5);
+    }
+    fine
+}
+
+fn maze(width: usize, height: usize, corridor: usize, density: f32, rng: &mut StdRng) -> WallGrid {
+    // Blocks of corridor width, maze cells sit on odd blocks with walls between them
+    let (bw, bh) = (width / corridor, height / corridor);
+    let (mw, mh) = (bw.saturating_sub(1) / 2, bh.saturating_sub(1) / 2);
+    let mut blocks = WallGrid::new(bw.max(1), bh.max(1), true);
+    if mw == 0 || mh == 0 {
+        return blocks.upscale(corridor, width, height);
+    }
+    let mut open = |bx: usize, by: usize| blocks.cells[by * bw + bx] = false;
+
+    // Iterative recursive backtracker
+    let mut visited = vec![false; mw * mh];
+    let start = (rng.gen_range(0..mw), rng.gen_range(0..mh));
+    let mut stack = vec![start];
+    visited[start.1 * mw + start.0] = true;
+    open(2 * start.0 + 1, 2 * start.1 + 1);
+    while let Some(&(x, y)) = stack.last() {
+        let unvisited: Vec<(usize, usize)> = [(0, 1), (1, 0), (0, -1), (-1, 0)]
+            .into_iter()
+            .map(|(dx, dy)| (x as isize + dx, y as isize + dy))
+            .filter(|&(nx, ny)| nx >= 0 && ny >= 0 && nx < mw as isize && ny < mh as isize)
+            .map(|(nx, ny)| (nx as usize, ny as usize))
+            .filter(|&(nx, ny)| !visited[ny * mw + nx])
+            .collect();
+        if unvisited.is_empty() {
+            stack.pop();
+            continue;
+        }
+        let (nx, ny) = unvisited[rng.gen_range(0..unvisited.len())];
+        visited[ny * mw + nx] = true;
+        open(x + nx + 1, y + ny + 1);
+        open(2 * nx + 1, 2 * ny + 1);
+        stack.push((nx, ny));
+    }
+
+    // Braid: knock out inner walls between two maze cells so trails get alternatives
+    for by in 1..2 * mh {
+        for bx in 1..2 * mw {
+            let is_between = (bx % 2 == 0) != (by % 2 == 0);
+            if is_between && rng.gen::<f32>() >= density {
+                blocks.cells[by * bw + bx] = false;
+            }
+        }
+    }
+    blocks.upscale(corridor, width, height)
+}
+
+/// Deterministic value in 0..1 for a lattice point
+fn lattice_value(seed: u64, octave: u64, x: i64, y: i64) -> f32 {
+    // splitmix64 over the packed coordinates
+    let mut z = seed
+        .wrapping_add(octave.wrapping_mul(0x9E37_79B9_7F4A_7C15))
+        .wrapping_add((x as u64).wrapping_mul(0xD1B5_4A32_D192_ED03))
+        .wrapping_add((y as u64).wrapping_mul(0xABC9_8388_FB8B_4C2F));
+    z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    z ^= z >> 31;
+    (z >> 40) as f32 / (1u64 << 24) as f32
+}
+
+fn value_noise(seed: u64, octave: u64, x: f32, y: f32) -> f32 {
+    let (x0, y0) = (x.floor(), y.floor());
+    let smooth = |t: f32| t * t * (3.0 - 2.0 * t);
+    let (tx, ty) = (smooth(x - x0), smooth(y - y0));
+    let v = |dx: i64, dy: i64| lattice_value(seed, octave, x0 as i64 + dx, y0 as i64 + dy);
+    let bottom = v(0, 0) + (v(1, 0) - v(0, 0)) * tx;
+    let top = v(0, 1) + (v(1, 1) - v(0, 1)) * tx;
+    bottom + (top - bottom) * ty
+}
+
+fn rocks(width: usize, height: usize, period: f32, density: f32, rng: &mut StdRng) -> WallGrid {
+    let seed = rng.gen::<u64>();
+    let period = period.max(1.0);
+    let mut values = Vec::with_capacity(width * height);
+    for y in 0..height {
+        for x in 0..width {
+            let (fx, fy) = (x as f32 / period, y as f32 / period);
+            // Two octaves, the second only roughens the outlines a bit
+            let v = value_noise(seed, 0, fx, fy) + 0.3 * value_noise(seed, 1, fx * 2.0, fy * 2.0);
+            values.push(v);
+        }
+    }
+
+    // Threshold at the quantile that makes `density` of the cells walls
+    let mut sorted = values.clone();
+    sorted.sort_by(f32::total_cmp);
+    let wall_count = (density * sorted.len() as f32) as usize;
+    let mut grid = WallGrid::new(width, height, false);
+    if wall_count == 0 {
+        return grid;
+    }
+    let threshold = sorted[sorted.len() - wall_count];
+    for (cell, v) in grid.cells.iter_mut().zip(values) {
+        *cell = v >= threshold;
+    }
+    grid
+}
+
+fn rectangles(width: usize, height: usize, side: f32, density: f32, rng: &mut StdRng) -> WallGrid {
+    let mut grid = WallGrid::new(width, height, false);
+    let target = (density * (width * height) as f32) as usize;
+    let (min_side, max_side) = ((side / 2.0).max(1.0) as usize, (side * 2.0).max(2.0) as usize);
+    let mut covered = 0;
+    // Overlaps add nothing, so give up eventually on dense settings
+    for _ in 0..MAPGEN_MAX_RECTANGLES {
+        if covered >= target {
+            break;
+        }
+        let (rw, rh) = (rng.gen_range(min_side..=max_side), rng.gen_range(min_side..=max_side));
+        let (x0, y0) = (rng.gen_range(0..width), rng.gen_range(0..height));
+        for y in y0..(y0 + rh).min(height) {
+            for x in x0..(x0 + rw).min(width) {
+                let cell = &mut grid.cells[y * width + x];
+                if !*cell {
+                    *cell = true;
+                    covered += 1;
+                }
+            }
+        }
+    }
+    grid
+}
+
+/// Clears the nest and food surroundings and carves corridors to unreachable food.
+/// Reachability uses cells with no wall within MAPGEN_CLEARANCE, so carved and
+/// existing passages are wide enough for ants to walk through
+fn connect(obstacle_map: &mut ObstacleMap, map_size: &MapSize, nest: Vec2, food: &[Vec2]) {
+    let (w, h) = (map_size.width, map_size.height);
+    for p in food.iter().chain(std::iter::once(&nest)) {
+        obstacle_map.set_obstacle(p.x, p.y, w, h, false, MAPGEN_CLEARING_RADIUS);
+    }
+
+    let (width, height) = (obstacle_map.width, obstacle_map.height);
+    let unit = PH_UNIT_GRID_SIZE as f32;
+    let cell_center = |idx: usize| {
+        Vec2::new(
+            (idx % width) as f32 * unit + unit / 2.0 - w / 2.0,
+            (idx / width) as f32 * unit + unit / 2.0 - h / 2.0,
+        )
+    };
+    let cell_of = |p: Vec2| {
+        let x = (((p.x + w / 2.0) / unit) as usize).min(width - 1);
+        let y = (((p.y + h / 2.0) / unit) as usize).min(height - 1);
+        y * width + x
+    };
+
+    // 0-1 BFS from the nest, stepping into a cell that is too tight costs 1
+    let tight: Vec<bool> = (0..width * height)
+        .map(|idx| {
+            let p = cell_center(idx);
+            obstacle_map.is_obstacle_in_radius(p.x, p.y, MAPGEN_CLEARANCE, w, h)
+        })
+        .collect();
+    let mut cost = vec![u32::MAX; width * height];
+    let mut parent = vec![usize::MAX; width * height];
+    let mut queue = VecDeque::new();
+    let start = cell_of(nest);
+    cost[start] = 0;
+    queue.push_back(start);
+    while let Some(idx) = queue.pop_front() {
+        let (x, y) = (idx % width, idx / width);
+        let neighbours = [
+            (x > 0).then(|| idx - 1),
+            (x + 1 < width).then(|| idx + 1),
+            (y > 0).then(|| idx - width),
+            (y + 1 < height).then(|| idx + width),
+        ];
+        for next in neighbours.into_iter().flatten() {
+            let step = tight[next] as u32;
+            if cost[idx] + step < cost[next] {
+                cost[next] = cost[idx] + step;
+                parent[next] = idx;
+                if step == 0 {
+                    queue.push_front(next);
+                } else {
+                    queue.push_back(next);
+                }
+            }
+        }
+    }
+
+    for p in food.iter() {
+        let mut idx = cell_of(*p);
+        if cost[idx] == 0 {
+            continue;
+        }
+        while idx != start && idx != usize::MAX {
+            if tight[idx] {
+                let c = cell_center(idx);
+                obstacle_map.set_obstacle(c.x, c.y, w, h, false, MAPGEN_CORRIDOR_RADIUS);
+            }
+            idx = parent[idx];
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::map::FoodSpec;
+
+    /// Cells reachable from `start` without coming closer than MAPGEN_CLEARANCE to a wall
+    fn reachable(obstacle_map: &ObstacleMap, map_size: &MapSize, start: Vec2) -> Vec<bool> {
+        let (w, h) = (map_size.width, map_size.height);
+        let (width, height) = (obstacle_map.width, obstacle_map.height);
+        let unit = PH_UNIT_GRID_SIZE as f32;
+        let passable = |idx: usize| {
+            let x = (idx % width) as f32 * unit + unit / 2.0 - w / 2.0;
+            let y = (idx / width) as f32 * unit + unit / 2.0 - h / 2.0;
+            !obstacle_map.is_obstacle_in_radius(x, y, MAPGEN_CLEARANCE, w, h)
+        };
+
+        let mut seen = vec![false; width * height];
+        let start = ((start.y + h / 2.0) / unit) as usize * width + ((start.x + w / 2.0) / unit) as usize;
+        let mut stack = vec![start];
+        seen[start] = true;
+        while let Some(idx) = stack.pop() {
+            let (x, y) = (idx % width, idx / width);
+            let neighbours = [
+                (x > 0).then(|| idx - 1),
+                (x + 1 < width).then(|| idx + 1),
+                (y > 0).then(|| idx - width),
+                (y + 1 < height).then(|| idx + width),
+            ];
+            for next in neighbours.into_iter().flatten() {
+                if !seen[next] && passable(next) {
+                    seen[next] = true;
+                    stack.push(next);
+                }
+            }
+        }
+        seen
+    }
+
+    #[test]
+    fn generated_maps_connect_nest_and_food() {
+        let mut base = MapLayout::new(MapSize::default());
+        base.food.push(FoodSpec::new(0.0, 0.0, FOOD_STORAGE));
+        base.food.push(FoodSpec::new(-700.0, -400.0, FOOD_STORAGE));
+
+        for kind in MapGenKind::ALL {
+            for density in [0.5, 0.9] {
+                for seed in 0..3 {
+                    let settings = MapGenSettings {
+                        kind,
+                        seed,
+                        density,
+                        ..Default::default()
+                    };
+                    let layout = generate(&base, &settings);
+                    let obstacle_map = layout.to_obstacle_map();
+                    let nest = Vec2::new(layout.nest.0, layout.nest.1);
+                    assert!(!obstacle_map.is_obstacle(nest.x, nest.y, layout.map_size.width, layout.map_size.height));
+
+                    let seen = reachable(&obstacle_map, &layout.map_size, nest);
+                    let (w, h) = (layout.map_size.width, layout.map_size.height);
+                    let unit = PH_UNIT_GRID_SIZE as f32;
+                    for food in layout.food.iter() {
+                        let idx = ((food.y + h / 2.0) / unit) as usize * obstacle_map.width
+                            + ((food.x + w / 2.0) / unit) as usize;
+                        assert!(
+                            seen[idx],
+                            "{:?} seed {} density {}: food at {:?} cut off from the nest",
+                            kind,
+                            seed,
+                            density,
+                            food.pos()
+                        );
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn generation_is_seeded() {
+        let base = MapLayout::new(MapSize::default());
+        for kind in MapGenKind::ALL {
+            let settings = MapGenSettings {
+                kind,
+                seed: 7,
+                ..Default::default()
+            };
+            assert_eq!(generate(&base, &settings).walls, generate(&base, &settings).walls);
+        }
+    }
+}
@@ -0,0 +1,790 @@
+//! Extraction of the trail network the ants have settled on.
+//! A pheromone layer (or the path visualisation grid) is thresholded, thinned to a
+//! one cell wide skeleton and traced into a graph: junctions and dead ends become
+//! nodes, the skeleton lines between them become edges with a length and a usage
+//! weight. The graph can be drawn over the map and exported as JSON or GraphML,
+//! together with the nest and food positions as terminals.
+
+use crate::{
+    ant::Food,
+    gui::{color_ramp, SimSettings},
+    map::{MapSize, Nest, ObstacleMap},
+    pathviz::PathVizGrid,
+    pheromone::Pheromones,
+    *,
+};
+use bevy::prelude::*;
+use bevy_egui::{egui, EguiContexts};
+use serde::Serialize;
+use std::collections::{HashMap, HashSet};
+
+pub struct NetworkPlugin;
+
+/// Grid the network is extracted from
+#[derive(Serialize, Clone, Copy, PartialEq, Debug)]
+pub enum NetworkSource {
+    ToFood,
+    ToHome,
+    /// Sum of both pheromone layers
+    Pheromones,
+    /// Recent ant positions from the path visualisation
+    Paths,
+}
+
+impl NetworkSource {
+    pub const ALL: [NetworkSource; 4] = [
+        NetworkSource::Pheromones,
+        NetworkSource::ToFood,
+        NetworkSource::ToHome,
+        NetworkSource::Paths,
+    ];
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            NetworkSource::ToFood => "To food ph",
+            NetworkSource::ToHome => "To home ph",
+            NetworkSource::Pheromones => "Both ph",
+            NetworkSource::Paths => "Ant paths",
+        }
+    }
+}
+
+/// Dense copy of a layer in image coordinates, row 0 is the top of the map
+pub struct LayerGrid {
+    pub width: usize,
+    pub height: usize,
+    pub values: Vec<f32>,
+    map_size: MapSize,
+}
+
+impl LayerGrid {
+    pub fn new(map_size: MapSize) -> Self {
+        let (width, height) = (
+            map_size.width as usize / PH_UNIT_GRID_SIZE,
+            map_size.height as usize / PH_UNIT_GRID_SIZE,
+        );
+        Self {
+            width,
+            height,
+            values: vec![0.0; width * height],
+            map_size,
+        }
+    }
+
+    /// Reads a layer the way the pheromone and path images are drawn
+    pub fn from_source(
+        source: NetworkSource,
+        pheromones: &Pheromones,
+        viz_grid: &PathVizGrid,
+        obstacle_map: &ObstacleMap,
+        map_size: MapSize,
+    ) -> Self {
+        let mut grid = Self::new(map_size);
+        match source {
+            NetworkSource::ToFood => grid.add_ph_keys(pheromones.to_food.get_signals()),
+            NetworkSource::ToHome => grid.add_ph_keys(pheromones.to_home.get_signals()),
+            NetworkSource::Pheromones => {
+                grid.add_ph_keys(pheromones.to_food.get_signals());
+                grid.add_ph_keys(pheromones.to_home.get_signals());
+            }
+            NetworkSource::Paths => {
+                grid.add_image_keys(viz_grid.dg_food.get_values());
+                grid.add_image_keys(viz_grid.dg_home.get_values());
+            }
+        }
+
+        // Trails never go through walls, this also keeps the nest seed value from
+        // leaking past the border
+        for row in 0..grid.height {
+            for col in 0..grid.width {
+                let pos = grid.world_pos(col, row);
+                if obstacle_map.is_obstacle(pos.x, pos.y, map_size.width, map_size.height) {
+                    grid.values[row * grid.width + col] = 0.0;
+                }
+            }
+        }
+        grid
+    }
+
+    fn add_ph_keys(&mut self, map: &HashMap<(i32, i32), f32>) {
+        let unit = PH_UNIT_GRID_SIZE as i32;
+        let (half_w, half_h) = (self.map_size.width as i32 / 2, self.map_size.height as i32 / 2);
+        for (&(kx, ky), &v) in map.iter() {
+            let col = (kx * unit + half_w) / unit;
+            let row = (half_h - ky * unit) / unit;
+            self.add(col, row, v);
+        }
+    }
+
+    fn add_image_keys(&mut self, map: &HashMap<(i32, i32), f32>) {
+        for (&(col, row), &v) in map.iter() {
+            self.add(col, row, v);
+        }
+    }
+
+    fn add(&mut self, col: i32, row: i32, v: f32) {
+        if col < 0 || row < 0 || col as usize >= self.width || row as usize >= self.height {
+            return;
+        }
+        self.values[row as usize * self.width + col as usize] += v;
+    }
+
+    /// Centre of a cell in world coordinates
+    pub fn world_pos(&self, col: usize, row: usize) -> Vec2 {
+        let unit = PH_UNIT_GRID_SIZE as f32;
+        Vec2::new(
+            col as f32 * unit - self.map_size.width / 2.0 + unit / 2.0,
+            self.map_size.height / 2.0 - row as f32 * unit - unit / 2.0,
+        )
+    }
+}
+
+#[derive(Serialize, Clone)]
+pub struct NetworkNode {
+    pub id: usize,
+    pub x: f32,
+    pub y: f32,
+    pub degree: usize,
+}
+
+#[derive(Serialize, Clone)]
+pub struct NetworkEdge {
+    pub from: usize,
+    pub to: usize,
+    /// World units along the skeleton
+    pub length: f32,
+    /// Mean layer value along the edge
+    pub usage: f32,
+    pub points: Vec<(f32, f32)>,
+}
+
+#[derive(Serialize, Clone)]
+pub struct TrailNetwork {
+    pub source: NetworkSource,
+    pub threshold: f32,
+    pub nodes: Vec<NetworkNode>,
+    pub edges: Vec<NetworkEdge>,
+    /// Terminals for comparing against spanning or Steiner trees
+    pub nest: (f32, f32),
+    pub food: Vec<(f32, f32)>,
+}
+
+const NEIGHBOURS: [(i32, i32); 8] = [(0, -1), (1, -1), (1, 0), (1, 1), (0, 1), (-1, 1), (-1, 0), (-1, -1)];
+
+/// Zhang-Suen thinning of a binary image, the border is treated as background
+pub fn skeletonize(mask: &mut [bool], width: usize, height: usize) {
+    let at = |m: &[bool], x: usize, y: usize, (dx, dy): (i32, i32)| -> bool {
+        m[(y as i32 + dy) as usize * width + (x as i32 + dx) as usize]
+    };
+    loop {
+        let mut is_changed = false;
+        for step in 0..2 {
+            let mut remove = Vec::new();
+            for y in 1..height.saturating_sub(1) {
+                for x in 1..width.saturating_sub(1) {
+                    if !mask[y * width + x] {
+                        continue;
+                    }
+                    // P2..P9 clockwise from north
+                    let p = NEIGHBOURS.map(|d| at(mask, x, y, d));
+                    let count = p.iter().filter(|v| **v).count();
+                    let transitions = (0..8).filter(|i| !p[*i] && p[(i + 1) % 8]).count();
+                    let (p2, p4, p6, p8) = (p[0], p[2], p[4], p[6]);
+                    let is_removable = if step == 0 {
+                        !(p4 && p6 && (p2 || p8))
+                    } else {
+                        !(p2 && p8 && (p4 || p6))
+                    };
+                    if (2..=6).contains(&count) && transitions == 1 && is_removable {
+                        remove.push(y * width + x);
+                    }
+                }
+            }
+            is_changed |= !remove.is_empty();
+            for idx in remove {
+                mask[idx] = false;
+            }
+        }
+        if !is_changed {
+            break;
+        }
+    }
+}
+
+impl TrailNetwork {
+    pub fn extract(grid: &LayerGrid, source: NetworkSource, threshold: f32, min_spur_length: f32) -> Self {
+        let (w, h) = (grid.width, grid.height);
+        let mut mask: Vec<bool> = grid.values.iter().map(|v| *v >= threshold).collect();
+        skeletonize(&mut mask, w, h);
+
+        let neighbours = |idx: usize| {
+            let (x, y) = ((idx % w) as i32, (idx / w) as i32);
+            NEIGHBOURS.iter().filter_map(move |(dx, dy)| {
+                let (nx, ny) = (x + dx, y + dy);
+                (nx >= 0 && ny >= 0 && (nx as usize) < w && (ny as usize) < h).then(|| ny as usize * w + nx as usize)
+            })
+        };
+        let pixels: Vec<usize> = (0..mask.len()).filter(|i| mask[*i]).collect();
+        let degree = |idx: usize| neighbours(idx).filter(|n| mask[*n]).count();
+
+        // Dead ends and junctions, touching junction cells form a single node
+        let mut cluster_of: HashMap<usize, usize> = HashMap::new();
+        let mut clusters: Vec<Vec<usize>> = Vec::new();
+        for &p in pixels.iter() {
+            if degree(p) == 2 || cluster_of.contains_key(&p) {
+                continue;
+            }
+            let id = clusters.len();
+            let mut members = vec![p];
+            cluster_of.insert(p, id);
+            let mut i = 0;
+            while i < members.len() {
+                for n in neighbours(members[i]) {
+                    if mask[n] && degree(n) != 2 && !cluster_of.contains_key(&n) {
+                        cluster_of.insert(n, id);
+                        members.push(n);
+                    }
+                }
+                i += 1;
+            }
+            clusters.push(members);
+        }
+
+        let mut edges: Vec<(usize, usize, Vec<usize>)> = Vec::new();
+        let mut visited: HashSet<usize> = HashSet::new();
+        let mut direct: HashSet<(usize, usize)> = HashSet::new();
+        let mut trace_from = |start: usize,
+                              cluster_of: &HashMap<usize, usize>,
+                              visited: &mut HashSet<usize>,
+                              edges: &mut Vec<(usize, usize, Vec<usize>)>| {
+            let from = cluster_of[&start];
+            for first in neighbours(start).filter(|n| mask[*n]) {
+                if let Some(&to) = cluster_of.get(&first) {
+                    // Two nodes right next to each other
+                    if to != from && direct.insert((from.min(to), from.max(to))) {
+                        edges.push((from, to, vec![start, first]));
+                    }
+                    continue;
+                }
+                if visited.contains(&first) {
+                    continue;
+                }
+                let mut path = vec![start, first];
+                visited.insert(first);
+                let end = loop {
+                    let cur = *path.last().unwrap();
+                    let prev = path[path.len() - 2];
+                    let next_nodes: Vec<usize> = neighbours(cur)
+                        .filter(|n| *n != prev && cluster_of.contains_key(n))
+                        .filter(|n| path.len() > 2 || cluster_of[n] != from)
+                        .collect();
+                    if let Some(&n) = next_nodes.first() {
+                        path.push(n);
+                        break Some(cluster_of[&n]);
+                    }
+                    let next = neighbours(cur)
+                        .find(|n| mask[*n] && *n != prev && !cluster_of.contains_key(n) && !visited.contains(n));
+                    match next {
+                        Some(n) => {
+                            visited.insert(n);
+                            path.push(n);
+                        }
+                        None => break None,
+                    }
+                };
+                if let Some(to) = end {
+                    edges.push((from, to, path));
+                }
+            }
+        };
+
+        for members in clusters.iter() {
+            for &p in members {
+                trace_from(p, &cluster_of, &mut visited, &mut edges);
+            }
+        }
+        // Closed loops without any junction get a node of their own
+        for &p in pixels.iter() {
+            if cluster_of.contains_key(&p) || visited.contains(&p) {
+                continue;
+            }
+            cluster_of.insert(p, clusters.len());
+            clusters.push(vec![p]);
+            visited.insert(p);
+            trace_from(p, &cluster_of, &mut visited, &mut edges);
+        }
+
+        let cell_pos = |idx: usize| grid.world_pos(idx % w, idx / w);
+        let mut graph = Graph {
+            nodes: clusters
+                .iter()
+                .map(|members| members.iter().map(|i| cell_pos(*i)).sum::<Vec2>() / members.len() as f32)
+                .collect(),
+            edges: edges
+                .into_iter()
+                .map(|(from, to, path)| {
+                    let points: Vec<Vec2> = path.iter().map(|i| cell_pos(*i)).collect();
+                    let length = points.windows(2).map(|p| p[0].distance(p[1])).sum();
+                    let usage = path.iter().map(|i| grid.values[*i]).sum::<f32>() / path.len() as f32;
+                    Some(GraphEdge { from, to, length, usage, points })
+                })
+                .collect(),
+        };
+        graph.contract();
+        graph.prune_spurs(min_spur_length);
+        graph.contract();
+        graph.into_network(source, threshold)
+    }
+
+    pub fn total_length(&self) -> f32 {
+        self.edges.iter().map(|e| e.length).sum()
+    }
+
+    pub fn to_graphml(&self) -> String {
+        let mut out = String::new();
+        out.push_str("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+        out.push_str("<graphml xmlns=\"http://graphml.graphdrawing.org/xmlns\">\n");
+        for (id, target, kind) in [
+            ("x", "node", "float"),
+            ("y", "node", "float"),
+            ("terminal", "node", "string"),
+            ("length", "edge", "float"),
+            ("usage", "edge", "float"),
+        ] {
+            out.push_str(&format!(
+                "  <key id=\"{0}\" for=\"{1}\" attr.name=\"{0}\" attr.type=\"{2}\"/>\n",
+                id, target, kind
+            ));
+        }
+        out.push_str("  <graph id=\"trails\" edgedefault=\"undirected\">\n");
+        for node in self.nodes.iter() {
+            out.push_str(&format!(
+                "    <node id=\"n{}\"><data key=\"x\">{}</data><data key=\"y\">{}</data></node>\n",
+                node.id, node.x, node.y
+            ));
+        }
+        // Terminals are not connected, they mark where a spanning tree has to reach
+        let terminals = std::iter::once(("nest", self.nest)).chain(self.food.iter().map(|f| ("food", *f)));
+        for (i, (kind, (x, y))) in terminals.enumerate() {
+            out.push_str(&format!(
+                "    <node id=\"t{}\"><data key=\"x\">{}</data><data key=\"y\">{}</data><data key=\"terminal\">{}</data></node>\n",
+                i, x, y, kind
+            ));
+        }
+        for (i, edge) in self.edges.iter().enumerate() {
+            out.push_str(&format!(
+                "    <edge id=\"e{}\" source=\"n{}\" target=\"n{}\"><data key=\"length\">{}</data><data key=\"usage\">{}</data></edge>\n",
+                i, edge.from, edge.to, edge.length, edge.usage
+            ));
+        }
+        out.push_str("  </graph>\n</graphml>\n");
+        out
+    }
+
+    pub fn save_json(&self, path: &str) -> Result<(), String> {
+        let json = serde_json::to_string(self).map_err(|e| e.to_string())?;
+        std::fs::write(path, json).map_err(|e| format!("{}: {}", path, e))
+    }
+
+    pub fn save_graphml(&self, path: &str) -> Result<(), String> {
+        std::fs::write(path, self.to_graphml()).map_err(|e| format!("{}: {}", path, e))
+    }
+}
+
+struct GraphEdge {
+    from: usize,
+    to: usize,
+    length: f32,
+    usage: f32,
+    points: Vec<Vec2>,
+}
+
+/// Working copy of the traced graph, removed edges are left as None
+struct Graph {
+    nodes: Vec<Vec2>,
+    edges: Vec<Option<GraphEdge>>,
+}
+
+impl Graph {
+    /// Edges at each node, a loop is listed twice at its node
+    fn adjacency(&self) -> Vec<Vec<usize>> {
+        let mut adjacency = vec![vec![]; self.nodes.len()];
+        for (i, edge) in self.edges.iter().enumerate() {
+            if let Some(e) = edge {
+                adjacency[e.from].push(i);
+                adjacency[e.to].push(i);
+            }
+        }
+        adjacency
+    }
+
+    /// Merges the two edges of nodes that only continue a line. Thinning leaves
+    /// small stair steps that look like junctions, these end up here as well
+    fn contract(&mut self) {
+        let mut adjacency = self.adjacency();
+        for node in 0..self.nodes.len() {
+            let [a, b] = adjacency[node][..] else {
+                continue;
+            };
+            // A loop has both its ends here, merging it would close it on itself
+            let is_loop = |i: usize| self.edges[i].as_ref().is_some_and(|e| e.from == e.to);
+            if is_loop(a) || is_loop(b) {
+                continue;
+            }
+            let mut ea = self.edges[a].take().unwrap();
+            let mut eb = self.edges[b].take().unwrap();
+            // Orient as ea: x -> node, eb: node -> y
+            if ea.to != node {
+                std::mem::swap(&mut ea.from, &mut ea.to);
+                ea.points.reverse();
+            }
+            if eb.from != node {
+                std::mem::swap(&mut eb.from, &mut eb.to);
+                eb.points.reverse();
+            }
+            let length = ea.length + eb.length;
+            let usage = if length > 0.0 {
+                (ea.usage * ea.length + eb.usage * eb.length) / length
+            } else {
+                ea.usage.max(eb.usage)
+            };
+            ea.points.extend(eb.points.into_iter().skip(1));
+            // `a` now ends where `b` did
+            adjacency[node].clear();
+            for e in adjacency[eb.to].iter_mut().filter(|e| **e == b) {
+                *e = a;
+            }
+            self.edges[a] = Some(GraphEdge {
+                from: ea.from,
+                to: eb.to,
+                length,
+                usage,
+                points: ea.points,
+            });
+        }
+    }
+
+    /// Drops short dead end branches, thinning grows one from every bump of a trail
+    fn prune_spurs(&mut self, min_length: f32) {
+        let degrees: Vec<usize> = self.adjacency().iter().map(|edges| edges.len()).collect();
+        for edge in self.edges.iter_mut() {
+            let is_spur = edge.as_ref().is_some_and(|e| {
+                let is_dead_end = degrees[e.from] == 1 || degrees[e.to] == 1;
+                let is_isolated = degrees[e.from] == 1 && degrees[e.to] == 1;
+                is_dead_end && !is_isolated && e.length < min_length
+            });
+            if is_spur {
+                *edge = None;
+            }
+        }
+    }
+
+    fn into_network(self, source: NetworkSource, threshold: f32) -> TrailNetwork {
+        let edges: Vec<GraphEdge> = self.edges.into_iter().flatten().collect();
+        let mut degrees = vec![0; self.nodes.len()];
+        for e in edges.iter() {
+            degrees[e.from] += 1;
+            degrees[e.to] += 1;
+        }
+        // Compact ids, nodes without edges are gone
+        let mut ids = vec![usize::MAX; self.nodes.len()];
+        let mut nodes = Vec::new();
+        for (i, pos) in self.nodes.iter().enumerate() {
+            if degrees[i] == 0 {
+                continue;
+            }
+            ids[i] = nodes.len();
+            nodes.push(NetworkNode {
+                id: nodes.len(),
+                x: pos.x,
+                y: pos.y,
+                degree: degrees[i],
+            });
+        }
+        TrailNetwork {
+            source,
+            threshold,
+            nodes,
+            edges: edges
+                .into_iter()
+                .map(|e| NetworkEdge {
+                    from: ids[e.from],
+                    to: ids[e.to],
+                    length: e.length,
+                    usage: e.usage,
+                    points: e.points.iter().map(|p| (p.x, p.y)).collect(),
+                })
+                .collect(),
+            nest: (0.0, 0.0),
+            food: Vec::new(),
+        }
+    }
+}
+
+#[derive(Resource)]
+pub struct TrailNetworkState {
+    pub network: Option<TrailNetwork>,
+    pub source: NetworkSource,
+    pub threshold: f32,
+    pub is_show: bool,
+    /// Re-extract every NETWORK_UPDATE_SECS
+    pub is_auto_update: bool,
+    is_extract_requested: bool,
+    since_update_secs: f32,
+}
+
+impl Default for TrailNetworkState {
+    fn default() -> Self {
+        Self {
+            network: None,
+            source: NetworkSource::Pheromones,
+            threshold: NETWORK_THRESHOLD,
+            is_show: true,
+            is_auto_update: false,
+            is_extract_requested: false,
+            since_update_secs: 0.0,
+        }
+    }
+}
+
+impl Plugin for NetworkPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(TrailNetworkState::default())
+            .add_systems(Update, (update_network, draw_network, network_ui));
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn update_network(
+    mut state: ResMut<TrailNetworkState>,
+    time: Res<Time>,
+    pheromones: Res<Pheromones>,
+    viz_grid: Res<PathVizGrid>,
+    obstacle_map: Res<ObstacleMap>,
+    map_size: Res<MapSize>,
+    nest: Res<Nest>,
+    food_query: Query<&Transform, With<Food>>,
+) {
+    state.since_update_secs += time.delta_seconds();
+    let is_due = state.is_auto_update && state.since_update_secs >= NETWORK_UPDATE_SECS;
+    if !state.is_extract_requested && !is_due {
+        return;
+    }
+    state.is_extract_requested = false;
+    state.since_update_secs = 0.0;
+
+    let grid = LayerGrid::from_source(state.source, &pheromones, &viz_grid, &obstacle_map, *map_size);
+    let mut network = TrailNetwork::extract(&grid, state.source, state.threshold, NETWORK_MIN_SPUR_LENGTH);
+    network.nest = (nest.0.x, nest.0.y);
+    network.food = food_query
+        .iter()
+        .map(|tr| (tr.translation.x, tr.translation.y))
+        .collect();
+    state.network = Some(network);
+}
+
+fn draw_network(mut gizmos: Gizmos, state: Res<TrailNetworkState>) {
+    let Some(network) = state.network.as_ref().filter(|_| state.is_show) else {
+        return;
+    };
+
+    let max_usage = network.edges.iter().map(|e| e.usage).fold(0.0, f32::max);
+    for edge in network.edges.iter() {
+        let color = color_ramp(if max_usage > 0.0 { edge.usage / max_usage } else { 0.0 });
+        for p in edge.points.windows(2) {
+            gizmos.line_2d(Vec2::new(p[0].0, p[0].1), Vec2::new(p[1].0, p[1].1), color);
+        }
+    }
+    for node in network.nodes.iter() {
+        let color = if node.degree == 1 { Color::YELLOW } else { Color::WHITE };
+        gizmos.circle_2d(Vec2::new(node.x, node.y), 2.0 + node.degree as f32, color);
+    }
+}
+
+fn network_ui(mut contexts: EguiContexts, mut state: ResMut<TrailNetworkState>, settings: Res<SimSettings>) {
+    if !settings.is_show_menu {
+        return;
+    }
+
+    let ctx = contexts.ctx_mut();
+    egui::Window::new("Trail Network")
+        .default_pos(egui::pos2(W - 340.0, H - 420.0))
+        .default_open(false)
+        .show(ctx, |ui| {
+            egui::ComboBox::from_label("Source")
+                .selected_text(state.source.label())
+                .show_ui(ui, |ui| {
+                    for source in NetworkSource::ALL {
+                        ui.selectable_value(&mut state.source, source, source.label());
+                    }
+                });
+            ui.add(
+                egui::Slider::new(&mut state.threshold, 1.0..=1000.0)
+                    .logarithmic(true)
+                    .text("Threshold"),
+            );
+            ui.horizontal(|ui| {
+                if ui.button("Extract").clicked() {
+                    state.is_extract_requested = true;
+                }
+                ui.checkbox(&mut state.is_auto_update, "Auto");
+                ui.checkbox(&mut state.is_show, "Show");
+            });
+
+            let Some(network) = &state.network else {
+                return;
+            };
+            ui.label(format!(
+                "{} nodes, {} edges, length {:.0}",
+                network.nodes.len(),
+                network.edges.len(),
+                network.total_length()
+            ));
+            ui.horizontal(|ui| {
+                if ui.button("Export JSON").clicked() {
+                    match network.save_json(TRAIL_NETWORK_JSON_FILE) {
+                        Ok(_) => println!("Saved trail network to {}", TRAIL_NETWORK_JSON_FILE),
+                        Err(e) => eprintln!("Failed to save trail network: {}", e),
+                    }
+                }
+                if ui.button("Export GraphML").clicked() {
+                    match network.save_graphml(TRAIL_NETWORK_GRAPHML_FILE) {
+                        Ok(_) => println!("Saved trail network to {}", TRAIL_NETWORK_GRAPHML_FILE),
+                        Err(e) => eprintln!("Failed to save trail network: {}", e),
+                    }
+                }
+            });
+        });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn mask_from(rows: &[&str]) -> (Vec<bool>, usize, usize) {
+        let mask = rows.iter().flat_map(|r| r.chars().map(|c| c == '#')).collect();
+        (mask, rows[0].len(), rows.len())
+    }
+
+    /// Number of 8-connected groups of set pixels
+    fn components(mask: &[bool], width: usize, height: usize) -> usize {
+        let mut seen = vec![false; mask.len()];
+        let mut count = 0;
+        for start in 0..mask.len() {
+            if !mask[start] || seen[start] {
+                continue;
+            }
+            count += 1;
+            let mut stack = vec![start];
+            seen[start] = true;
+            while let Some(idx) = stack.pop() {
+                let (x, y) = ((idx % width) as i32, (idx / width) as i32);
+                for (dx, dy) in NEIGHBOURS {
+                    let (nx, ny) = (x + dx, y + dy);
+                    if nx < 0 || ny < 0 || nx as usize >= width || ny as usize >= height {
+                        continue;
+                    }
+                    let n = ny as usize * width + nx as usize;
+                    if mask[n] && !seen[n] {
+                        seen[n] = true;
+                        stack.push(n);
+                    }
+                }
+            }
+        }
+        count
+    }
+
+    #[test]
+    fn skeletonize_thins_a_bar_to_a_line() {
+        let (mut mask, w, h) = mask_from(&[
+            "......................",
+            ".####################.",
+            ".####################.",
+            ".####################.",
+            ".####################.",
+            ".####################.",
+            "......................",
+        ]);
+        skeletonize(&mut mask, w, h);
+        // Away from the ends every column keeps a single pixel
+        for x in 5..w - 5 {
+            let column = (0..h).filter(|y| mask[y * w + x]).count();
+            assert_eq!(column, 1, "column {}", x);
+        }
+        assert_eq!(components(&mask, w, h), 1);
+    }
+
+    #[test]
+    fn skeletonize_keeps_thin_lines_and_connectivity() {
+        let (mut mask, w, h) = mask_from(&[
+            "............",
+            ".#######....",
+            ".......#....",
+            ".......#....",
+            ".......####.",
+            "............",
+        ]);
+        let before = mask.clone();
+        skeletonize(&mut mask, w, h);
+        assert_eq!(mask, before);
+    }
+
+    #[test]
+    fn skeletonize_keeps_thick_shapes_connected() {
+        let (mut mask, w, h) = mask_from(&[
+            "..............",
+            ".####.........",
+            ".####.........",
+            ".####.........",
+            ".############.",
+            ".############.",
+            ".############.",
+            "..............",
+        ]);
+        skeletonize(&mut mask, w, h);
+        assert!(mask.iter().any(|v| *v));
+        assert_eq!(components(&mask, w, h), 1);
+    }
+
+    fn graph(nodes: usize, edges: &[(usize, usize, f32)]) -> Graph {
+        let nodes: Vec<Vec2> = (0..nodes).map(|i| Vec2::new(i as f32, 0.0)).collect();
+        let edges = edges
+            .iter()
+            .map(|&(from, to, length)| {
+                Some(GraphEdge {
+                    from,
+                    to,
+                    length,
+                    usage: 1.0,
+                    points: vec![nodes[from], nodes[to]],
+                })
+            })
+            .collect();
+        Graph { nodes, edges }
+    }
+
+    fn remaining(graph: &Graph) -> Vec<(usize, usize, f32)> {
+        graph.edges.iter().flatten().map(|e| (e.from, e.to, e.length)).collect()
+    }
+
+    #[test]
+    fn contract_merges_line_continuations() {
+        let mut g = graph(4, &[(0, 1, 1.0), (1, 2, 2.0), (2, 3, 3.0)]);
+        g.contract();
+        assert_eq!(remaining(&g), vec![(0, 3, 6.0)]);
+    }
+
+    #[test]
+    fn contract_keeps_loops_separate() {
+        // Chain into a node that also has a loop, only the chain is merged
+        let mut g = graph(3, &[(0, 1, 1.0), (1, 2, 1.0), (2, 2, 5.0)]);
+        g.contract();
+        assert_eq!(remaining(&g), vec![(0, 2, 2.0), (2, 2, 5.0)]);
+
+        // Two parallel edges close into a single loop, which is not merged any further
+        let mut g = graph(2, &[(0, 1, 1.0), (1, 0, 2.0)]);
+        g.contract();
+        assert_eq!(remaining(&g), vec![(1, 1, 3.0)]);
+    }
+}
@@ -0,0 +1,268 @@
+//! Long-term occupancy: ticks spent by ants in each cell over the whole run, split
+//! by task. Unlike `PathVizGrid` nothing decays, so the layer shows where the colony
+//! spent its effort since the last reset. It can be exported as a png and as a csv
+//! with the raw counts.
+
+use crate::{
+    ant::{Ant, AntTask, CurrentTask},
+    gui::{run_if_not_paused, ResetSimEvent, SimSettings},
+    map::MapSize,
+    pathviz::viz_key,
+    utils::save_png,
+    *,
+};
+use bevy::{
+    prelude::*,
+    render::render_resource::{Extent3d, TextureDimension, TextureFormat},
+    time::common_conditions::on_timer,
+};
+use bevy_egui::{egui, EguiContexts};
+use std::{fmt::Write, time::Duration};
+
+pub struct OccupancyPlugin;
+
+/// Which counts the layer and the png show
+#[derive(PartialEq, Clone, Copy, Debug)]
+pub enum OccupancyView {
+    Both,
+    FindFood,
+    FindHome,
+}
+
+/// Visit counts in image coordinates, row 0 is the top of the map
+#[derive(Resource)]
+pub struct Occupancy {
+    pub width: usize,
+    pub height: usize,
+    pub find_food: Vec<u32>,
+    pub find_home: Vec<u32>,
+    pub ticks: u64,
+    pub view: OccupancyView,
+    pub is_show: bool,
+}
+
+#[derive(Component)]
+struct OccupancyImageRender;
+
+impl Occupancy {
+    pub fn new(map_size: &MapSize) -> Self {
+        let (width, height) = (
+            map_size.width as usize / PH_UNIT_GRID_SIZE,
+            map_size.height as usize / PH_UNIT_GRID_SIZE,
+        );
+        Self {
+            width,
+            height,
+            find_food: vec![0; width * height],
+            find_home: vec![0; width * height],
+            ticks: 0,
+            view: OccupancyView::Both,
+            is_show: false,
+        }
+    }
+
+    /// Drops the counts, keeps the view settings
+    pub fn clear(&mut self, map_size: &MapSize) {
+        *self = Self {
+            view: self.view,
+            is_show: self.is_show,
+            ..Self::new(map_size)
+        };
+    }
+
+    pub fn add(&mut self, pos: Vec2, task: AntTask, map_size: &MapSize) {
+        let (col, row) = viz_key(pos, map_size.width, map_size.height);
+        if col < 0 || row < 0 || col as usize >= self.width || row as usize >= self.height {
+            return;
+        }
+        let idx = row as usize * self.width + col as usize;
+        match task {
+            AntTask::FindFood => self.find_food[idx] += 1,
+            AntTask::FindHome => self.find_home[idx] += 1,
+        }
+    }
+
+    /// Log scaled image, food seekers in blue and home seekers in green like the ant tint
+    pub fn to_rgba(&self, view: OccupancyView) -> Vec<u8> {
+        let (show_food, show_home) = match view {
+            OccupancyView::Both => (true, true),
+            OccupancyView::FindFood => (true, false),
+            OccupancyView::FindHome => (false, true),
+        };
+        let max = self
+            .find_food
+            .iter()
+            .filter(|_| show_food)
+            .chain(self.find_home.iter().filter(|_| show_home))
+            .copied()
+            .max()
+            .unwrap_or(0);
+        let log_max = (max as f32).ln_1p().max(f32::EPSILON);
+        let scale = |c: u32| (c as f32).ln_1p() / log_max;
+
+        let mut bytes = vec![0; self.width * self.height * 4];
+        for (i, px) in bytes.chunks_exact_mut(4).enumerate() {
+            let food = if show_food { scale(self.find_food[i]) } else { 0.0 };
+            let home = if show_home { scale(self.find_home[i]) } else { 0.0 };
+            if food <= 0.0 && home <= 0.0 {
+                continue;
+            }
+            px[0] = (40.0 * food.max(home)) as u8;
+            px[1] = (255.0 * home).max(80.0 * food) as u8;
+            px[2] = (255.0 * food) as u8;
+            px[3] = (255.0 * food.max(home)) as u8;
+        }
+        bytes
+    }
+
+    /// One line per cell with its centre in world coordinates and both counts
+    pub fn to_csv(&self, map_size: &MapSize) -> String {
+        let unit = PH_UNIT_GRID_SIZE as f32;
+        let mut out = String::from("col,row,x,y,find_food,find_home\n");
+        for row in 0..self.height {
+            for col in 0..self.width {
+                let idx = row * self.width + col;
+                let x = col as f32 * unit - map_size.width / 2.0 + unit / 2.0;
+                let y = map_size.height / 2.0 - row as f32 * unit - unit / 2.0;
+                let _ = writeln!(
+                    out,
+                    "{},{},{},{},{},{}",
+                    col, row, x, y, self.find_food[idx], self.find_home[idx]
+                );
+            }
+        }
+        out
+    }
+
+    pub fn save_png(&self, path: &str) -> Result<(), String> {
+        save_png(path, self.width, self.height, self.to_rgba(self.view))
+    }
+
+    pub fn save_csv(&self, path: &str, map_size: &MapSize) -> Result<(), String> {
+        std::fs::write(path, self.to_csv(map_size)).map_err(|e| format!("{}: {}", path, e))
+    }
+}
+
+impl Plugin for OccupancyPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(Occupancy::new(&MapSize::default()))
+            .add_systems(Startup, setup)
+            .add_systems(Update, (reset_occupancy, update_occupancy.run_if(run_if_not_paused)).chain())
+            .add_systems(
+                Update,
+                update_occupancy_image.run_if(on_timer(Duration::from_secs_f32(OCCUPANCY_IMG_UPDATE_SECS))),
+            )
+            .add_systems(Update, occupancy_ui);
+    }
+}
+
+fn setup(mut commands: Commands) {
+    commands.spawn((
+        SpriteBundle {
+            transform: Transform::from_xyz(0.0, 0.0, 1.5).with_scale(Vec3::splat(PH_UNIT_GRID_SIZE as f32)),
+            visibility: Visibility::Hidden,
+            ..Default::default()
+        },
+        OccupancyImageRender,
+    ));
+}
+
+fn reset_occupancy(
+    mut events: EventReader<ResetSimEvent>,
+    mut occupancy: ResMut<Occupancy>,
+    map_size: Res<MapSize>,
+) {
+    let is_resized = occupancy.width != map_size.width as usize / PH_UNIT_GRID_SIZE
+        || occupancy.height != map_size.height as usize / PH_UNIT_GRID_SIZE;
+    if events.iter().count() > 0 || is_resized {
+        occupancy.clear(&map_size);
+    }
+}
+
+fn update_occupancy(
+    mut occupancy: ResMut<Occupancy>,
+    map_size: Res<MapSize>,
+    ant_query: Query<(&Transform, &CurrentTask), With<Ant>>,
+) {
+    occupancy.ticks += 1;
+    for (transform, task) in ant_query.iter() {
+        occupancy.add(transform.translation.truncate(), task.0, &map_size);
+    }
+}
+
+fn update_occupancy_image(
+    mut textures: ResMut<Assets<Image>>,
+    occupancy: Res<Occupancy>,
+    mut query: Query<(&mut Handle<Image>, &mut Visibility), With<OccupancyImageRender>>,
+) {
+    let (mut img_handle, mut visibility) = query.single_mut();
+    if !occupancy.is_show {
+        *visibility = Visibility::Hidden;
+        return;
+    }
+    *visibility = Visibility::Visible;
+
+    let img = Image::new(
+        Extent3d {
+            width: occupancy.width as u32,
+            height: occupancy.height as u32,
+            ..Default::default()
+        },
+        TextureDimension::D2,
+        occupancy.to_rgba(occupancy.view),
+        TextureFormat::Rgba8Unorm,
+    );
+    *img_handle = textures.add(img);
+}
+
+fn occupancy_ui(
+    mut contexts: EguiContexts,
+    mut occupancy: ResMut<Occupancy>,
+    settings: Res<SimSettings>,
+    map_size: Res<MapSize>,
+) {
+    if !settings.is_show_menu {
+        return;
+    }
+
+    let ctx = contexts.ctx_mut();
+    egui::Window::new("Occupancy")
+        .default_pos(egui::pos2(W - 340.0, H - 560.0))
+        .default_open(false)
+        .show(ctx, |ui| {
+            ui.horizontal(|ui| {
+                ui.checkbox(&mut occupancy.is_show, "Show");
+                for (view, label) in [
+                    (OccupancyView::Both, "Both"),
+                    (OccupancyView::FindFood, "Find food"),
+                    (OccupancyView::FindHome, "Find home"),
+                ] {
+                    ui.selectable_value(&mut occupancy.view, view, label);
+                }
+            });
+            let total: u64 = occupancy
+                .find_food
+                .iter()
+                .chain(occupancy.find_home.iter())
+                .map(|c| *c as u64)
+                .sum();
+            ui.label(format!("{} ticks, {} ant visits", occupancy.ticks, total));
+            ui.horizontal(|ui| {
+                if ui.button("Export PNG").clicked() {
+                    match occupancy.save_png(OCCUPANCY_PNG_FILE) {
+                        Ok(_) => println!("Saved occupancy to {}", OCCUPANCY_PNG_FILE),
+                        Err(e) => eprintln!("Failed to save occupancy: {}", e),
+                    }
+                }
+                if ui.button("Export CSV").clicked() {
+                    match occupancy.save_csv(OCCUPANCY_CSV_FILE, &map_size) {
+                        Ok(_) => println!("Saved occupancy to {}", OCCUPANCY_CSV_FILE),
+                        Err(e) => eprintln!("Failed to save occupancy: {}", e),
+                    }
+                }
+                if ui.button("Clear").clicked() {
+                    occupancy.clear(&map_size);
+                }
+            });
+        });
+}
@@ -0,0 +1,225 @@
+//! Genetic optimisation of `SimConfig`.
+//! Each genome is a full config, fitness is the food delivered by a headless
+//! run of a fixed number of ticks on the given map.
+
+use crate::{gui::SimConfig, headless::HeadlessSim, map::MapLayout};
+use rand::{rngs::StdRng, Rng, SeedableRng};
+use std::thread;
+
+/// One tunable parameter, bounds mirror the sliders in the Parameters panel
+pub struct Gene {
+    pub name: &'static str,
+    pub min: f32,
+    pub max: f32,
+    get: fn(&SimConfig) -> f32,
+    set: fn(&mut SimConfig, f32),
+}
+
+impl Gene {
+    pub fn value(&self, config: &SimConfig) -> f32 {
+        (self.get)(config)
+    }
+
+    /// Sets the parameter without clamping it to the gene's bounds
+    pub fn set_value(&self, config: &mut SimConfig, value: f32) {
+        (self.set)(config, value)
+    }
+
+    /// Sets the parameter clamped to the gene's bounds
+    pub fn set_clamped(&self, config: &mut SimConfig, value: f32) {
+        (self.set)(config, value.clamp(self.min, self.max))
+    }
+}
+
+pub const GENES: [Gene; 10] = [
+    Gene { name: "ph_decay_rate", min: 0.01, max: 2.0, get: |c| c.ph_decay_rate, set: |c, v| c.ph_decay_rate = v },
+    Gene { name: "ant_ph_strength_decay_rate", min: 0.1, max: 10.0, get: |c| c.ant_ph_strength_decay_rate, set: |c, v| c.ant_ph_strength_decay_rate = v },
+    Gene { name: "ant_sensor_dist", min: 5.0, max: 100.0, get: |c| c.ant_sensor_dist, set: |c, v| c.ant_sensor_dist = v },
+    Gene { name: "ant_sensor_angle", min: 10.0, max: 90.0, get: |c| c.ant_sensor_angle, set: |c, v| c.ant_sensor_angle = v },
+    Gene { name: "ant_turn_randomness", min: 0.0, max: 1.0, get: |c| c.ant_turn_randomness, set: |c, v| c.ant_turn_randomness = v },
+    Gene { name: "ant_update_interval", min: 0.01, max: 0.5, get: |c| c.ant_update_interval, set: |c, v| c.ant_update_interval = v },
+    Gene { name: "ants_count", min: 0.0, max: 5000.0, get: |c| c.ants_count as f32, set: |c, v| c.ants_count = v.round() as usize },
+    Gene { name: "ant_target_auto_pull_radius", min: 10.0, max: 500.0, get: |c| c.ant_target_auto_pull_radius, set: |c, v| c.ant_target_auto_pull_radius = v },
+    Gene { name: "ant_steering_force_factor", min: 1.0, max: 20.0, get: |c| c.ant_steering_force_factor, set: |c, v| c.ant_steering_force_factor = v },
+    Gene { name: "max_pheromone_strength", min: 100.0, max: 10000.0, get: |c| c.max_pheromone_strength, set: |c, v| c.max_pheromone_strength = v },
+];
+
+/// The gene for the `SimConfig` field named `name`
+pub fn gene(name: &str) -> Option<&'static Gene> {
+    GENES.iter().find(|g| g.name == name)
+}
+
+/// Genes frozen unless unfrozen explicitly. More ants always deliver more food, so
+/// a free colony size would just be driven to its maximum
+pub const DEFAULT_FROZEN: [&str; 1] = ["ants_count"];
+
+pub struct OptimizerSettings {
+    pub population: usize,
+    pub generations: usize,
+    /// Length of each fitness run
+    pub ticks: u64,
+    /// Runs averaged per genome, with different seeds
+    pub runs: u64,
+    /// Chance for each gene to be mutated
+    pub mutation_rate: f32,
+    /// Mutation step as a fraction of the gene's range
+    pub mutation_scale: f32,
+    /// Best genomes copied unchanged into the next generation
+    pub elitism: usize,
+    pub tournament_size: usize,
+    pub seed: u64,
+    /// Genes kept at the base config's value
+    pub frozen: Vec<String>,
+}
+
+impl Default for OptimizerSettings {
+    fn default() -> Self {
+        Self {
+            population: 16,
+            generations: 10,
+            ticks: 6000,
+            runs: 1,
+            mutation_rate: 0.2,
+            mutation_scale: 0.1,
+            elitism: 2,
+            tournament_size: 3,
+            seed: 0,
+            frozen: DEFAULT_FROZEN.iter().map(|name| name.to_string()).collect(),
+        }
+    }
+}
+
+pub struct Optimizer<'a> {
+    layout: &'a MapLayout,
+    base: SimConfig,
+    settings: OptimizerSettings,
+    rng: StdRng,
+}
+
+impl<'a> Optimizer<'a> {
+    pub fn new(layout: &'a MapLayout, base: SimConfig, settings: OptimizerSettings) -> Self {
+        let rng = StdRng::seed_from_u64(settings.seed);
+        Self { layout, base, settings, rng }
+    }
+
+    /// Runs all generations, calling `on_generation` with the best genome so far.
+    /// Returns the best config found and its fitness
+    pub fn run(&mut self, mut on_generation: impl FnMut(usize, f32, &SimConfig)) -> (SimConfig, f32) {
+        let mut population: Vec<SimConfig> = (0..self.settings.population.max(1))
+            .map(|i| if i == 0 { self.base.clone() } else { self.random_genome() })
+            .collect();
+        let mut best = (self.base.clone(), f32::MIN);
+
+        for generation in 0..self.settings.generations {
+            // Same seeds for the whole generation so genomes are compared on equal terms
+            let run_seed = self.rng.gen::<u64>();
+            let fitness = self.evaluate(&population, run_seed);
+
+            let mut ranked: Vec<(SimConfig, f32)> = population.into_iter().zip(fitness).collect();
+            ranked.sort_by(|a, b| b.1.total_cmp(&a.1));
+            if ranked[0].1 > best.1 {
+                best = ranked[0].clone();
+            }
+            on_generation(generation, best.1, &best.0);
+
+            let mut next: Vec<SimConfig> = ranked
+                .iter()
+                .take(self.settings.elitism)
+                .map(|(c, _)| c.clone())
+                .collect();
+            while next.len() < ranked.len() {
+                let a = self.tournament(&ranked);
+                let b = self.tournament(&ranked);
+                let mut child = self.crossover(a, b);
+                self.mutate(&mut child);
+                next.push(child);
+            }
+            population = next;
+        }
+
+        best
+    }
+
+    pub fn evaluate(&self, population: &[SimConfig], run_seed: u64) -> Vec<f32> {
+        let threads = thread::available_parallelism().map(|n| n.get()).unwrap_or(1);
+        let chunk_size = population.len().div_ceil(threads).max(1);
+        let (layout, ticks, runs) = (self.layout, self.settings.ticks, self.settings.runs.max(1));
+
+        thread::scope(|scope| {
+            let handles: Vec<_> = population
+                .chunks(chunk_size)
+                .map(|chunk| {
+                    scope.spawn(move || {
+                        chunk
+                            .iter()
+                            .map(|config| fitness(config, layout, ticks, runs, run_seed))
+                            .collect::<Vec<_>>()
+                    })
+                })
+                .collect();
+
+            handles
+                .into_iter()
+                .flat_map(|h| h.join().expect("fitness thread panicked"))
+                .collect()
+        })
+    }
+
+    fn is_frozen(&self, gene: &Gene) -> bool {
+        self.settings.frozen.iter().any(|name| name == gene.name)
+    }
+
+    fn random_genome(&mut self) -> SimConfig {
+        let mut config = self.base.clone();
+        for gene in GENES.iter() {
+            if !self.is_frozen(gene) {
+                (gene.set)(&mut config, self.rng.gen_range(gene.min..=gene.max));
+            }
+        }
+        config
+    }
+
+    fn tournament<'b>(&mut self, ranked: &'b [(SimConfig, f32)]) -> &'b SimConfig {
+        let mut best = &ranked[self.rng.gen_range(0..ranked.len())];
+        for _ in 1..self.settings.tournament_size {
+            let other = &ranked[self.rng.gen_range(0..ranked.len())];
+            if other.1 > best.1 {
+                best = other;
+            }
+        }
+        &best.0
+    }
+
+    fn crossover(&mut self, a: &SimConfig, b: &SimConfig) -> SimConfig {
+        let mut child = a.clone();
+        for gene in GENES.iter() {
+            if self.rng.gen_bool(0.5) {
+                (gene.set)(&mut child, (gene.get)(b));
+            }
+        }
+        child
+    }
+
+    fn mutate(&mut self, config: &mut SimConfig) {
+        for gene in GENES.iter() {
+            if self.is_frozen(gene) || !self.rng.gen_bool(self.settings.mutation_rate as f64) {
+                continue;
+            }
+            let step = (gene.max - gene.min) * self.settings.mutation_scale;
+            let value = (gene.get)(config) + self.rng.gen_range(-step..=step);
+            gene.set_clamped(config, value);
+        }
+    }
+}
+
+/// Average food delivered over `runs` headless runs
+pub fn fitness(config: &SimConfig, layout: &MapLayout, ticks: u64, runs: u64, seed: u64) -> f32 {
+    let total: f32 = (0..runs)
+        .map(|i| {
+            let mut sim = HeadlessSim::new(config.clone(), layout, seed.wrapping_add(i));
+            sim.run(ticks);
+            sim.food_delivered
+        })
+        .sum();
+    total / runs as f32
+}
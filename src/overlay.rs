@@ -0,0 +1,302 @@
+//! Debug overlays that read the pheromone grids directly instead of going through
+//! the colour ramp of the pheromone image.
+//! Hovering over the map with the "View/Select" tool shows the raw values of the
+//! cell under the cursor. The vector field samples both layers on a coarse lattice
+//! and draws their gradients together with the direction an ant would steer.
+//! In contour mode the pheromone layers are drawn as isolines instead of cells.
+
+use crate::{
+    ant::{find_pull_target, sensor_readings, AntTask, Food},
+    grid::{contour_segments, ph_scale, ph_unscale},
+    gui::{cursor_world_pos, EditorState, EditorTool, PhRenderMode, SimConfig, SimSettings},
+    map::{MapSize, Nest, ObstacleMap},
+    pathviz::PathVizGrid,
+    pheromone::Pheromones,
+    *,
+};
+use bevy::{prelude::*, time::common_conditions::on_timer};
+use bevy_egui::{egui, EguiContexts};
+use bevy_pancam::PanCam;
+use std::{f32::consts::TAU, time::Duration};
+
+pub struct OverlayPlugin;
+
+pub struct FieldPoint {
+    pub pos: Vec2,
+    pub grad_home: Vec2,
+    pub grad_food: Vec2,
+    /// Direction to the food or nest when an ant would be pulled straight to it, else
+    /// the mean of the sensor steer directions over all headings. The length tells how
+    /// much the headings agree
+    pub steer: Vec2,
+}
+
+/// Vector field sampled from the pheromone grids, refreshed on a timer since
+/// sampling every lattice point is too slow to do each frame
+#[derive(Resource, Default)]
+pub struct VectorField {
+    pub points: Vec<FieldPoint>,
+}
+
+/// Isoline segments of both pheromone layers in world coordinates
+#[derive(Resource, Default)]
+pub struct Contours {
+    pub segments: Vec<(Vec2, Vec2, Color)>,
+}
+
+impl Plugin for OverlayPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(VectorField::default())
+            .insert_resource(Contours::default())
+            .add_systems(Update, cell_tooltip)
+            .add_systems(
+                Update,
+                update_vector_field.run_if(on_timer(Duration::from_secs_f32(FIELD_UPDATE_SECS))),
+            )
+            .add_systems(Update, draw_vector_field)
+            .add_systems(
+                Update,
+                update_contours.run_if(on_timer(Duration::from_secs_f32(PH_IMG_UPDATE_SEC))),
+            )
+            .add_systems(Update, draw_contours);
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn cell_tooltip(
+    mut contexts: EguiContexts,
+    settings: Res<SimSettings>,
+    editor_state: Res<EditorState>,
+    pheromones: Res<Pheromones>,
+    viz_grid: Res<PathVizGrid>,
+    obstacle_map: Res<ObstacleMap>,
+    map_size: Res<MapSize>,
+    windows: Query<&Window>,
+    camera_q: Query<(&Camera, &GlobalTransform), With<PanCam>>,
+) {
+    if !settings.is_show_cell_info || editor_state.selected_tool != EditorTool::None {
+        return;
+    }
+    let ctx = contexts.ctx_mut();
+    if ctx.is_pointer_over_area() {
+        return;
+    }
+    let Some(pos) = cursor_world_pos(&windows, &camera_q) else {
+        return;
+    };
+    if pos.x.abs() > map_size.width / 2.0 || pos.y.abs() > map_size.height / 2.0 {
+        return;
+    }
+
+    let terrain = obstacle_map.terrain_at(pos.x, pos.y, map_size.width, map_size.height);
+    let decay_factor = obstacle_map.decay_factor_at(pos.x, pos.y, map_size.width, map_size.height);
+    let (path_food, path_home) = viz_grid.get_values_at(pos, &map_size);
+
+    egui::show_tooltip_at_pointer(ctx, egui::Id::new("cell_tooltip"), |ui| {
+        egui::Grid::new("cell_tooltip_grid").num_columns(3).show(ui, |ui| {
+            ui.label(format!("({:.0}, {:.0})", pos.x, pos.y));
+            ui.label("cell");
+            ui.label(format!("sensor r={}", ANT_SENSOR_RADIUS));
+            ui.end_row();
+            ui.label("To home");
+            ui.label(format!("{:.1}", pheromones.to_home.get_value_at(pos)));
+            ui.label(format!("{:.1}", pheromones.to_home.sample_sensor_sum(pos, ANT_SENSOR_RADIUS)));
+            ui.end_row();
+            ui.label("To food");
+            ui.label(format!("{:.1}", pheromones.to_food.get_value_at(pos)));
+            ui.label(format!("{:.1}", pheromones.to_food.sample_sensor_sum(pos, ANT_SENSOR_RADIUS)));
+            ui.end_row();
+            ui.label("Path food/home");
+            ui.label(format!("{:.0} / {:.0}", path_food, path_home));
+            ui.end_row();
+            ui.label("Terrain");
+            ui.label(terrain.label());
+            ui.end_row();
+            ui.label("Decay");
+            ui.label(format!("x{:.2}", decay_factor));
+            ui.end_row();
+        });
+    });
+}
+
+/// Central difference of the sensor sums, so the gradient is what an ant sensor sees
+fn sensor_gradient(grid: &crate::grid::WorldGrid, pos: Vec2, d: f32) -> Vec2 {
+    let sample = |p: Vec2| grid.sample_sensor_sum(p, ANT_SENSOR_RADIUS);
+    Vec2::new(
+        sample(pos + Vec2::X * d) - sample(pos - Vec2::X * d),
+        sample(pos + Vec2::Y * d) - sample(pos - Vec2::Y * d),
+    ) / (2.0 * d)
+}
+
+/// Direction an ant standing at the given position steers to. Like the ant's own
+/// decision, a pull target in sight wins, otherwise the sensor steering is averaged
+/// over evenly spread headings
+fn net_steer(
+    task: &AntTask,
+    pos: Vec2,
+    pull_target: Option<Vec2>,
+    pheromones: &Pheromones,
+    config: &SimConfig,
+) -> Vec2 {
+    if let Some(target) = pull_target {
+        return (target - pos).normalize_or_zero();
+    }
+    let sum = (0..FIELD_STEER_HEADINGS)
+        .map(|i| Vec2::from_angle(i as f32 / FIELD_STEER_HEADINGS as f32 * TAU))
+        .filter_map(|heading| {
+            sensor_readings(task, pos, heading, pheromones, config).steer_target(pos, config.ant_sensor_dist)
+        })
+        .map(|target| (target - pos).normalize_or_zero())
+        .sum::<Vec2>();
+    sum / FIELD_STEER_HEADINGS as f32
+}
+
+#[allow(clippy::too_many_arguments)]
+fn update_vector_field(
+    mut field: ResMut<VectorField>,
+    settings: Res<SimSettings>,
+    pheromones: Res<Pheromones>,
+    config: Res<SimConfig>,
+    obstacle_map: Res<ObstacleMap>,
+    map_size: Res<MapSize>,
+    nest: Res<Nest>,
+    food_query: Query<(&Transform, &Food)>,
+) {
+    field.points.clear();
+    if !settings.is_show_vector_field {
+        return;
+    }
+
+    let food: Vec<Vec2> = food_query
+        .iter()
+        .filter(|(_, food)| food.storage > 0)
+        .map(|(t, _)| t.translation.truncate())
+        .collect();
+
+    let (cols, rows) = (
+        (map_size.width / FIELD_LATTICE_SPACING) as i32,
+        (map_size.height / FIELD_LATTICE_SPACING) as i32,
+    );
+    let origin = Vec2::new(-map_size.width, -map_size.height) / 2.0 + FIELD_LATTICE_SPACING / 2.0;
+    let d = FIELD_LATTICE_SPACING / 2.0;
+
+    for row in 0..rows {
+        for col in 0..cols {
+            let pos = origin + Vec2::new(col as f32, row as f32) * FIELD_LATTICE_SPACING;
+            if obstacle_map.is_obstacle(pos.x, pos.y, map_size.width, map_size.height) {
+                continue;
+            }
+            let task = &settings.field_task;
+            let pull_target =
+                find_pull_target(task, pos, food.iter().copied(), nest.0, &config, &obstacle_map, &map_size);
+            field.points.push(FieldPoint {
+                pos,
+                grad_home: sensor_gradient(&pheromones.to_home, pos, d),
+                grad_food: sensor_gradient(&pheromones.to_food, pos, d),
+                steer: net_steer(task, pos, pull_target, &pheromones, &config),
+            });
+        }
+    }
+}
+
+fn draw_arrow(gizmos: &mut Gizmos, from: Vec2, vector: Vec2, color: Color) {
+    let len = vector.length();
+    if len < 1.0 {
+        return;
+    }
+    let tip = from + vector;
+    let back = -vector / len * len.min(6.0);
+    gizmos.line_2d(from, tip, color);
+    gizmos.line_2d(tip, tip + Vec2::from_angle(0.5).rotate(back), color);
+    gizmos.line_2d(tip, tip + Vec2::from_angle(-0.5).rotate(back), color);
+}
+
+fn draw_vector_field(mut gizmos: Gizmos, settings: Res<SimSettings>, field: Res<VectorField>) {
+    if !settings.is_show_vector_field || field.points.is_empty() {
+        return;
+    }
+
+    // Gradients span orders of magnitude (the nest seed alone is 100000), so arrow
+    // length follows the log of the magnitude relative to the layer's maximum
+    let max_log = |f: fn(&FieldPoint) -> Vec2| {
+        field
+            .points
+            .iter()
+            .map(|p| f(p).length().ln_1p())
+            .fold(0.0, f32::max)
+    };
+    let scaled = |v: Vec2, max: f32| v.normalize_or_zero() * v.length().ln_1p() / max * FIELD_ARROW_LENGTH;
+    let max_home = max_log(|p| p.grad_home);
+    let max_food = max_log(|p| p.grad_food);
+    let color_of = |(r, g, b): (u8, u8, u8)| Color::rgb_u8(r, g, b);
+
+    for point in field.points.iter() {
+        if settings.is_show_home_ph && max_home > 0.0 {
+            draw_arrow(&mut gizmos, point.pos, scaled(point.grad_home, max_home), color_of(PH_COLOR_TO_HOME));
+        }
+        if settings.is_show_food_ph && max_food > 0.0 {
+            draw_arrow(&mut gizmos, point.pos, scaled(point.grad_food, max_food), color_of(PH_COLOR_TO_FOOD));
+        }
+        draw_arrow(&mut gizmos, point.pos, point.steer * FIELD_ARROW_LENGTH, Color::WHITE);
+        gizmos.circle_2d(point.pos, 1.0, Color::GRAY);
+    }
+}
+
+fn update_contours(
+    mut contours: ResMut<Contours>,
+    settings: Res<SimSettings>,
+    config: Res<SimConfig>,
+    pheromones: Res<Pheromones>,
+    map_size: Res<MapSize>,
+) {
+    contours.segments.clear();
+    if settings.ph_render_mode != PhRenderMode::Contours {
+        return;
+    }
+
+    // Levels evenly spread along the colour ramp, so with the log scale most of
+    // them sit at the weak end
+    let max_val = config.max_pheromone_strength;
+    let n = settings.contour_levels;
+    let levels: Vec<f32> = (1..=n)
+        .map(|i| ph_unscale(i as f32 / (n + 1) as f32, max_val, settings.is_ph_log_scale))
+        .collect();
+
+    let half = (
+        (map_size.width / 2.0) as i32 / PH_UNIT_GRID_SIZE as i32,
+        (map_size.height / 2.0) as i32 / PH_UNIT_GRID_SIZE as i32,
+    );
+    let layers = [
+        (settings.is_show_home_ph, &pheromones.to_home, PH_COLOR_TO_HOME_MAX),
+        (settings.is_show_food_ph, &pheromones.to_food, PH_COLOR_TO_FOOD_MAX),
+    ];
+    for (is_shown, grid, high_color) in layers {
+        if !is_shown {
+            continue;
+        }
+        let level_colors: Vec<Color> = levels
+            .iter()
+            .map(|level| {
+                let t = ph_scale(*level, max_val, settings.is_ph_log_scale);
+                let base = Vec3::new(grid.color.0 as f32, grid.color.1 as f32, grid.color.2 as f32);
+                let high = Vec3::new(high_color.0 as f32, high_color.1 as f32, high_color.2 as f32);
+                let c = base.lerp(high, t) / 255.0;
+                // Brighten a bit so dark base colours show up as thin lines
+                Color::rgb(c.x, c.y, c.z) * 1.5
+            })
+            .collect();
+        let segments = contour_segments(grid.get_signals(), (-half.0, -half.1), half, &levels);
+        contours.segments.extend(segments.into_iter().map(|(level_idx, a, b)| {
+            (a * PH_UNIT_GRID_SIZE as f32, b * PH_UNIT_GRID_SIZE as f32, level_colors[level_idx])
+        }));
+    }
+}
+
+fn draw_contours(mut gizmos: Gizmos, settings: Res<SimSettings>, contours: Res<Contours>) {
+    if settings.ph_render_mode != PhRenderMode::Contours {
+        return;
+    }
+    for (a, b, color) in contours.segments.iter() {
+        gizmos.line_2d(*a, *b, *color);
+    }
+}
@@ -70,13 +70,7 @@ fn update_grid_values(
     let h_map = map_size.height;
     
     for (transform, current_task) in ant_query.iter() {
-        let x = transform.translation.x as i32;
-        let y = transform.translation.y as i32;
-        
-        // Inline window_to_grid logic with dynamic size
-        let k_x = (x + (w_map as i32 / 2)) / PH_UNIT_GRID_SIZE as i32;
-        let k_y = ((h_map as i32 / 2) - y) / PH_UNIT_GRID_SIZE as i32;
-        let key = (k_x, k_y);
+        let key = viz_key(transform.translation.truncate(), w_map, h_map);
 
         match current_task.0 {
             AntTask::FindFood => {
@@ -88,9 +82,9 @@ fn update_grid_values(
         }
     }
 
-    viz_grid.dg_food.decay_values(VIZ_DECAY_RATE);
+    viz_grid.dg_food.decay_values(VIZ_DECAY_RATE, |_| 1.0);
     viz_grid.dg_food.drop_zero_values();
-    viz_grid.dg_home.decay_values(VIZ_DECAY_RATE);
+    viz_grid.dg_home.decay_values(VIZ_DECAY_RATE, |_| 1.0);
     viz_grid.dg_home.drop_zero_values();
 }
 
@@ -116,6 +110,7 @@ fn update_path_viz_image(
         VIZ_MAX_COLOR_STRENGTH, 
         VIZ_COLOR_TO_FOOD,
         VIZ_COLOR_TO_FOOD,
+        false,
     );
     add_map_to_grid_img(
         viz_grid.dg_home.get_values(),
@@ -126,6 +121,7 @@ fn update_path_viz_image(
         VIZ_MAX_COLOR_STRENGTH,
         VIZ_COLOR_TO_HOME,
         VIZ_COLOR_TO_HOME,
+        false,
     );
 
     let path_img = Image::new(
@@ -142,11 +138,25 @@ fn update_path_viz_image(
 }
 
 
+/// Image cell of a world position, row 0 is the top of the map
+pub(crate) fn viz_key(pos: Vec2, w_map: f32, h_map: f32) -> (i32, i32) {
+    let (x, y) = (pos.x as i32, pos.y as i32);
+    let k_x = (x + (w_map as i32 / 2)) / PH_UNIT_GRID_SIZE as i32;
+    let k_y = ((h_map as i32 / 2) - y) / PH_UNIT_GRID_SIZE as i32;
+    (k_x, k_y)
+}
+
 impl PathVizGrid {
     fn new() -> Self {
         Self {
-            dg_home: DecayGrid::new(HashMap::new(), VIZ_MAX_COLOR_STRENGTH),
-            dg_food: DecayGrid::new(HashMap::new(), VIZ_MAX_COLOR_STRENGTH),
+            dg_home: DecayGrid::new(HashMap::new()),
+            dg_food: DecayGrid::new(HashMap::new()),
         }
     }
+
+    /// Path intensity of (food seekers, home seekers) at a world position
+    pub fn get_values_at(&self, pos: Vec2, map_size: &crate::map::MapSize) -> (f32, f32) {
+        let key = viz_key(pos, map_size.width, map_size.height);
+        (self.dg_food.get_value(&key), self.dg_home.get_value(&key))
+    }
 }
@@ -1,6 +1,7 @@
 use crate::{
     grid::{add_map_to_grid_img, WorldGrid},
-    gui::{ResetSimEvent, SimConfig, SimSettings, SimStatistics},
+    gui::{run_if_not_paused, PhRenderMode, ResetSimEvent, SimConfig, SimSettings, SimStatistics},
+    map::{MapSize, Nest, ObstacleMap},
     *,
 };
 use bevy::{
@@ -16,6 +17,8 @@ pub struct PheromonePlugin;
 pub struct Pheromones {
     pub to_home: WorldGrid,
     pub to_food: WorldGrid,
+    /// Where the strong to_home signal of the nest is
+    home: Vec2,
 }
 
 #[derive(Component)]
@@ -24,7 +27,7 @@ struct PheromoneImageRender;
 impl Plugin for PheromonePlugin {
     fn build(&self, app: &mut App) {
         app.add_systems(Startup, setup)
-            .insert_resource(Pheromones::new())
+            .insert_resource(Pheromones::new(Nest::default().0))
             .add_systems(
                 Update,
                 (
@@ -45,26 +48,36 @@ impl Plugin for PheromonePlugin {
                     PH_KD_TREE_UPDATE_INTERVAL,
                 ))),
             )
-            .add_systems(Update, reset_pheromones);
+            .add_systems(Update, (reset_pheromones, follow_nest));
     }
 }
 
-fn run_if_not_paused(settings: Res<SimSettings>) -> bool {
-    !settings.is_paused
-}
-
 fn reset_pheromones(
     mut events: EventReader<ResetSimEvent>,
     mut pheromones: ResMut<Pheromones>,
+    nest: Res<Nest>,
 ) {
     for _ in events.iter() {
-        *pheromones = Pheromones::new();
+        *pheromones = Pheromones::new(nest.0);
+    }
+}
+
+/// Keeps the home signal on the nest when it is moved, loaded or replayed
+fn follow_nest(nest: Res<Nest>, mut pheromones: ResMut<Pheromones>) {
+    if nest.is_changed() {
+        pheromones.set_home(nest.0);
     }
 }
 
-fn pheromone_decay(mut pheromones: ResMut<Pheromones>, config: Res<SimConfig>) {
-    pheromones.to_food.decay_signals(config.ph_decay_rate);
-    pheromones.to_home.decay_signals(config.ph_decay_rate);
+fn pheromone_decay(
+    mut pheromones: ResMut<Pheromones>,
+    config: Res<SimConfig>,
+    obstacle_map: Res<ObstacleMap>,
+    map_size: Res<MapSize>,
+) {
+    let multiplier_at = |p: Vec2| obstacle_map.decay_factor_at(p.x, p.y, map_size.width, map_size.height);
+    pheromones.to_food.decay_signals(config.ph_decay_rate, multiplier_at);
+    pheromones.to_home.decay_signals(config.ph_decay_rate, multiplier_at);
 }
 
 fn update_sim_stats(pheromones: Res<Pheromones>, mut stats: ResMut<SimStatistics>) {
@@ -86,7 +99,7 @@ fn pheromone_image_update(
     sim_settings: Res<SimSettings>,
     pheromone: Res<Pheromones>,
     mut image_handle_query: Query<&mut Handle<Image>, With<PheromoneImageRender>>,
-    map_size: Res<crate::map::MapSize>,
+    map_size: Res<MapSize>,
     config: Res<SimConfig>,
 ) {
     let mut img_handle = image_handle_query.single_mut();
@@ -95,8 +108,10 @@ fn pheromone_image_update(
         map_size.height as usize / PH_UNIT_GRID_SIZE,
     );
     let mut bytes = vec![0; w * h * 4];
+    // Contours are drawn by the overlay instead, the image stays empty
+    let is_cells = sim_settings.ph_render_mode == PhRenderMode::Cells;
 
-    if sim_settings.is_show_home_ph {
+    if is_cells && sim_settings.is_show_home_ph {
         add_map_to_grid_img(
             pheromone.to_home.get_signals(),
             &mut bytes,
@@ -105,10 +120,11 @@ fn pheromone_image_update(
             map_size.height,
             config.max_pheromone_strength,
             pheromone.to_home.color,
-            (255, 0, 0), // Deep Red for Home Pheromone Max
+            PH_COLOR_TO_HOME_MAX,
+            sim_settings.is_ph_log_scale,
         );
     }
-    if sim_settings.is_show_food_ph {
+    if is_cells && sim_settings.is_show_food_ph {
         add_map_to_grid_img(
             pheromone.to_food.get_signals(),
             &mut bytes,
@@ -117,7 +133,8 @@ fn pheromone_image_update(
             map_size.height,
             config.max_pheromone_strength,
             pheromone.to_food.color,
-            (0, 0, 255), // Deep Blue for Food Pheromone Max
+            PH_COLOR_TO_FOOD_MAX,
+            sim_settings.is_ph_log_scale,
         );
     }
 
@@ -146,20 +163,45 @@ fn setup(mut commands: Commands) {
 }
 
 impl Pheromones {
-    fn new() -> Self {
-        let mut to_food_map = HashMap::new();
-        let mut to_home_map = HashMap::new();
+    pub(crate) fn new(home: Vec2) -> Self {
+        let to_food_map = HashMap::new();
+        let mut to_home = WorldGrid::new(PH_COLOR_TO_HOME, HashMap::new());
 
         // Food and Home have high pheromone strength
         // to_food_map.insert((FOOD_LOCATION.0 as i32, FOOD_LOCATION.1 as i32), 100000.0);
-        to_home_map.insert((HOME_LOCATION.0 as i32, HOME_LOCATION.1 as i32), 100000.0);
+        to_home.set_value_at(home, HOME_PH_STRENGTH);
 
         Self {
             to_food: WorldGrid::new(PH_COLOR_TO_FOOD, to_food_map),
-            to_home: WorldGrid::new(PH_COLOR_TO_HOME, to_home_map),
+            to_home,
+            home,
         }
     }
 
+    /// Rebuilds the grids from stored values, `home` being where the nest signal is
+    pub(crate) fn from_signals(
+        to_home: HashMap<(i32, i32), f32>,
+        to_food: HashMap<(i32, i32), f32>,
+        home: Vec2,
+    ) -> Self {
+        Self {
+            to_food: WorldGrid::new(PH_COLOR_TO_FOOD, to_food),
+            to_home: WorldGrid::new(PH_COLOR_TO_HOME, to_home),
+            home,
+        }
+    }
+
+    /// Moves the strong to_home signal to a new nest position
+    pub fn set_home(&mut self, home: Vec2) {
+        if home == self.home {
+            return;
+        }
+        self.to_home.set_value_at(self.home, 0.0);
+        self.to_home.set_value_at(home, HOME_PH_STRENGTH);
+        self.home = home;
+        self.to_home.update_tree();
+    }
+
     fn update_tree(&mut self) {
         self.to_food.update_tree();
         self.to_home.update_tree();
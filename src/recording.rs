@@ -0,0 +1,653 @@
+//! Recording and replay of simulation runs.
+//! A recording stores the starting map, keyframes of the ant, food and pheromone
+//! state taken every RECORD_KEYFRAME_SECS of sim time, and the editor actions made
+//! in between. Replay interpolates between keyframes instead of re-simulating, so
+//! it can be paused, scrubbed and played at any speed.
+
+use crate::{
+    ant::{
+        spawn_ant_with_state, spawn_food, task_atlas, Ant, AntAnimations, AntTask, CarriedFood, CurrentTask, Food,
+        Motion, PhStrength, PickupTime, Velocity,
+    },
+    grid::WorldGrid,
+    gui::{EditorAction, EditorActionEvent, SimConfig, SimSettings},
+    map::{FoodSpec, LoadMapEvent, MapLayout, MapSize, Nest, ObstacleMap},
+    pheromone::Pheromones,
+    *,
+};
+use bevy::prelude::*;
+use bevy_egui::{egui, EguiContexts};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::{HashMap, HashSet},
+    fs::File,
+    io::{BufReader, BufWriter},
+};
+
+pub struct RecordingPlugin;
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct AntState {
+    pub id: u64,
+    pub x: f32,
+    pub y: f32,
+    pub vx: f32,
+    pub vy: f32,
+    pub task: AntTask,
+    pub ph_strength: f32,
+    /// Quality of the food being carried
+    #[serde(default = "default_carried")]
+    pub carried: f32,
+    #[serde(default)]
+    pub pickup_time: Option<f32>,
+}
+
+fn default_carried() -> f32 {
+    FOOD_QUALITY
+}
+
+#[derive(Serialize, Deserialize, Clone, Default)]
+pub struct PheromoneState {
+    pub to_home: Vec<(i32, i32, f32)>,
+    pub to_food: Vec<(i32, i32, f32)>,
+}
+
+impl PheromoneState {
+    pub fn capture(pheromones: &Pheromones) -> Self {
+        let flatten = |grid: &WorldGrid| {
+            grid.get_signals()
+                .iter()
+                .filter(|(_, v)| **v > 0.0)
+                .map(|(&(x, y), &v)| (x, y, v))
+                .collect()
+        };
+        Self {
+            to_home: flatten(&pheromones.to_home),
+            to_food: flatten(&pheromones.to_food),
+        }
+    }
+
+    /// `home` is the nest position at the time of the capture
+    pub fn restore(&self, home: Vec2) -> Pheromones {
+        let values = |values: &[(i32, i32, f32)]| values.iter().map(|&(x, y, v)| ((x, y), v)).collect();
+        let mut pheromones = Pheromones::from_signals(values(&self.to_home), values(&self.to_food), home);
+        pheromones.to_home.update_tree();
+        pheromones.to_food.update_tree();
+        pheromones
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct Keyframe {
+    /// Seconds since the recording started
+    pub time: f32,
+    pub ants: Vec<AntState>,
+    pub food: Vec<FoodSpec>,
+    /// Only stored every RECORD_PHEROMONE_EVERY keyframes, the maps are large
+    pub pheromones: Option<PheromoneState>,
+}
+
+/// Changes to the map made while recording
+#[derive(Serialize, Deserialize, Clone)]
+pub enum RecordedAction {
+    Edit(EditorAction),
+    ResizeMap(MapSize),
+    LoadMap(MapLayout),
+}
+
+impl RecordedAction {
+    /// Applies the action to the walls, food comes from the keyframes instead
+    fn apply(&self, obstacle_map: &mut ObstacleMap, map_size: &mut MapSize, nest: &mut Nest) {
+        match self {
+            RecordedAction::Edit(EditorAction::MoveNest { x, y }) => nest.0 = Vec2::new(*x, *y),
+            RecordedAction::Edit(action) => action.apply_to_obstacles(obstacle_map, map_size),
+            RecordedAction::ResizeMap(size) => {
+                *map_size = *size;
+                let mut resized = ObstacleMap::new(size.width, size.height);
+                // Same rule as resize_obstacle_map, a loaded map already has the right size
+                if resized.width != obstacle_map.width || resized.height != obstacle_map.height {
+                    resized.dynamic_walls = std::mem::take(&mut obstacle_map.dynamic_walls);
+                    set_walls(obstacle_map, resized);
+                }
+            }
+            RecordedAction::LoadMap(layout) => {
+                *map_size = layout.map_size;
+                nest.0 = Vec2::new(layout.nest.0, layout.nest.1);
+                set_walls(obstacle_map, layout.to_obstacle_map());
+            }
+        }
+    }
+}
+
+/// Replaces the grid but keeps the texture the map is drawn to
+fn set_walls(obstacle_map: &mut ObstacleMap, mut walls: ObstacleMap) {
+    walls.texture_handle = obstacle_map.texture_handle.clone();
+    *obstacle_map = walls;
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct TimedAction {
+    pub time: f32,
+    pub action: RecordedAction,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct Recording {
+    pub config: SimConfig,
+    /// Map when the recording started
+    pub start: MapLayout,
+    pub keyframes: Vec<Keyframe>,
+    pub actions: Vec<TimedAction>,
+}
+
+impl Recording {
+    pub fn duration(&self) -> f32 {
+        self.keyframes.last().map(|k| k.time).unwrap_or(0.0)
+    }
+
+    /// Index of the last keyframe at or before `time`
+    pub fn keyframe_index_at(&self, time: f32) -> usize {
+        self.keyframes.partition_point(|k| k.time <= time).saturating_sub(1)
+    }
+
+    /// Index of the last keyframe with pheromones at or before keyframe `idx`
+    pub fn pheromone_index_at(&self, idx: usize) -> Option<usize> {
+        (0..=idx).rev().find(|&i| self.keyframes[i].pheromones.is_some())
+    }
+
+    pub fn load(path: &str) -> Result<Self, String> {
+        let file = File::open(path).map_err(|e| format!("{}: {}", path, e))?;
+        let recording: Self =
+            serde_json::from_reader(BufReader::new(file)).map_err(|e| format!("{}: {}", path, e))?;
+        if recording.keyframes.is_empty() {
+            return Err(format!("{}: recording has no keyframes", path));
+        }
+        Ok(recording)
+    }
+
+    pub fn save(&self, path: &str) -> Result<(), String> {
+        let file = File::create(path).map_err(|e| format!("{}: {}", path, e))?;
+        serde_json::to_writer(BufWriter::new(file), self).map_err(|e| format!("{}: {}", path, e))
+    }
+}
+
+#[derive(Resource, Default)]
+pub struct Recorder {
+    recording: Option<Recording>,
+    /// Sim time since the recording started, kept here since a reset clears SimClock
+    elapsed_secs: f32,
+    next_keyframe_secs: f32,
+}
+
+impl Recorder {
+    pub fn is_recording(&self) -> bool {
+        self.recording.is_some()
+    }
+}
+
+#[derive(Resource)]
+pub struct Replay {
+    recording: Option<Recording>,
+    pub time: f32,
+    pub speed: f32,
+    pub is_playing: bool,
+    applied_actions: usize,
+    shown_keyframe: Option<usize>,
+    shown_pheromones: Option<usize>,
+    /// Ants spawned for recorded ids that have no live entity
+    ant_ids: HashMap<u64, Entity>,
+}
+
+impl Default for Replay {
+    fn default() -> Self {
+        Self {
+            recording: None,
+            time: 0.0,
+            speed: 1.0,
+            is_playing: false,
+            applied_actions: 0,
+            shown_keyframe: None,
+            shown_pheromones: None,
+            ant_ids: HashMap::new(),
+        }
+    }
+}
+
+#[derive(Event)]
+pub enum RecordingCommand {
+    StartRecording,
+    StopRecording,
+    StartReplay,
+    StopReplay,
+}
+
+impl Plugin for RecordingPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(Recorder::default())
+            .insert_resource(Replay::default())
+            .add_event::<RecordingCommand>()
+            .add_systems(
+                Update,
+                (handle_recording_commands, record_actions, record_keyframes, play_replay).chain(),
+            )
+            .add_systems(Update, recording_ui);
+    }
+}
+
+pub(crate) type AntReadQuery<'w, 's> = Query<
+    'w,
+    's,
+    (
+        Entity,
+        &'static Transform,
+        &'static CurrentTask,
+        &'static Velocity,
+        &'static PhStrength,
+        &'static CarriedFood,
+        &'static PickupTime,
+    ),
+    With<Ant>,
+>;
+
+fn capture_keyframe(
+    time: f32,
+    ant_query: &AntReadQuery,
+    food_query: &Query<(&Transform, &Food)>,
+    pheromones: Option<&Pheromones>,
+) -> Keyframe {
+    Keyframe {
+        time,
+        ants: capture_ants(ant_query),
+        food: capture_food(food_query),
+        pheromones: pheromones.map(PheromoneState::capture),
+    }
+}
+
+pub(crate) fn capture_ants(ant_query: &AntReadQuery) -> Vec<AntState> {
+    ant_query
+        .iter()
+        .map(|(entity, transform, task, velocity, ph_strength, carried, pickup_time)| AntState {
+            id: entity.to_bits(),
+            x: transform.translation.x,
+            y: transform.translation.y,
+            vx: velocity.0.x,
+            vy: velocity.0.y,
+            task: task.0,
+            ph_strength: ph_strength.0,
+            carried: carried.0,
+            pickup_time: pickup_time.0,
+        })
+        .collect()
+}
+
+pub(crate) fn capture_food(food_query: &Query<(&Transform, &Food)>) -> Vec<FoodSpec> {
+    food_query
+        .iter()
+        .map(|(tr, food)| food.spec(tr.translation.truncate()))
+        .collect()
+}
+
+#[allow(clippy::too_many_arguments)]
+fn handle_recording_commands(
+    mut commands: EventReader<RecordingCommand>,
+    mut recorder: ResMut<Recorder>,
+    mut replay: ResMut<Replay>,
+    mut settings: ResMut<SimSettings>,
+    mut config: ResMut<SimConfig>,
+    obstacle_map: Res<ObstacleMap>,
+    map_size: Res<MapSize>,
+    nest: Res<Nest>,
+    pheromones: Res<Pheromones>,
+    ant_query: AntReadQuery,
+    food_query: Query<(&Transform, &Food)>,
+) {
+    for command in commands.iter() {
+        match command {
+            RecordingCommand::StartRecording => {
+                if settings.is_replaying {
+                    continue;
+                }
+                let start = MapLayout::capture(&obstacle_map, *map_size, &nest, capture_food(&food_query));
+                let first = capture_keyframe(0.0, &ant_query, &food_query, Some(&pheromones));
+                *recorder = Recorder {
+                    recording: Some(Recording {
+                        config: config.clone(),
+                        start,
+                        keyframes: vec![first],
+                        actions: vec![],
+                    }),
+                    elapsed_secs: 0.0,
+                    next_keyframe_secs: RECORD_KEYFRAME_SECS,
+                };
+                println!("Recording started");
+            }
+            RecordingCommand::StopRecording => {
+                let Some(recording) = recorder.recording.take() else {
+                    continue;
+                };
+                match recording.save(RECORDING_FILE) {
+                    Ok(_) => println!(
+                        "Saved recording ({:.1}s, {} keyframes) to {}",
+                        recording.duration(),
+                        recording.keyframes.len(),
+                        RECORDING_FILE
+                    ),
+                    Err(e) => eprintln!("Failed to save recording: {}", e),
+                }
+            }
+            RecordingCommand::StartReplay => {
+                if recorder.is_recording() {
+                    continue;
+                }
+                match Recording::load(RECORDING_FILE) {
+                    Ok(recording) => {
+                        *config = recording.config.clone();
+                        *replay = Replay {
+                            recording: Some(recording),
+                            is_playing: true,
+                            speed: replay.speed,
+                            ..default()
+                        };
+                        settings.is_replaying = true;
+                        println!("Replaying {}", RECORDING_FILE);
+                    }
+                    Err(e) => eprintln!("Failed to load recording: {}", e),
+                }
+            }
+            RecordingCommand::StopReplay => {
+                // The world is left at the replayed state, paused, so it can be resumed from there
+                replay.recording = None;
+                settings.is_replaying = false;
+                settings.is_paused = true;
+            }
+        }
+    }
+}
+
+fn record_actions(
+    mut recorder: ResMut<Recorder>,
+    mut edits: EventReader<EditorActionEvent>,
+    mut map_loads: EventReader<LoadMapEvent>,
+    map_size: Res<MapSize>,
+) {
+    let time = recorder.elapsed_secs;
+    let Some(recording) = recorder.recording.as_mut() else {
+        edits.clear();
+        map_loads.clear();
+        return;
+    };
+
+    let mut log = |action| recording.actions.push(TimedAction { time, action });
+    for event in map_loads.iter() {
+        log(RecordedAction::LoadMap(event.layout.clone()));
+    }
+    for EditorActionEvent { action, .. } in edits.iter() {
+        log(RecordedAction::Edit(action.clone()));
+    }
+    if map_size.is_changed() {
+        log(RecordedAction::ResizeMap(*map_size));
+    }
+}
+
+fn record_keyframes(
+    mut recorder: ResMut<Recorder>,
+    settings: Res<SimSettings>,
+    time: Res<Time>,
+    pheromones: Res<Pheromones>,
+    ant_query: AntReadQuery,
+    food_query: Query<(&Transform, &Food)>,
+) {
+    if !recorder.is_recording() || !settings.is_running() {
+        return;
+    }
+
+    recorder.elapsed_secs += time.delta_seconds();
+    if recorder.elapsed_secs < recorder.next_keyframe_secs {
+        return;
+    }
+    recorder.next_keyframe_secs += RECORD_KEYFRAME_SECS;
+
+    let elapsed_secs = recorder.elapsed_secs;
+    let Some(recording) = recorder.recording.as_mut() else {
+        return;
+    };
+    let with_pheromones = recording.keyframes.len() % RECORD_PHEROMONE_EVERY == 0;
+    let keyframe = capture_keyframe(
+        elapsed_secs,
+        &ant_query,
+        &food_query,
+        with_pheromones.then_some(&*pheromones),
+    );
+    recording.keyframes.push(keyframe);
+}
+
+#[allow(clippy::too_many_arguments)]
+fn play_replay(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    mut replay: ResMut<Replay>,
+    time: Res<Time>,
+    ant_animations: Res<AntAnimations>,
+    mut obstacle_map: ResMut<ObstacleMap>,
+    mut map_size: ResMut<MapSize>,
+    mut nest: ResMut<Nest>,
+    mut pheromones: ResMut<Pheromones>,
+    mut ant_query: AntStateQuery,
+    food_query: Query<Entity, With<Food>>,
+) {
+    let replay = &mut *replay;
+    let Some(recording) = &replay.recording else {
+        return;
+    };
+
+    if replay.is_playing {
+        replay.time += time.delta_seconds() * replay.speed;
+        if replay.time >= recording.duration() {
+            replay.time = recording.duration();
+            replay.is_playing = false;
+        }
+    }
+    replay.time = replay.time.clamp(0.0, recording.duration());
+
+    // Walls: replay the logged actions, rebuilding from the start when scrubbing back
+    let num_actions = recording.actions.partition_point(|a| a.time <= replay.time);
+    if replay.shown_keyframe.is_none() || num_actions < replay.applied_actions {
+        set_walls(&mut obstacle_map, recording.start.to_obstacle_map());
+        *map_size = recording.start.map_size;
+        nest.0 = Vec2::new(recording.start.nest.0, recording.start.nest.1);
+        replay.applied_actions = 0;
+    }
+    if num_actions > replay.applied_actions {
+        for timed in recording.actions[replay.applied_actions..num_actions].iter() {
+            timed.action.apply(&mut obstacle_map, &mut map_size, &mut nest);
+        }
+        replay.applied_actions = num_actions;
+    }
+
+    let idx = recording.keyframe_index_at(replay.time);
+    let keyframe = &recording.keyframes[idx];
+
+    if replay.shown_keyframe != Some(idx) {
+        for entity in food_query.iter() {
+            commands.entity(entity).despawn();
+        }
+        for f in keyframe.food.iter() {
+            spawn_food(&mut commands, &asset_server, f.clone());
+        }
+        replay.shown_keyframe = Some(idx);
+    }
+
+    let ph_idx = recording.pheromone_index_at(idx);
+    if replay.shown_pheromones != ph_idx {
+        if let Some(state) = ph_idx.and_then(|i| recording.keyframes[i].pheromones.as_ref()) {
+            *pheromones = state.restore(nest.0);
+        }
+        replay.shown_pheromones = ph_idx;
+    }
+
+    // Ants: placed between this keyframe and the next
+    let next = recording.keyframes.get(idx + 1);
+    let t = next
+        .map(|n| ((replay.time - keyframe.time) / (n.time - keyframe.time).max(f32::EPSILON)).clamp(0.0, 1.0))
+        .unwrap_or(0.0);
+    let next_pos: HashMap<u64, Vec2> = next
+        .map(|n| n.ants.iter().map(|a| (a.id, Vec2::new(a.x, a.y))).collect())
+        .unwrap_or_default();
+
+    apply_ant_states(
+        &mut commands,
+        &ant_animations,
+        &mut ant_query,
+        &mut replay.ant_ids,
+        &keyframe.ants,
+        |state| {
+            let pos = Vec2::new(state.x, state.y);
+            next_pos.get(&state.id).map(|p| pos.lerp(*p, t)).unwrap_or(pos)
+        },
+    );
+}
+
+pub(crate) type AntStateQuery<'w, 's> = Query<
+    'w,
+    's,
+    (
+        Entity,
+        &'static mut Transform,
+        &'static mut CurrentTask,
+        &'static mut Velocity,
+        &'static mut PhStrength,
+        &'static mut CarriedFood,
+        &'static mut PickupTime,
+        &'static mut Motion,
+        &'static mut Handle<TextureAtlas>,
+    ),
+    With<Ant>,
+>;
+
+/// Moves each ant to its state in `states`, spawning or despawning ants to match the count.
+/// States are matched to ants by id, `ids` maps the ids of states whose ant had to be
+/// spawned to the new entity. `pos_of` gives the position to show for each state
+pub(crate) fn apply_ant_states(
+    commands: &mut Commands,
+    ant_animations: &AntAnimations,
+    ant_query: &mut AntStateQuery,
+    ids: &mut HashMap<u64, Entity>,
+    states: &[AntState],
+    pos_of: impl Fn(&AntState) -> Vec2,
+) {
+    let mut matched = HashSet::new();
+    let mut missing = vec![];
+    for state in states.iter() {
+        let entity = ids.get(&state.id).copied().unwrap_or(Entity::from_bits(state.id));
+        let Ok((
+            entity,
+            mut transform,
+            mut task,
+            mut velocity,
+            mut ph_strength,
+            mut carried,
+            mut pickup_time,
+            mut motion,
+            mut atlas,
+        )) = ant_query.get_mut(entity)
+        else {
+            missing.push(state);
+            continue;
+        };
+        if !matched.insert(entity) {
+            missing.push(state);
+            continue;
+        }
+        let pos = pos_of(state);
+        transform.translation.x = pos.x;
+        transform.translation.y = pos.y;
+        motion.reset(pos);
+        velocity.0 = Vec2::new(state.vx, state.vy);
+        transform.rotation = Quat::from_rotation_z(velocity.0.y.atan2(velocity.0.x));
+        ph_strength.0 = state.ph_strength;
+        carried.0 = state.carried;
+        pickup_time.0 = state.pickup_time;
+        if task.0 != state.task {
+            task.0 = state.task;
+            *atlas = task_atlas(state.task, ant_animations);
+        }
+    }
+
+    for (entity, ..) in ant_query.iter() {
+        if !matched.contains(&entity) {
+            commands.entity(entity).despawn();
+        }
+    }
+    for state in missing {
+        let entity = spawn_ant_with_state(commands, ant_animations, state, pos_of(state));
+        ids.insert(state.id, entity);
+    }
+}
+
+fn recording_ui(
+    mut contexts: EguiContexts,
+    recorder: Res<Recorder>,
+    mut replay: ResMut<Replay>,
+    settings: Res<SimSettings>,
+    mut commands: EventWriter<RecordingCommand>,
+) {
+    if !settings.is_show_menu {
+        return;
+    }
+
+    let ctx = contexts.ctx_mut();
+
+    egui::Window::new("Recorder")
+        .anchor(egui::Align2::RIGHT_BOTTOM, egui::vec2(-10.0, -10.0))
+        .default_open(false)
+        .show(ctx, |ui| {
+            let replay = &mut *replay;
+            if let Some(recording) = &replay.recording {
+                let duration = recording.duration();
+                ui.horizontal(|ui| {
+                    let label = if replay.is_playing { "Pause" } else { "Play" };
+                    if ui.button(label).clicked() {
+                        // Restart from the beginning when at the end
+                        if !replay.is_playing && replay.time >= duration {
+                            replay.time = 0.0;
+                        }
+                        replay.is_playing = !replay.is_playing;
+                    }
+                    if ui.button("Exit Replay").clicked() {
+                        commands.send(RecordingCommand::StopReplay);
+                    }
+                });
+                ui.add(egui::Slider::new(&mut replay.time, 0.0..=duration).text("Time (s)"));
+                ui.add(
+                    egui::Slider::new(&mut replay.speed, 0.1..=8.0)
+                        .logarithmic(true)
+                        .text("Speed"),
+                );
+                ui.label(format!(
+                    "{} keyframes, {} edits",
+                    recording.keyframes.len(),
+                    recording.actions.len()
+                ));
+            } else if let Some(recording) = &recorder.recording {
+                ui.label(format!("Recording: {:.1}s", recorder.elapsed_secs));
+                ui.label(format!(
+                    "{} keyframes, {} edits",
+                    recording.keyframes.len(),
+                    recording.actions.len()
+                ));
+                if ui.button("Stop & Save").clicked() {
+                    commands.send(RecordingCommand::StopRecording);
+                }
+            } else {
+                ui.horizontal(|ui| {
+                    if ui.button("Record").clicked() {
+                        commands.send(RecordingCommand::StartRecording);
+                    }
+                    if ui.button("Replay").clicked() {
+                        commands.send(RecordingCommand::StartReplay);
+                    }
+                });
+                ui.label(format!("File: {}", RECORDING_FILE));
+            }
+        });
+}
@@ -0,0 +1,172 @@
+//! Rewind of the live simulation.
+//! A snapshot of the whole world is kept every REWIND_SNAPSHOT_SECS of sim time in a
+//! ring buffer. While paused, the timeline slider restores any of them; resuming
+//! continues from the restored point and drops the snapshots after it.
+
+use crate::{
+    ant::{AntAnimations, Food},
+    gui::{ResetSimEvent, SimClock, SimSettings},
+    map::{LoadMapEvent, MapLayout, MapSize, Nest, ObstacleMap},
+    pheromone::Pheromones,
+    recording::{apply_ant_states, capture_ants, capture_food, AntReadQuery, AntState, AntStateQuery, PheromoneState},
+    *,
+};
+use bevy::prelude::*;
+use bevy_egui::{egui, EguiContexts};
+use std::collections::{HashMap, VecDeque};
+
+pub struct RewindPlugin;
+
+pub struct WorldSnapshot {
+    pub tick: u64,
+    pub elapsed_secs: f32,
+    /// Walls, food and nest
+    pub layout: MapLayout,
+    pub ants: Vec<AntState>,
+    pub pheromones: PheromoneState,
+}
+
+#[derive(Resource, Default)]
+pub struct Rewind {
+    pub snapshots: VecDeque<WorldSnapshot>,
+    next_snapshot_secs: f32,
+    /// Snapshot shown while paused, the ones after it are dropped on resume
+    restored: Option<usize>,
+    /// Ants spawned for snapshot ids whose entity was gone
+    ant_ids: HashMap<u64, Entity>,
+}
+
+/// Restores the snapshot at the given index of the ring buffer
+#[derive(Event)]
+pub struct RewindEvent(pub usize);
+
+impl Plugin for RewindPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(Rewind::default())
+            .add_event::<RewindEvent>()
+            .add_systems(Update, (clear_rewind, take_snapshot, restore_snapshot).chain())
+            .add_systems(Update, rewind_ui);
+    }
+}
+
+fn clear_rewind(mut events: EventReader<ResetSimEvent>, mut rewind: ResMut<Rewind>) {
+    for _ in events.iter() {
+        *rewind = Rewind::default();
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn take_snapshot(
+    mut rewind: ResMut<Rewind>,
+    settings: Res<SimSettings>,
+    clock: Res<SimClock>,
+    obstacle_map: Res<ObstacleMap>,
+    map_size: Res<MapSize>,
+    nest: Res<Nest>,
+    pheromones: Res<Pheromones>,
+    ant_query: AntReadQuery,
+    food_query: Query<(&Transform, &Food)>,
+) {
+    if !settings.is_running() {
+        return;
+    }
+
+    // Resumed after a rewind, the old future is gone
+    if let Some(idx) = rewind.restored.take() {
+        rewind.snapshots.truncate(idx + 1);
+    }
+
+    if clock.elapsed_secs < rewind.next_snapshot_secs {
+        return;
+    }
+    rewind.next_snapshot_secs = clock.elapsed_secs + REWIND_SNAPSHOT_SECS;
+
+    if rewind.snapshots.len() >= REWIND_CAPACITY {
+        rewind.snapshots.pop_front();
+    }
+    rewind.snapshots.push_back(WorldSnapshot {
+        tick: clock.tick,
+        elapsed_secs: clock.elapsed_secs,
+        layout: MapLayout::capture(&obstacle_map, *map_size, &nest, capture_food(&food_query)),
+        ants: capture_ants(&ant_query),
+        pheromones: PheromoneState::capture(&pheromones),
+    });
+}
+
+#[allow(clippy::too_many_arguments)]
+fn restore_snapshot(
+    mut commands: Commands,
+    mut events: EventReader<RewindEvent>,
+    mut rewind: ResMut<Rewind>,
+    mut clock: ResMut<SimClock>,
+    mut pheromones: ResMut<Pheromones>,
+    mut load_map_event: EventWriter<LoadMapEvent>,
+    ant_animations: Res<AntAnimations>,
+    mut ant_query: AntStateQuery,
+) {
+    let rewind = &mut *rewind;
+    // Only the last request of the frame matters when dragging the slider
+    let Some(&RewindEvent(idx)) = events.iter().last() else {
+        return;
+    };
+    let Some(snapshot) = rewind.snapshots.get(idx) else {
+        return;
+    };
+
+    // Walls, food and nest go through the map loader
+    load_map_event.send(LoadMapEvent {
+        layout: snapshot.layout.clone(),
+        reset: false,
+    });
+    apply_ant_states(
+        &mut commands,
+        &ant_animations,
+        &mut ant_query,
+        &mut rewind.ant_ids,
+        &snapshot.ants,
+        |state| Vec2::new(state.x, state.y),
+    );
+    *pheromones = snapshot.pheromones.restore(Vec2::new(snapshot.layout.nest.0, snapshot.layout.nest.1));
+    clock.tick = snapshot.tick;
+    clock.elapsed_secs = snapshot.elapsed_secs;
+
+    rewind.next_snapshot_secs = snapshot.elapsed_secs + REWIND_SNAPSHOT_SECS;
+    rewind.restored = Some(idx);
+}
+
+fn rewind_ui(
+    mut contexts: EguiContexts,
+    rewind: Res<Rewind>,
+    mut settings: ResMut<SimSettings>,
+    mut rewind_event: EventWriter<RewindEvent>,
+) {
+    if !settings.is_show_menu || !settings.is_paused || settings.is_replaying || rewind.snapshots.is_empty() {
+        return;
+    }
+
+    let ctx = contexts.ctx_mut();
+    let last = rewind.snapshots.len() - 1;
+
+    egui::Window::new("Timeline")
+        .anchor(egui::Align2::CENTER_TOP, egui::vec2(0.0, 10.0))
+        .collapsible(false)
+        .show(ctx, |ui| {
+            let mut idx = rewind.restored.unwrap_or(last);
+            let slider = egui::Slider::new(&mut idx, 0..=last)
+                .text("Snapshot")
+                .custom_formatter(|v, _| format!("{:.1}s", rewind.snapshots[v as usize].elapsed_secs));
+            if ui.add(slider).changed() {
+                rewind_event.send(RewindEvent(idx));
+            }
+            ui.horizontal(|ui| {
+                if ui.button("Resume from here").clicked() {
+                    settings.is_paused = false;
+                }
+                ui.label(format!(
+                    "{} snapshots, every {:.0}s",
+                    rewind.snapshots.len(),
+                    REWIND_SNAPSHOT_SECS
+                ));
+            });
+        });
+}
@@ -0,0 +1,378 @@
+//! Built-in experiment scenarios.
+//!
+//! Double bridge (Deneubourg et al.): the nest and a food source are joined by
+//! two branches of different length. With equal branches the colony breaks the
+//! symmetry and settles on one of them, with unequal branches it should pick the
+//! short one. `BranchTracker` counts which branch each ant traverses over time.
+
+use crate::{
+    ant::{Ant, AntTask, CurrentTask},
+    gui::{ResetSimEvent, SimClock, SimSettings},
+    map::{BrushShape, FoodSpec, LoadMapEvent, MapLayout, MapSize, Nest, ObstacleMap, Terrain},
+    *,
+};
+use bevy::{math::vec2, prelude::*};
+use bevy_egui::{egui, EguiContexts};
+use std::collections::HashMap;
+
+pub struct ScenarioPlugin;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Branch {
+    Short,
+    Long,
+}
+
+/// Geometry of the double bridge, in world coordinates
+#[derive(Clone)]
+pub struct DoubleBridge {
+    pub ratio: f32,
+    pub nest: Vec2,
+    pub food: Vec2,
+    pub fork: Vec2,
+    pub join: Vec2,
+    pub short_path: Vec<Vec2>,
+    pub long_path: Vec<Vec2>,
+}
+
+impl DoubleBridge {
+    /// `ratio` is long branch length / short branch length, clamped to 1..=2 so it fits the default map
+    pub fn new(ratio: f32) -> Self {
+        let ratio = ratio.clamp(1.0, 2.0);
+        let half_span = 400.0;
+        let offset = 120.0; // Both branches leave the fork at 45 degrees
+
+        let short_len = 2.0 * offset * std::f32::consts::SQRT_2 + (2.0 * half_span - 2.0 * offset);
+        // The long branch dips further down, each vertical leg adds to the length
+        let depth = offset + (ratio - 1.0) * short_len / 2.0;
+        // Center the whole figure vertically, but keep the fork axis off y = 0: pheromone
+        // keys truncate towards zero, so the row around y = 0 is twice as tall and
+        // biases ants towards one branch when both are the same length
+        let c = (depth - offset) / 2.0 + 60.0;
+
+        let fork = vec2(-half_span, c);
+        let join = vec2(half_span, c);
+        let short_path = vec![
+            fork,
+            vec2(fork.x + offset, c + offset),
+            vec2(join.x - offset, c + offset),
+            join,
+        ];
+        let long_path = vec![
+            fork,
+            vec2(fork.x + offset, c - offset),
+            vec2(fork.x + offset, c - depth),
+            vec2(join.x - offset, c - depth),
+            vec2(join.x - offset, c - offset),
+            join,
+        ];
+
+        Self {
+            ratio,
+            nest: vec2(-half_span - 250.0, c),
+            food: vec2(half_span + 250.0, c),
+            fork,
+            join,
+            short_path,
+            long_path,
+        }
+    }
+
+    pub fn path(&self, branch: Branch) -> &[Vec2] {
+        match branch {
+            Branch::Short => &self.short_path,
+            Branch::Long => &self.long_path,
+        }
+    }
+
+    pub fn branch_length(&self, branch: Branch) -> f32 {
+        self.path(branch).windows(2).map(|w| w[0].distance(w[1])).sum()
+    }
+
+    /// Middle of the branch, passing through it counts as taking the branch
+    fn gate(&self, branch: Branch) -> Vec2 {
+        let path = self.path(branch);
+        // Middle segment is the one between the diagonal/vertical legs
+        let mid = path.len() / 2;
+        (path[mid - 1] + path[mid]) / 2.0
+    }
+
+    /// Everything is wall except the corridors, nest and food chambers
+    pub fn layout(&self) -> MapLayout {
+        let map_size = MapSize::default();
+        let (w, h) = (map_size.width, map_size.height);
+        let brush = BRIDGE_CORRIDOR_WIDTH / 2.0;
+
+        let mut obstacle_map = ObstacleMap::new(w, h);
+        obstacle_map.grid.fill(Terrain::Wall);
+
+        obstacle_map.set_obstacle_line(self.nest, self.fork, w, h, false, brush, BrushShape::Round);
+        obstacle_map.set_obstacle_line(self.join, self.food, w, h, false, brush, BrushShape::Round);
+        for path in [&self.short_path, &self.long_path] {
+            for segment in path.windows(2) {
+                obstacle_map.set_obstacle_line(segment[0], segment[1], w, h, false, brush, BrushShape::Round);
+            }
+        }
+        obstacle_map.set_obstacle(self.nest.x, self.nest.y, w, h, false, BRIDGE_CORRIDOR_WIDTH);
+        obstacle_map.set_obstacle(self.food.x, self.food.y, w, h, false, BRIDGE_CORRIDOR_WIDTH);
+
+        let food = vec![FoodSpec::new(self.food.x, self.food.y, BRIDGE_FOOD_STORAGE)];
+        MapLayout::capture(&obstacle_map, map_size, &Nest(self.nest), food)
+    }
+}
+
+/// Branch traversals in one time bin, split by direction
+#[derive(Default, Clone, Copy)]
+pub struct BranchBin {
+    pub short_outbound: u32,
+    pub short_return: u32,
+    pub long_outbound: u32,
+    pub long_return: u32,
+}
+
+impl BranchBin {
+    pub fn short(&self) -> u32 {
+        self.short_outbound + self.short_return
+    }
+
+    pub fn long(&self) -> u32 {
+        self.long_outbound + self.long_return
+    }
+
+    /// Fraction of traversals that used the short branch, None if nobody crossed
+    pub fn short_fraction(&self) -> Option<f32> {
+        let total = self.short() + self.long();
+        (total > 0).then(|| self.short() as f32 / total as f32)
+    }
+}
+
+#[derive(Resource)]
+pub struct BranchTracker {
+    pub bridge: Option<DoubleBridge>,
+    pub bin_secs: f32,
+    pub bins: Vec<BranchBin>,
+    // Last branch counted per ant, cleared when the ant reaches the fork or join
+    last_branch: HashMap<u64, Branch>,
+}
+
+impl Default for BranchTracker {
+    fn default() -> Self {
+        Self {
+            bridge: None,
+            bin_secs: BRIDGE_BIN_SECS,
+            bins: vec![],
+            last_branch: HashMap::new(),
+        }
+    }
+}
+
+impl BranchTracker {
+    pub fn new(bridge: DoubleBridge, bin_secs: f32) -> Self {
+        Self {
+            bridge: Some(bridge),
+            bin_secs,
+            ..default()
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.bins.clear();
+        self.last_branch.clear();
+    }
+
+    pub fn observe(&mut self, ant_id: u64, pos: Vec2, task: AntTask, elapsed_secs: f32) {
+        let Some(bridge) = &self.bridge else {
+            return;
+        };
+
+        let junction_radius = BRIDGE_CORRIDOR_WIDTH;
+        if pos.distance_squared(bridge.fork) < junction_radius * junction_radius
+            || pos.distance_squared(bridge.join) < junction_radius * junction_radius
+        {
+            self.last_branch.remove(&ant_id);
+            return;
+        }
+
+        let gate_radius = BRIDGE_CORRIDOR_WIDTH * 0.75;
+        for branch in [Branch::Short, Branch::Long] {
+            if pos.distance_squared(bridge.gate(branch)) > gate_radius * gate_radius {
+                continue;
+            }
+            if self.last_branch.insert(ant_id, branch) == Some(branch) {
+                // Still walking through the same gate
+                return;
+            }
+
+            let idx = (elapsed_secs / self.bin_secs) as usize;
+            if self.bins.len() <= idx {
+                self.bins.resize(idx + 1, BranchBin::default());
+            }
+            let bin = &mut self.bins[idx];
+            match (branch, task) {
+                (Branch::Short, AntTask::FindFood) => bin.short_outbound += 1,
+                (Branch::Short, AntTask::FindHome) => bin.short_return += 1,
+                (Branch::Long, AntTask::FindFood) => bin.long_outbound += 1,
+                (Branch::Long, AntTask::FindHome) => bin.long_return += 1,
+            }
+            return;
+        }
+    }
+
+    pub fn totals(&self) -> BranchBin {
+        self.bins.iter().fold(BranchBin::default(), |acc, b| BranchBin {
+            short_outbound: acc.short_outbound + b.short_outbound,
+            short_return: acc.short_return + b.short_return,
+            long_outbound: acc.long_outbound + b.long_outbound,
+            long_return: acc.long_return + b.long_return,
+        })
+    }
+
+    pub fn to_csv(&self) -> String {
+        let mut csv = String::from("bin_start_secs,short_outbound,short_return,long_outbound,long_return,short_fraction\n");
+        for (i, bin) in self.bins.iter().enumerate() {
+            csv.push_str(&format!(
+                "{},{},{},{},{},{}\n",
+                i as f32 * self.bin_secs,
+                bin.short_outbound,
+                bin.short_return,
+                bin.long_outbound,
+                bin.long_return,
+                bin.short_fraction().map(|f| format!("{:.3}", f)).unwrap_or_default(),
+            ));
+        }
+        csv
+    }
+}
+
+#[derive(Resource)]
+struct ScenarioUiState {
+    bridge_ratio: f32,
+}
+
+impl Plugin for ScenarioPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(BranchTracker::default())
+            .insert_resource(ScenarioUiState { bridge_ratio: 2.0 })
+            .add_systems(Update, (track_branches, reset_branch_tracker, draw_branch_gates, scenario_ui));
+    }
+}
+
+fn track_branches(
+    mut tracker: ResMut<BranchTracker>,
+    ant_query: Query<(Entity, &Transform, &CurrentTask), With<Ant>>,
+    settings: Res<SimSettings>,
+    clock: Res<SimClock>,
+) {
+    if tracker.bridge.is_none() || !settings.is_running() {
+        return;
+    }
+
+    for (entity, transform, task) in ant_query.iter() {
+        tracker.observe(entity.to_bits(), transform.translation.truncate(), task.0, clock.elapsed_secs);
+    }
+}
+
+fn reset_branch_tracker(mut events: EventReader<ResetSimEvent>, mut tracker: ResMut<BranchTracker>) {
+    for _ in events.iter() {
+        tracker.clear();
+    }
+}
+
+fn draw_branch_gates(mut gizmos: Gizmos, tracker: Res<BranchTracker>, settings: Res<SimSettings>) {
+    let Some(bridge) = &tracker.bridge else {
+        return;
+    };
+    if !settings.is_show_menu {
+        return;
+    }
+
+    let gate_radius = BRIDGE_CORRIDOR_WIDTH * 0.75;
+    gizmos.circle_2d(bridge.gate(Branch::Short), gate_radius, Color::rgba(0.2, 1.0, 0.2, 0.5));
+    gizmos.circle_2d(bridge.gate(Branch::Long), gate_radius, Color::rgba(1.0, 0.6, 0.2, 0.5));
+}
+
+fn scenario_ui(
+    mut contexts: EguiContexts,
+    mut ui_state: ResMut<ScenarioUiState>,
+    mut tracker: ResMut<BranchTracker>,
+    mut load_map_event: EventWriter<LoadMapEvent>,
+    settings: Res<SimSettings>,
+) {
+    if !settings.is_show_menu {
+        return;
+    }
+
+    let ctx = contexts.ctx_mut();
+
+    egui::Window::new("Scenarios")
+        .anchor(egui::Align2::RIGHT_TOP, egui::vec2(-10.0, 10.0))
+        .default_open(false)
+        .show(ctx, |ui| {
+            egui::CollapsingHeader::new("Double Bridge")
+                .default_open(true)
+                .show(ui, |ui| {
+                    ui.add(egui::Slider::new(&mut ui_state.bridge_ratio, 1.0..=2.0).text("Long/Short"));
+                    ui.horizontal(|ui| {
+                        if ui.button("Load").clicked() {
+                            let bridge = DoubleBridge::new(ui_state.bridge_ratio);
+                            load_map_event.send(LoadMapEvent {
+                                layout: bridge.layout(),
+                                reset: true,
+                            });
+                            *tracker = BranchTracker::new(bridge, BRIDGE_BIN_SECS);
+                        }
+                        if tracker.bridge.is_some() && ui.button("Stop tracking").clicked() {
+                            *tracker = BranchTracker::default();
+                        }
+                    });
+
+                    let Some(bridge) = &tracker.bridge else {
+                        return;
+                    };
+                    ui.label(format!(
+                        "Short: {:.0}px  Long: {:.0}px",
+                        bridge.branch_length(Branch::Short),
+                        bridge.branch_length(Branch::Long)
+                    ));
+
+                    let totals = tracker.totals();
+                    ui.label(format!("Short: {} out / {} back", totals.short_outbound, totals.short_return));
+                    ui.label(format!("Long: {} out / {} back", totals.long_outbound, totals.long_return));
+                    if let Some(f) = totals.short_fraction() {
+                        ui.label(format!("Short branch share: {:.0}%", f * 100.0));
+                    }
+
+                    ui.label(format!("Short share per {}s:", tracker.bin_secs));
+                    branch_share_plot(ui, &tracker.bins);
+
+                    if ui.button("Export CSV").clicked() {
+                        match std::fs::write(BRIDGE_CSV_FILE, tracker.to_csv()) {
+                            Ok(_) => println!("Saved branch counts to {}", BRIDGE_CSV_FILE),
+                            Err(e) => eprintln!("Failed to save branch counts: {}", e),
+                        }
+                    }
+                });
+        });
+}
+
+/// Line plot of the short branch share per bin, 50% marked as a reference line
+fn branch_share_plot(ui: &mut egui::Ui, bins: &[BranchBin]) {
+    let (response, painter) = ui.allocate_painter(egui::vec2(260.0, 80.0), egui::Sense::hover());
+    let rect = response.rect;
+    painter.rect_stroke(rect, 0.0, egui::Stroke::new(1.0, egui::Color32::GRAY));
+    painter.line_segment(
+        [rect.left_center(), rect.right_center()],
+        egui::Stroke::new(1.0, egui::Color32::DARK_GRAY),
+    );
+
+    let points: Vec<egui::Pos2> = bins
+        .iter()
+        .enumerate()
+        .filter_map(|(i, bin)| {
+            let f = bin.short_fraction()?;
+            let x = rect.left() + rect.width() * (i as f32 + 0.5) / bins.len().max(1) as f32;
+            Some(egui::pos2(x, rect.bottom() - f * rect.height()))
+        })
+        .collect();
+    painter.add(egui::Shape::line(points, egui::Stroke::new(2.0, egui::Color32::LIGHT_GREEN)));
+}
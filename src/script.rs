@@ -0,0 +1,359 @@
+//! Scripted perturbation experiments.
+//! A script is a json list of events, each with a trigger (a tick, a sim time or an
+//! amount of food delivered) and the actions to run when it is first met: walls drawn
+//! or cleared, doors added or removed, food added or removed, the nest moved and
+//! parameters set or scaled.
+//! Map changes go through the editor actions, so the recorder and undo history see
+//! them like any other edit. The same script runs headless with `ants script`.
+
+use crate::{
+    gui::{
+        run_if_not_paused, EditorAction, EditorActionEvent, ResetSimEvent, SimClock, SimConfig, SimSettings,
+        SimStatistics,
+    },
+    doors::DynamicWall,
+    headless::HeadlessSim,
+    map::{BrushShape, FoodSpec, LoadMapEvent, MapLayout},
+    optimizer::gene,
+    *,
+};
+use bevy::prelude::*;
+use bevy_egui::{egui, EguiContexts};
+use serde::{Deserialize, Serialize};
+
+pub struct ScriptPlugin;
+
+/// When an event fires, each event fires at most once per run
+#[derive(Serialize, Deserialize, Clone, Copy, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum Trigger {
+    AtTick(u64),
+    AtSecs(f32),
+    /// Food delivered to the nest, weighted by quality like the statistics
+    FoodDelivered(f32),
+}
+
+impl Trigger {
+    pub fn is_met(&self, tick: u64, elapsed_secs: f32, food_delivered: f32) -> bool {
+        match *self {
+            Trigger::AtTick(t) => tick >= t,
+            Trigger::AtSecs(secs) => elapsed_secs >= secs,
+            Trigger::FoodDelivered(amount) => food_delivered >= amount,
+        }
+    }
+
+    pub fn label(&self) -> String {
+        match self {
+            Trigger::AtTick(t) => format!("at tick {}", t),
+            Trigger::AtSecs(secs) => format!("at {} s", secs),
+            Trigger::FoodDelivered(amount) => format!("when {} food delivered", amount),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum ScriptAction {
+    /// Wall segment drawn with a round brush of `radius`
+    AddWall {
+        from: (f32, f32),
+        to: (f32, f32),
+        #[serde(default = "default_wall_radius")]
+        radius: f32,
+    },
+    /// Open ground carved along a segment
+    RemoveWall {
+        from: (f32, f32),
+        to: (f32, f32),
+        #[serde(default = "default_wall_radius")]
+        radius: f32,
+    },
+    AddFood(FoodSpec),
+    RemoveFood(FoodTarget),
+    MoveNest { x: f32, y: f32 },
+    /// Door or sliding wall, in the same format as in map files
+    AddDynamicWall(DynamicWall),
+    /// Removes every door or sliding wall drawn within `radius` of the point
+    RemoveDynamicWalls {
+        x: f32,
+        y: f32,
+        #[serde(default = "default_door_radius")]
+        radius: f32,
+    },
+    /// Parameters are named like the `SimConfig` fields
+    SetParam { param: String, value: f32 },
+    ScaleParam { param: String, factor: f32 },
+}
+
+/// Food sources removed by `RemoveFood`
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(untagged)]
+pub enum FoodTarget {
+    /// Sources added with this `name`
+    Named { name: String },
+    /// Every source within `radius` of the point
+    At {
+        x: f32,
+        y: f32,
+        #[serde(default = "default_food_radius")]
+        radius: f32,
+    },
+}
+
+fn default_wall_radius() -> f32 {
+    SCRIPT_WALL_RADIUS
+}
+
+fn default_door_radius() -> f32 {
+    DOOR_REMOVE_RADIUS
+}
+
+fn default_food_radius() -> f32 {
+    FOOD_PICKUP_RADIUS
+}
+
+impl ScriptAction {
+    /// The editor action making the same change, None for parameter changes
+    pub fn to_edit(&self) -> Option<EditorAction> {
+        let stroke = |from: (f32, f32), to: (f32, f32), radius: f32, is_obstacle: bool| EditorAction::Stroke {
+            from,
+            to,
+            is_obstacle,
+            brush_size: radius,
+            brush_shape: BrushShape::Round,
+        };
+        match *self {
+            ScriptAction::AddWall { from, to, radius } => Some(stroke(from, to, radius, true)),
+            ScriptAction::RemoveWall { from, to, radius } => Some(stroke(from, to, radius, false)),
+            ScriptAction::AddFood(ref spec) => Some(EditorAction::PlaceFood(spec.clone())),
+            ScriptAction::RemoveFood(FoodTarget::Named { ref name }) => {
+                Some(EditorAction::RemoveNamedFood { name: name.clone() })
+            }
+            ScriptAction::RemoveFood(FoodTarget::At { x, y, radius }) => Some(EditorAction::RemoveFood { x, y, radius }),
+            ScriptAction::MoveNest { x, y } => Some(EditorAction::MoveNest { x, y }),
+            ScriptAction::AddDynamicWall(ref wall) => Some(EditorAction::AddDynamicWall(wall.clone())),
+            ScriptAction::RemoveDynamicWalls { x, y, radius } => Some(EditorAction::RemoveDynamicWalls { x, y, radius }),
+            ScriptAction::SetParam { .. } | ScriptAction::ScaleParam { .. } => None,
+        }
+    }
+
+    /// Applies a parameter change clamped to the parameter's bounds, does nothing for
+    /// the other actions
+    pub fn apply_to_config(&self, config: &mut SimConfig) {
+        let Some(gene) = self.param().and_then(gene) else {
+            return;
+        };
+        let value = match self {
+            ScriptAction::SetParam { value, .. } => *value,
+            ScriptAction::ScaleParam { factor, .. } => gene.value(config) * factor,
+            _ => return,
+        };
+        gene.set_clamped(config, value);
+    }
+
+    fn param(&self) -> Option<&str> {
+        match self {
+            ScriptAction::SetParam { param, .. } | ScriptAction::ScaleParam { param, .. } => Some(param),
+            _ => None,
+        }
+    }
+
+    pub fn label(&self) -> String {
+        match self {
+            ScriptAction::AddWall { from, to, .. } => format!("wall {:?} -> {:?}", from, to),
+            ScriptAction::RemoveWall { from, to, .. } => format!("clear wall {:?} -> {:?}", from, to),
+            ScriptAction::AddFood(spec) => format!("add food at ({}, {})", spec.x, spec.y),
+            ScriptAction::RemoveFood(FoodTarget::Named { name }) => format!("remove food {}", name),
+            ScriptAction::RemoveFood(FoodTarget::At { x, y, .. }) => format!("remove food at ({}, {})", x, y),
+            ScriptAction::MoveNest { x, y } => format!("move nest to ({}, {})", x, y),
+            ScriptAction::AddDynamicWall(wall) => format!("add {}", wall.label()),
+            ScriptAction::RemoveDynamicWalls { x, y, .. } => format!("remove doors at ({}, {})", x, y),
+            ScriptAction::SetParam { param, value } => format!("{} = {}", param, value),
+            ScriptAction::ScaleParam { param, factor } => format!("{} x{}", param, factor),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct ScriptEvent {
+    pub trigger: Trigger,
+    pub actions: Vec<ScriptAction>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct Script {
+    /// Map loaded when the script starts, the current map is kept if not set
+    #[serde(default)]
+    pub map: Option<String>,
+    pub events: Vec<ScriptEvent>,
+}
+
+impl Script {
+    pub fn load(path: &str) -> Result<Self, String> {
+        let content = std::fs::read_to_string(path).map_err(|e| format!("{}: {}", path, e))?;
+        let script: Self = serde_json::from_str(&content).map_err(|e| format!("{}: {}", path, e))?;
+        // Catch typos before the run instead of silently skipping the change
+        let actions = script.events.iter().flat_map(|e| e.actions.iter());
+        if let Some(param) = actions.filter_map(|a| a.param()).find(|p| gene(p).is_none()) {
+            return Err(format!("{}: unknown parameter {}", path, param));
+        }
+        Ok(script)
+    }
+
+    pub fn load_map(&self) -> Result<Option<MapLayout>, String> {
+        self.map.as_deref().map(MapLayout::load).transpose()
+    }
+}
+
+/// A script and which of its events have fired
+#[derive(Clone)]
+pub struct ScriptRun {
+    pub script: Script,
+    pub fired: Vec<bool>,
+}
+
+impl ScriptRun {
+    pub fn new(script: Script) -> Self {
+        let fired = vec![false; script.events.len()];
+        Self { script, fired }
+    }
+
+    /// Marks the events met for the first time as fired and returns them
+    pub fn due(&mut self, tick: u64, elapsed_secs: f32, food_delivered: f32) -> Vec<ScriptEvent> {
+        let mut due = vec![];
+        for (event, fired) in self.script.events.iter().zip(self.fired.iter_mut()) {
+            if !*fired && event.trigger.is_met(tick, elapsed_secs, food_delivered) {
+                *fired = true;
+                due.push(event.clone());
+            }
+        }
+        due
+    }
+
+    /// Runs the due events of a headless sim, returns them for logging
+    pub fn step_headless(&mut self, sim: &mut HeadlessSim) -> Vec<ScriptEvent> {
+        let due = self.due(sim.tick, sim.elapsed_secs(), sim.food_delivered);
+        for action in due.iter().flat_map(|e| e.actions.iter()) {
+            match action.to_edit() {
+                Some(edit) => sim.apply_edit(&edit),
+                None => {
+                    action.apply_to_config(&mut sim.config);
+                    // Like update_ant_count in the app
+                    sim.match_ants_count();
+                }
+            }
+        }
+        due
+    }
+}
+
+#[derive(Resource, Default)]
+pub struct ScriptRunner {
+    pub run: Option<ScriptRun>,
+    /// Parameters before the script first changed them, restored on reset
+    config_before: Option<SimConfig>,
+}
+
+impl Plugin for ScriptPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(ScriptRunner::default())
+            .add_systems(Update, run_script.run_if(run_if_not_paused))
+            .add_systems(Update, (reset_script, script_ui));
+    }
+}
+
+fn run_script(
+    mut runner: ResMut<ScriptRunner>,
+    mut config: ResMut<SimConfig>,
+    mut actions: EventWriter<EditorActionEvent>,
+    clock: Res<SimClock>,
+    stats: Res<SimStatistics>,
+) {
+    let runner = runner.as_mut();
+    let Some(run) = runner.run.as_mut() else {
+        return;
+    };
+    for event in run.due(clock.tick, clock.elapsed_secs, stats.food_delivered) {
+        println!("Script {} (tick {}, {:.1} s)", event.trigger.label(), clock.tick, clock.elapsed_secs);
+        for action in event.actions.iter() {
+            println!("  {}", action.label());
+            match action.to_edit() {
+                Some(edit) => actions.send(EditorActionEvent::new(edit)),
+                None => {
+                    runner.config_before.get_or_insert_with(|| config.clone());
+                    action.apply_to_config(&mut config);
+                }
+            }
+        }
+    }
+}
+
+/// Reset starts the script over
+fn reset_script(
+    mut events: EventReader<ResetSimEvent>,
+    mut runner: ResMut<ScriptRunner>,
+    mut config: ResMut<SimConfig>,
+) {
+    if events.iter().count() == 0 {
+        return;
+    }
+    if let Some(before) = runner.config_before.take() {
+        *config = before;
+    }
+    if let Some(run) = runner.run.as_mut() {
+        run.fired.iter_mut().for_each(|f| *f = false);
+    }
+}
+
+fn script_ui(
+    mut contexts: EguiContexts,
+    mut runner: ResMut<ScriptRunner>,
+    mut config: ResMut<SimConfig>,
+    mut load_map_event: EventWriter<LoadMapEvent>,
+    mut reset_sim_event: EventWriter<ResetSimEvent>,
+    settings: Res<SimSettings>,
+) {
+    if !settings.is_show_menu {
+        return;
+    }
+
+    let ctx = contexts.ctx_mut();
+    egui::Window::new("Script")
+        .default_pos(egui::pos2(W - 700.0, 80.0))
+        .default_open(false)
+        .show(ctx, |ui| {
+            ui.horizontal(|ui| {
+                // Starts from a reset so the triggers count from zero
+                if ui.button("Load & Start").clicked() {
+                    match Script::load(SCRIPT_FILE).and_then(|s| Ok((s.load_map()?, s))) {
+                        Ok((layout, script)) => {
+                            if let Some(before) = runner.config_before.take() {
+                                *config = before;
+                            }
+                            runner.run = Some(ScriptRun::new(script));
+                            match layout {
+                                Some(layout) => load_map_event.send(LoadMapEvent { layout, reset: true }),
+                                None => reset_sim_event.send(ResetSimEvent),
+                            }
+                            println!("Loaded script from {}", SCRIPT_FILE);
+                        }
+                        Err(e) => eprintln!("Failed to load script: {}", e),
+                    }
+                }
+                if runner.run.is_some() && ui.button("Stop").clicked() {
+                    runner.run = None;
+                }
+            });
+
+            let Some(run) = &runner.run else {
+                ui.label(format!("No script, reads {}", SCRIPT_FILE));
+                return;
+            };
+            // Events that already fired are greyed out
+            for (event, fired) in run.script.events.iter().zip(run.fired.iter()) {
+                let actions: Vec<String> = event.actions.iter().map(|a| a.label()).collect();
+                let text = format!("{}: {}", event.trigger.label(), actions.join(", "));
+                ui.add_enabled(!*fired, egui::Label::new(text));
+            }
+        });
+}
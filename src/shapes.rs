@@ -0,0 +1,176 @@
+//! Shape tools of the map editor.
+//! Lines, rectangles and ellipses are dragged out, polygons are clicked vertex by
+//! vertex and closed on the first vertex or with Enter. While a shape is being drawn
+//! its outline is previewed at the cursor. Committed shapes become ordinary editor
+//! actions: outlines are brush strokes, filled rectangles and flood fills have their own.
+
+use crate::{
+    gui::{cursor_world_pos, EditorAction, EditorState, EditorTool},
+    map::BrushShape,
+    *,
+};
+use bevy::prelude::*;
+use bevy_pancam::PanCam;
+use std::f32::consts::TAU;
+
+pub struct ShapesPlugin;
+
+const PREVIEW_COLOR: Color = Color::rgb(1.5, 1.5, 1.5);
+const ERASE_COLOR: Color = Color::rgb(2.0, 0.5, 0.5);
+
+#[derive(PartialEq, Clone, Copy, Debug)]
+pub enum ShapeKind {
+    Line,
+    Rectangle,
+    FilledRectangle,
+    Ellipse,
+    Polygon,
+    FloodFill,
+}
+
+impl ShapeKind {
+    pub const ALL: [ShapeKind; 6] = [
+        ShapeKind::Line,
+        ShapeKind::Rectangle,
+        ShapeKind::FilledRectangle,
+        ShapeKind::Ellipse,
+        ShapeKind::Polygon,
+        ShapeKind::FloodFill,
+    ];
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            ShapeKind::Line => "Line",
+            ShapeKind::Rectangle => "Rectangle",
+            ShapeKind::FilledRectangle => "Filled Rect",
+            ShapeKind::Ellipse => "Ellipse",
+            ShapeKind::Polygon => "Polygon",
+            ShapeKind::FloodFill => "Flood Fill",
+        }
+    }
+
+    /// Drawn with the brush, the others ignore the brush size
+    pub fn is_outline(&self) -> bool {
+        matches!(self, ShapeKind::Line | ShapeKind::Rectangle | ShapeKind::Ellipse | ShapeKind::Polygon)
+    }
+}
+
+/// Shape being drawn with the shape tool
+#[derive(Resource, Default)]
+pub struct ShapeDraft {
+    /// Where the drag of a line, rectangle or ellipse started
+    pub drag_start: Option<Vec2>,
+    /// Polygon vertices placed so far
+    pub vertices: Vec<Vec2>,
+}
+
+impl ShapeDraft {
+    pub fn clear(&mut self) {
+        self.drag_start = None;
+        self.vertices.clear();
+    }
+}
+
+impl Plugin for ShapesPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(ShapeDraft::default())
+            .add_systems(Update, draw_shape_preview);
+    }
+}
+
+/// Outline of a dragged shape as a polyline, closed shapes repeat the first point
+pub fn drag_outline(kind: ShapeKind, start: Vec2, end: Vec2) -> Vec<Vec2> {
+    match kind {
+        ShapeKind::Line => vec![start, end],
+        ShapeKind::Rectangle | ShapeKind::FilledRectangle => vec![
+            start,
+            Vec2::new(end.x, start.y),
+            end,
+            Vec2::new(start.x, end.y),
+            start,
+        ],
+        ShapeKind::Ellipse => {
+            let center = (start + end) / 2.0;
+            let radii = (end - start).abs() / 2.0;
+            (0..=SHAPE_ELLIPSE_SEGMENTS)
+                .map(|i| {
+                    let angle = i as f32 / SHAPE_ELLIPSE_SEGMENTS as f32 * TAU;
+                    center + radii * Vec2::new(angle.cos(), angle.sin())
+                })
+                .collect()
+        }
+        ShapeKind::Polygon | ShapeKind::FloodFill => vec![],
+    }
+}
+
+/// Brush strokes along a polyline
+pub fn outline_actions(points: &[Vec2], editor_state: &EditorState) -> Vec<EditorAction> {
+    points
+        .windows(2)
+        .map(|segment| EditorAction::Stroke {
+            from: (segment[0].x, segment[0].y),
+            to: (segment[1].x, segment[1].y),
+            is_obstacle: !editor_state.is_shape_erase,
+            brush_size: editor_state.brush_size,
+            brush_shape: editor_state.brush_shape,
+        })
+        .collect()
+}
+
+/// Actions for a shape dragged from `start` to `end`
+pub fn drag_actions(start: Vec2, end: Vec2, editor_state: &EditorState) -> Vec<EditorAction> {
+    match editor_state.shape {
+        ShapeKind::FilledRectangle => vec![EditorAction::FillRect {
+            from: (start.x, start.y),
+            to: (end.x, end.y),
+            is_obstacle: !editor_state.is_shape_erase,
+        }],
+        kind => outline_actions(&drag_outline(kind, start, end), editor_state),
+    }
+}
+
+/// Closed outline through the polygon vertices
+pub fn polygon_outline(vertices: &[Vec2]) -> Vec<Vec2> {
+    let mut points = vertices.to_vec();
+    points.extend(vertices.first());
+    points
+}
+
+fn draw_shape_preview(
+    mut gizmos: Gizmos,
+    editor_state: Res<EditorState>,
+    draft: Res<ShapeDraft>,
+    windows: Query<&Window>,
+    camera_q: Query<(&Camera, &GlobalTransform), With<PanCam>>,
+) {
+    if editor_state.selected_tool != EditorTool::Shape {
+        return;
+    }
+    let Some(cursor) = cursor_world_pos(&windows, &camera_q) else {
+        return;
+    };
+    let color = if editor_state.is_shape_erase { ERASE_COLOR } else { PREVIEW_COLOR };
+
+    if let Some(start) = draft.drag_start {
+        gizmos.linestrip_2d(drag_outline(editor_state.shape, start, cursor), color);
+    }
+    if let Some(&first) = draft.vertices.first() {
+        let mut points = draft.vertices.clone();
+        points.push(cursor);
+        gizmos.linestrip_2d(points, color);
+        // Clicking inside this circle closes the polygon
+        gizmos.circle_2d(first, SHAPE_CLOSE_RADIUS, color);
+    }
+    // Outline shapes are painted with the brush, show how wide
+    if editor_state.shape.is_outline() {
+        let radius = editor_state.brush_size;
+        match editor_state.brush_shape {
+            BrushShape::Round => {
+                gizmos.circle_2d(cursor, radius, color);
+            }
+            BrushShape::Square => {
+                gizmos.rect_2d(cursor, 0.0, Vec2::splat(2.0 * radius), color);
+            }
+        }
+    }
+}
@@ -106,6 +106,11 @@ pub fn get_rand_unit_vec2() -> Vec2 {
     vec2(rand_vec3.x, rand_vec3.y)
 }
 
+// Same as above but driven by the caller's rng, so seeded runs are reproducible
+pub fn get_rand_unit_vec2_with<R: Rng>(rng: &mut R) -> Vec2 {
+    vec2(rng.gen_range(-1.0..1.0), rng.gen_range(-1.0..1.0)).normalize()
+}
+
 // Function to partition the array based on the pivot (max z value)
 fn partition(points: &mut [(i32, i32, f32)], low: usize, high: usize) -> usize {
     let pivot = points[high].2;
@@ -127,12 +132,18 @@ fn quickselect(points: &mut [(i32, i32, f32)], low: usize, high: usize, n: usize
     if low < high {
         let pivot_index = partition(points, low, high);
 
-        if pivot_index == n - 1 {
-            return;
-        } else if pivot_index > n - 1 {
+        // Done once the pivot lands at n - 1
+        if pivot_index > n - 1 {
             quickselect(points, low, pivot_index - 1, n);
-        } else {
+        } else if pivot_index < n - 1 {
             quickselect(points, pivot_index + 1, high, n);
         }
     }
 }
+
+/// Writes an RGBA8 buffer, row 0 at the top, as a png file
+pub fn save_png(path: &str, width: usize, height: usize, rgba: Vec<u8>) -> Result<(), String> {
+    let img = image::RgbaImage::from_raw(width as u32, height as u32, rgba)
+        .ok_or_else(|| format!("{}: buffer does not match {}x{}", path, width, height))?;
+    img.save(path).map_err(|e| format!("{}: {}", path, e))
+}